@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, File},
     io::{Seek, SeekFrom},
     path::PathBuf,
@@ -10,16 +10,59 @@ use clap::{
     error::{ErrorKind, Result},
     ArgMatches, Error,
 };
-use leviathan_ir::binary::OffsetTable;
+use leviathan_ir::{binary::OffsetTable, Span};
 use urban_common::bus::InstructionBus;
 use yansi::{Color, Paint};
 
 struct Disassembler {
     index: isize,
-    offsets: HashMap<usize, (char, String)>,
+    offsets: BTreeMap<usize, (char, String)>,
+    sources: BTreeMap<usize, (String, Span)>,
+    /// Contents of the source files, `None` if a file could not be read
+    files: HashMap<String, Option<String>>,
+    /// File and line of the last printed source line
+    line: Option<(String, usize)>,
 }
 
 impl Disassembler {
+    /// Prints the source line that produced the instruction at the current index.
+    fn source_line(&mut self) {
+        let Some((file, span)) = self.sources.get(&(self.index as usize)) else {
+            return;
+        };
+        let source = self
+            .files
+            .entry(file.clone())
+            .or_insert_with(|| fs::read_to_string(file).ok());
+        let Some(source) = source else {
+            println!("{}", Paint::new(format!("; {file}")).dimmed());
+            return;
+        };
+        let start = span.start.min(source.len());
+        if !source.is_char_boundary(start) {
+            return;
+        }
+        let line = source[..start].matches('\n').count() + 1;
+        if let Some((last_file, last_line)) = &self.line {
+            if last_file == file && *last_line == line {
+                return;
+            }
+        }
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        println!(
+            "{}",
+            Paint::new(format!(
+                "; {file}:{line}: {}",
+                source[line_start..line_end].trim()
+            ))
+            .dimmed()
+        );
+        self.line = Some((file.clone(), line));
+    }
+
     fn addr(&self, offset: isize) -> String {
         let addr = (self.index + offset) as usize;
         if let Some((_, name)) = self.offsets.get(&addr) {
@@ -573,12 +616,12 @@ impl InstructionBus for Disassembler {
 
 pub fn disasm(matches: &ArgMatches) -> Result<()> {
     let file: &PathBuf = matches.get_one("FILE").unwrap();
-    let offsets = if let Some(file) = matches.get_one::<PathBuf>("OFFSETS") {
+    let (offsets, sources) = if let Some(file) = matches.get_one::<PathBuf>("OFFSETS") {
         let offset_source = fs::read_to_string(file)?;
-        let OffsetTable { table } = OffsetTable::read_offset_key(&offset_source)?;
-        table
+        let OffsetTable { table, sources } = OffsetTable::read_offset_key(&offset_source)?;
+        (table, sources)
     } else {
-        HashMap::with_capacity(0)
+        (BTreeMap::new(), BTreeMap::new())
     };
     let file = File::open(file);
     let Ok(mut file) = file else {
@@ -586,7 +629,13 @@ pub fn disasm(matches: &ArgMatches) -> Result<()> {
     };
     file.seek(SeekFrom::Start(8))?;
     let entrypoint = file.read_u64::<LittleEndian>()? as usize;
-    let mut disasm = Disassembler { index: 0, offsets };
+    let mut disasm = Disassembler {
+        index: 0,
+        offsets,
+        sources,
+        files: HashMap::with_capacity(0),
+        line: None,
+    };
     let mut ac = ' ';
     while let Ok(insn) = file.read_u32::<LittleEndian>() {
         if disasm.index == entrypoint as isize {
@@ -625,6 +674,7 @@ pub fn disasm(matches: &ArgMatches) -> Result<()> {
                 );
             }
             'f' => {
+                disasm.source_line();
                 print!(
                     "{} {:>08x}:\t{:02x} {:02x} {:02x} {:02x}\t",
                     Paint::new("|").fg(Color::Yellow),
@@ -648,6 +698,7 @@ pub fn disasm(matches: &ArgMatches) -> Result<()> {
                 );
             }
             _ => {
+                disasm.source_line();
                 print!(
                     "{} {:>08x}:\t{:02x} {:02x} {:02x} {:02x}\t",
                     Paint::new("|").fg(Color::Red),
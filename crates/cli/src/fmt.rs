@@ -0,0 +1,73 @@
+use std::{
+    fs::{read_dir, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use clap::{
+    error::{ErrorKind, Result},
+    ArgMatches, Error,
+};
+use leviathan_compiler::format::format_source;
+
+/// Formats the given files, or all source files of the project, and returns the exit code.
+///
+/// In check mode no files are written and the exit code is 1 if any file is not formatted.
+pub fn fmt(matches: &ArgMatches) -> Result<i32> {
+    let check = matches.get_flag("check");
+    let mut files: Vec<PathBuf> = matches
+        .get_many("FILES")
+        .map(|files| files.cloned().collect())
+        .unwrap_or_default();
+    if files.is_empty() {
+        if !Path::new("src").is_dir() {
+            return Err(Error::raw(ErrorKind::Io, "Could not find src directory"));
+        }
+        collect_files(&mut files, Path::new("src"))?;
+        files.sort();
+    }
+    let mut code = 0;
+    for path in files {
+        let src = read_to_string(&path)?;
+        let Some(file) = path.to_str() else {
+            return Err(Error::raw(ErrorKind::InvalidUtf8, "File with invalid name"));
+        };
+        let name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let formatted = match format_source(name, file.to_string(), src.clone()) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for err in errors {
+                    err.report();
+                }
+                code = 1;
+                continue;
+            }
+        };
+        if formatted == src {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", path.display());
+            code = 1;
+        } else {
+            write(&path, formatted)?;
+        }
+    }
+    Ok(code)
+}
+
+/// Collects all `.lvt` files in a directory and its subdirectories.
+fn collect_files(files: &mut Vec<PathBuf>, dir: &Path) -> Result<()> {
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(files, &path)?;
+        } else if path.extension().is_some_and(|ext| ext == "lvt") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
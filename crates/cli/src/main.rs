@@ -1,11 +1,17 @@
 pub mod disasm;
+pub mod fmt;
+pub mod new;
 pub mod project;
+pub mod run;
 
-use std::path::PathBuf;
+use std::{path::PathBuf, process::exit};
 
 use clap::{arg, command, crate_version, value_parser, Command};
 use disasm::disasm;
-use project::build;
+use fmt::fmt;
+use new::{init, new};
+use project::{build, check};
+use run::run;
 
 const BUILD_DATE: &str = env!("BUILD_DATE");
 
@@ -15,10 +21,37 @@ fn main() {
         .subcommand_required(true)
         .subcommands([
             command!("version").alias("v").about("Shows the version"),
-            command!("build")
-                .alias("b")
-                .about("Build a project")
-                .arg(arg!(--"no-offsets").required(false)),
+            command!("new")
+                .about("Create a project in a new directory")
+                .args([
+                    arg!(<NAME>).required(true),
+                    arg!(--code "Use the code dialect").required(false),
+                ]),
+            command!("init")
+                .about("Create a project in the current directory")
+                .args([arg!(--code "Use the code dialect").required(false)]),
+            command!("build").alias("b").about("Build a project").args([
+                arg!(--"no-offsets").required(false),
+                arg!(--"deny-warnings" "Treat warnings as errors").required(false),
+                arg!(--"message-format" <FORMAT> "Print diagnostics as 'human' or 'json'")
+                    .required(false),
+            ]),
+            command!("check")
+                .alias("c")
+                .about("Check a project for errors without building it")
+                .args([
+                    arg!(--"deny-warnings" "Treat warnings as errors").required(false),
+                    arg!(--"message-format" <FORMAT> "Print diagnostics as 'human' or 'json'")
+                        .required(false),
+                ]),
+            command!("fmt")
+                .about("Format the source files of a project")
+                .args([
+                    arg!([FILES] ... "Format only these files")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(false),
+                    arg!(--check "Only check whether the files are formatted").required(false),
+                ]),
             command!("disasm")
                 .alias("d")
                 .about("Disassemble a binary")
@@ -30,18 +63,41 @@ fn main() {
                         .value_parser(value_parser!(PathBuf))
                         .required(false),
                 ]),
+            command!("run").alias("r").about("Run a binary").args([
+                arg!(<FILE>)
+                    .value_parser(value_parser!(PathBuf))
+                    .required(true),
+                arg!(<OFFSETS>)
+                    .value_parser(value_parser!(PathBuf))
+                    .required(false),
+            ]),
         ]);
     let matches = cmd.get_matches_mut();
     match matches.subcommand() {
         Some(("version", _)) => {
             println!("leviathan-cli version {} {BUILD_DATE}", crate_version!());
         }
+        Some(("new", matches)) => new(matches).unwrap_or_else(|err| err.format(&mut cmd).exit()),
+        Some(("init", matches)) => init(matches).unwrap_or_else(|err| err.format(&mut cmd).exit()),
         Some(("build", matches)) => {
-            build(matches).unwrap_or_else(|err| err.format(&mut cmd).exit())
+            let code = build(matches).unwrap_or_else(|err| err.format(&mut cmd).exit());
+            exit(code);
+        }
+        Some(("check", matches)) => {
+            let code = check(matches).unwrap_or_else(|err| err.format(&mut cmd).exit());
+            exit(code);
+        }
+        Some(("fmt", matches)) => {
+            let code = fmt(matches).unwrap_or_else(|err| err.format(&mut cmd).exit());
+            exit(code);
         }
         Some(("disasm", matches)) => {
             disasm(matches).unwrap_or_else(|err| err.format(&mut cmd).exit())
         }
+        Some(("run", matches)) => {
+            let code = run(matches).unwrap_or_else(|err| err.format(&mut cmd).exit());
+            exit(code);
+        }
         _ => unreachable!("clap should ensure we don't get here"),
     };
 }
@@ -0,0 +1,91 @@
+use std::{
+    fs::{create_dir_all, write},
+    path::Path,
+};
+
+use clap::{
+    error::{ErrorKind, Result},
+    ArgMatches, Error,
+};
+
+const GITIGNORE: &str = "/out\n";
+
+const ASM_MAIN: &str = r#"(mod asm)
+
+(static message "Hello world!\n")
+
+(-label main (do
+  (mov r0 1u) ; file descriptor of stdout
+  (ref r1 message) ; address of the message
+  (ldr r2 r1 -8) ; length of the message
+  (int 1u) ; write interrupt
+  (halt)
+))
+"#;
+
+const CODE_MAIN: &str = r#"(mod code)
+
+(use io)
+
+(fn main [] {
+  (print "Hello world!\n")
+})
+"#;
+
+const CODE_IO: &str = r#"(mod asm)
+
+(+label print [:message str] (do
+  (mov r1 r0) ; address of the message
+  (ldr r2 r1 -8) ; length of the message
+  (mov r0 1u) ; file descriptor of stdout
+  (int 1u) ; write interrupt
+))
+"#;
+
+/// Creates a project in a new directory.
+pub fn new(matches: &ArgMatches) -> Result<()> {
+    let dir: &String = matches.get_one("NAME").unwrap();
+    let dir = Path::new(dir);
+    let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+        return Err(Error::raw(ErrorKind::InvalidValue, "Invalid project name"));
+    };
+    create_project(dir, name, matches.get_flag("code"))
+}
+
+/// Creates a project in the current directory.
+pub fn init(matches: &ArgMatches) -> Result<()> {
+    let dir = Path::new(".").canonicalize()?;
+    let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+        return Err(Error::raw(ErrorKind::InvalidValue, "Invalid project name"));
+    };
+    create_project(Path::new("."), name, matches.get_flag("code"))
+}
+
+/// Writes the files of a hello world project, none of which may exist yet.
+fn create_project(dir: &Path, name: &str, code: bool) -> Result<()> {
+    let config = format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n");
+    let mut files = vec![
+        ("build.lvt.toml", config.as_str()),
+        (".gitignore", GITIGNORE),
+    ];
+    if code {
+        files.push(("src/main.lvt", CODE_MAIN));
+        files.push(("src/io.lvt", CODE_IO));
+    } else {
+        files.push(("src/main.lvt", ASM_MAIN));
+    }
+    for (path, _) in &files {
+        let path = dir.join(path);
+        if path.exists() {
+            return Err(Error::raw(
+                ErrorKind::Io,
+                format!("{} already exists", path.display()),
+            ));
+        }
+    }
+    create_dir_all(dir.join("src"))?;
+    for (path, contents) in files {
+        write(dir.join(path), contents)?;
+    }
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use std::{
-    fs::{create_dir_all, read_dir, read_to_string, File, ReadDir},
-    path::PathBuf,
-    process::exit,
+    collections::HashMap,
+    fs::{create_dir_all, read_dir, read_to_string, write},
+    io::{stderr, stdout},
+    path::{Path, PathBuf},
 };
 
 use clap::{
@@ -9,14 +10,22 @@ use clap::{
     ArgMatches, Error,
 };
 use leviathan_compiler::{
-    compiler::{CompileTask, Status},
-    parser::{ast::build_ast, tokenizer::tokenize},
+    build::Build,
+    compiler::warning::{Lint, LintLevel},
+    diagnostics::Diagnostics,
+    util::message::MessageFormat,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 pub struct Config {
     pub package: PackageConfig,
+    /// Maps lint names to `allow`, `warn` or `deny`
+    #[serde(default)]
+    pub lints: HashMap<String, String>,
+    /// Maps package names to other Leviathan projects
+    #[serde(default)]
+    pub dependencies: HashMap<String, DependencyConfig>,
 }
 
 #[derive(Deserialize)]
@@ -27,154 +36,560 @@ pub struct PackageConfig {
     pub binary_path: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct DependencyConfig {
+    /// The project directory, relative to the depending project
+    pub path: String,
+}
+
+/// The contents of `build.lvt.lock`, which records the resolved dependencies.
+#[derive(Serialize)]
+struct LockFile {
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Serialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    path: String,
+}
+
+struct Dependency {
+    name: String,
+    version: String,
+    /// The project directory, relative to the built project
+    path: PathBuf,
+    canonical_path: PathBuf,
+}
+
 struct SourceFile {
-    main: bool,
     module_name: String,
     path: PathBuf,
 }
 
-enum LoadError {
-    Parse(leviathan_compiler::parser::error::Error),
-    Compile(leviathan_compiler::compiler::error::Error),
+/// A project whose dependencies were resolved and whose source files were found.
+struct Project {
+    config: Config,
+    dependencies: Vec<Dependency>,
+    source_files: Vec<SourceFile>,
 }
 
-pub fn build(matches: &ArgMatches) -> Result<()> {
-    let config = read_to_string("build.lvt.toml");
-    if let Err(err) = config {
-        if let std::io::ErrorKind::NotFound = err.kind() {
-            return Err(Error::raw(ErrorKind::Io, "Could not find a build.lvt.toml"));
-        }
-        return Err(err.into());
+/// Builds the project in the current directory and returns the exit code.
+pub fn build(matches: &ArgMatches) -> Result<i32> {
+    let root = Path::new("");
+    let project = read_project(root)?;
+    let build = create_build(&project, matches)?;
+    let output = build.offsets(!matches.get_flag("no-offsets")).compile();
+    report(&output.diagnostics)?;
+    let Some(binary) = output.binary else {
+        return Ok(1);
+    };
+    write_lock_file(root, &project.dependencies)?;
+    let config = project.config;
+    create_dir_all("out")?;
+    let binary_path = config
+        .package
+        .binary_path
+        .unwrap_or_else(|| format!("out/{}.bin", config.package.name));
+    write(binary_path, binary)?;
+    if let Some(offsets) = output.offsets {
+        write(format!("out/{}.map", config.package.name), offsets)?;
     }
-    let config = config.unwrap();
-    let config = toml::from_str(&config);
-    if let Err(err) = config {
-        return Err(Error::raw(ErrorKind::Format, err));
+    Ok(0)
+}
+
+/// Checks the project in the current directory for errors without writing any files.
+pub fn check(matches: &ArgMatches) -> Result<i32> {
+    let project = read_project(Path::new(""))?;
+    let diagnostics = create_build(&project, matches)?.check();
+    report(&diagnostics)?;
+    if diagnostics.has_errors() {
+        return Ok(1);
     }
-    let config: Config = config.unwrap();
-    let source_dir = read_dir("src");
-    if let Err(err) = source_dir {
-        if let std::io::ErrorKind::NotFound = err.kind() {
-            return Err(Error::raw(ErrorKind::Io, "Could not find src directory"));
-        }
-        return Err(err.into());
+    Ok(0)
+}
+
+/// Prints JSON diagnostics to stdout and all other output to stderr.
+fn report(diagnostics: &Diagnostics) -> Result<()> {
+    match diagnostics.format {
+        MessageFormat::Human => diagnostics.report(&mut stderr())?,
+        MessageFormat::Json => diagnostics.report(&mut stdout())?,
+    }
+    if let Some(summary) = diagnostics.summary() {
+        eprintln!("{summary}");
+    }
+    Ok(())
+}
+
+/// Reads the config of the project in `root`, resolves its dependencies and finds all source
+/// files.
+fn read_project(root: &Path) -> Result<Project> {
+    let config = read_config(&root.join("build.lvt.toml"))?;
+    let mut dependencies = Vec::with_capacity(0);
+    resolve_dependencies(&mut dependencies, root, Path::new(""), &config)?;
+    let canonical_root = root.join(".").canonicalize()?;
+    if let Some(dependency) = dependencies
+        .iter()
+        .find(|dep| dep.canonical_path == canonical_root)
+    {
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "The package cannot depend on itself as '{}'",
+                dependency.name
+            ),
+        ));
+    }
+    let source_dir = root.join("src");
+    if !source_dir.is_dir() {
+        return Err(Error::raw(ErrorKind::Io, "Could not find src directory"));
     }
     let mut source_files = Vec::with_capacity(0);
     let mut main_found = false;
-    collect_dir(&mut source_files, source_dir.unwrap(), &mut main_found)?;
+    collect_dir(&mut source_files, &source_dir, "", &mut main_found)?;
+    for source_file in &source_files {
+        let package = source_file.module_name.split("::").next().unwrap();
+        if dependencies.iter().any(|dep| dep.name == package) {
+            return Err(Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "The module '{}' conflicts with the dependency '{package}'",
+                    source_file.module_name
+                ),
+            ));
+        }
+    }
+    for dependency in &dependencies {
+        let source_dir = root.join(&dependency.path).join("src");
+        if !source_dir.is_dir() {
+            return Err(Error::raw(
+                ErrorKind::Io,
+                format!(
+                    "Could not find src directory of dependency '{}'",
+                    dependency.name
+                ),
+            ));
+        }
+        // Dependencies are libraries, so their main modules are not entrypoints
+        collect_dir(&mut source_files, &source_dir, &dependency.name, &mut false)?;
+    }
+    // Module indices follow the include order, which must not depend on the file system
+    source_files.sort_by(|a, b| a.path.cmp(&b.path));
     if !main_found {
         return Err(Error::raw(
             ErrorKind::MissingRequiredArgument,
             "No main module was found",
         ));
     }
-    let mut task = CompileTask::with_offsets(!matches.get_flag("no-offsets"));
-    let mut errors = Vec::with_capacity(0);
-    for source_file in source_files {
+    Ok(Project {
+        config,
+        dependencies,
+        source_files,
+    })
+}
+
+/// Configures a build of the project and reads its source files.
+fn create_build(project: &Project, matches: &ArgMatches) -> Result<Build> {
+    let mut build = Build::new().deny_warnings(matches.get_flag("deny-warnings"));
+    for dependency in &project.dependencies {
+        build = build.package(&dependency.name);
+    }
+    for (name, level) in &project.config.lints {
+        let Some(lint) = Lint::from_name(name) else {
+            return Err(Error::raw(
+                ErrorKind::InvalidValue,
+                format!("Unknown lint '{name}'"),
+            ));
+        };
+        let Some(level) = LintLevel::from_name(level) else {
+            return Err(Error::raw(
+                ErrorKind::InvalidValue,
+                format!("Invalid level '{level}' for lint '{name}'"),
+            ));
+        };
+        build = build.lint(lint, level);
+    }
+    if let Some(format) = matches.get_one::<String>("message-format") {
+        let Some(format) = MessageFormat::from_name(format) else {
+            return Err(Error::raw(
+                ErrorKind::InvalidValue,
+                format!("Invalid message format '{format}'"),
+            ));
+        };
+        build = build.message_format(format);
+    }
+    for source_file in &project.source_files {
         let source = read_to_string(&source_file.path)?;
         let Some(path) = source_file.path.to_str() else {
             return Err(Error::raw(ErrorKind::InvalidUtf8, "File with invalid name"));
         };
-        let result = tokenize(source_file.module_name, path.to_string(), source);
-        if let Err(err) = result {
-            errors.push(LoadError::Parse(err));
-            continue;
-        }
-        let ast = build_ast(result.unwrap());
-        if let Err(err) = ast {
-            errors.push(LoadError::Parse(err));
-            continue;
-        }
-        let result = task.include(ast.unwrap(), source_file.main);
-        if let Err(err) = result {
-            errors.push(LoadError::Compile(err));
-            task.status = Status::Open;
-            continue;
+        build = build.source(&source_file.module_name, path, source);
+    }
+    Ok(build)
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    let config = read_to_string(path);
+    if let Err(err) = config {
+        if let std::io::ErrorKind::NotFound = err.kind() {
+            return Err(Error::raw(
+                ErrorKind::Io,
+                format!("Could not find {}", path.display()),
+            ));
         }
+        return Err(err.into());
     }
-    if !errors.is_empty() {
-        for err in errors {
-            match err {
-                LoadError::Parse(err) => err.report(),
-                LoadError::Compile(err) => err.report(),
+    let config = toml::from_str(&config.unwrap());
+    if let Err(err) = config {
+        return Err(Error::raw(ErrorKind::Format, err));
+    }
+    Ok(config.unwrap())
+}
+
+/// Resolves the dependencies of the project in `dir` and of their dependencies.
+///
+/// `dir` is relative to the built project in `root`. All packages share one namespace, so a
+/// name must always refer to the same project.
+fn resolve_dependencies(
+    dependencies: &mut Vec<Dependency>,
+    root: &Path,
+    dir: &Path,
+    config: &Config,
+) -> Result<()> {
+    let mut names: Vec<&String> = config.dependencies.keys().collect();
+    names.sort();
+    for name in names {
+        let path = dir.join(&config.dependencies[name].path);
+        let Ok(canonical_path) = root.join(&path).canonicalize() else {
+            return Err(Error::raw(
+                ErrorKind::Io,
+                format!("Could not find dependency '{name}' at {}", path.display()),
+            ));
+        };
+        if let Some(dependency) = dependencies.iter().find(|dep| dep.name == *name) {
+            if dependency.canonical_path != canonical_path {
+                return Err(Error::raw(
+                    ErrorKind::InvalidValue,
+                    format!(
+                        "The dependency '{name}' refers to both {} and {}",
+                        dependency.path.display(),
+                        path.display()
+                    ),
+                ));
             }
+            continue;
         }
-        exit(1);
+        check_name(name)?;
+        let dependency_config = read_config(&root.join(&path).join("build.lvt.toml"))?;
+        dependencies.push(Dependency {
+            name: name.clone(),
+            version: dependency_config.package.version.clone(),
+            path: path.clone(),
+            canonical_path,
+        });
+        resolve_dependencies(dependencies, root, &path, &dependency_config)?;
+    }
+    Ok(())
+}
+
+/// Records the resolved dependencies in the `build.lvt.lock` of the project in `root`.
+///
+/// Projects without dependencies have nothing to record and get no lock file.
+fn write_lock_file(root: &Path, dependencies: &[Dependency]) -> Result<()> {
+    if dependencies.is_empty() {
+        return Ok(());
     }
-    if let Err(err) = task.compile() {
-        err.abort();
+    let lock_file = LockFile {
+        package: dependencies
+            .iter()
+            .map(|dependency| LockedPackage {
+                name: dependency.name.clone(),
+                version: dependency.version.clone(),
+                path: dependency.path.to_string_lossy().into_owned(),
+            })
+            .collect(),
     };
-    if let Err(err) = task.filter() {
-        err.abort();
+    let lock_file = toml::to_string(&lock_file);
+    let Ok(lock_file) = lock_file else {
+        return Err(Error::raw(ErrorKind::Format, lock_file.unwrap_err()));
     };
-    create_dir_all("out")?;
-    let mut binary = File::create(
-        config
-            .package
-            .binary_path
-            .unwrap_or_else(|| format!("out/{}.bin", config.package.name)),
+    write(
+        root.join("build.lvt.lock"),
+        format!("# Generated by lvt build, records the resolved dependencies\n{lock_file}"),
     )?;
-    let mut offset_out = if !matches.get_flag("no-offsets") {
-        Some(File::create(format!("out/{}.map", config.package.name))?)
-    } else {
-        None
-    };
-    if let Err(err) = task.assemble(&mut binary, offset_out.as_mut()) {
-        err.abort();
-    };
     Ok(())
 }
 
+/// Collects the source files of a directory, prefixing their module names with `module_path`.
 fn collect_dir(
     source_files: &mut Vec<SourceFile>,
-    source_dir: ReadDir,
+    source_dir: &Path,
+    module_path: &str,
     main_found: &mut bool,
 ) -> Result<()> {
-    for entry in source_dir {
+    for entry in read_dir(source_dir)? {
         let entry = entry?;
         let type_ = entry.file_type()?;
+        let os_file_name = entry.file_name();
+        let Some(file_name) = os_file_name.to_str() else {
+            return Err(Error::raw(ErrorKind::InvalidUtf8, "File with invalid name"));
+        };
         if type_.is_file() {
-            let os_file_name = entry.file_name();
-            let Some(file_name) = os_file_name.to_str() else {
-                return Err(Error::raw(ErrorKind::InvalidUtf8, "File with invalid name"));
-            };
             let Some(stripped_name) = file_name.strip_suffix(".lvt") else {
                 continue;
             };
-            if stripped_name.is_empty() {
-                return Err(Error::raw(ErrorKind::Io, "File has empty name"));
-            }
-            for c in stripped_name.chars() {
-                match c {
-                    '#' | ';' | '"' | '\\' | '(' | ')' | '[' | ']' | '{' | '}' => {
-                        return Err(Error::raw(
-                            ErrorKind::Io,
-                            format!("File with invalid character '{c}' in name"),
-                        ));
-                    }
-                    c if c.is_whitespace() => {
-                        return Err(Error::raw(ErrorKind::Io, "File with whitespace in name"));
-                    }
-                    _ => {}
-                }
-            }
+            check_name(stripped_name)?;
             let path = entry.path();
-            let main = stripped_name == "main";
-            if main {
+            // Only the main module at the root of the source directory is the entrypoint
+            if module_path.is_empty() && stripped_name == "main" {
                 *main_found = true;
             }
             source_files.push(SourceFile {
-                main,
-                module_name: stripped_name.to_string(),
+                module_name: join_module_path(module_path, stripped_name),
                 path,
             });
             continue;
         }
         if type_.is_dir() {
-            let source_dir = read_dir(entry.path())?;
-            collect_dir(source_files, source_dir, main_found)?;
+            check_name(file_name)?;
+            let module_path = join_module_path(module_path, file_name);
+            collect_dir(source_files, &entry.path(), &module_path, main_found)?;
             continue;
         }
     }
     Ok(())
 }
+
+/// Checks that a file or directory name can be used as part of a module path.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::raw(ErrorKind::Io, "File has empty name"));
+    }
+    for c in name.chars() {
+        match c {
+            '#' | ';' | '"' | '\\' | '(' | ')' | '[' | ']' | '{' | '}' | ':' => {
+                return Err(Error::raw(
+                    ErrorKind::Io,
+                    format!("File with invalid character '{c}' in name"),
+                ));
+            }
+            c if c.is_whitespace() => {
+                return Err(Error::raw(ErrorKind::Io, "File with whitespace in name"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Joins the module path of a directory and a name with `::`.
+fn join_module_path(module_path: &str, name: &str) -> String {
+    if module_path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{module_path}::{name}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all, write},
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use super::{check_name, read_project, write_lock_file, Project};
+
+    const MAIN: &str = "(mod asm)\n(-label main (do\n  (halt)\n))\n";
+
+    /// Creates a fresh directory for the test containing the files.
+    fn workspace(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = temp_dir().join(format!("lvt-{}-{test}", process::id()));
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        for (path, contents) in files {
+            let path = dir.join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn config(name: &str, dependencies: &[(&str, &str)]) -> String {
+        let mut config = format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n");
+        config.push_str("\n[dependencies]\n");
+        for (name, path) in dependencies {
+            config.push_str(&format!("{name} = {{ path = \"{path}\" }}\n"));
+        }
+        config
+    }
+
+    fn error(root: &Path) -> String {
+        let Err(err) = read_project(root) else {
+            panic!("{} was read successfully", root.display());
+        };
+        err.to_string()
+    }
+
+    fn module_names(project: &Project) -> Vec<&str> {
+        let mut names: Vec<_> = project
+            .source_files
+            .iter()
+            .map(|source_file| source_file.module_name.as_str())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_module_paths() {
+        let dir = workspace(
+            "module-paths",
+            &[
+                ("build.lvt.toml", &config("app", &[])),
+                ("src/main.lvt", MAIN),
+                ("src/io/file.lvt", ""),
+                ("src/io/net/tcp.lvt", ""),
+                ("src/io/main.lvt", ""),
+                ("src/notes.txt", ""),
+            ],
+        );
+        let project = read_project(&dir).unwrap();
+        assert_eq!(
+            module_names(&project),
+            ["io::file", "io::main", "io::net::tcp", "main"]
+        );
+        remove_dir_all(dir).unwrap();
+        // A main module in a subdirectory is no entrypoint
+        let dir = workspace(
+            "nested-main",
+            &[
+                ("build.lvt.toml", &config("app", &[])),
+                ("src/io/main.lvt", ""),
+            ],
+        );
+        assert!(error(&dir).contains("No main module was found"));
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("file-name_1").is_ok());
+        assert!(check_name("ä").is_ok());
+        for name in ["", "a b", "a:b", "a(b", "a#b", "a\"b"] {
+            assert!(check_name(name).is_err(), "{name}");
+        }
+        let dir = workspace(
+            "invalid-name",
+            &[
+                ("build.lvt.toml", &config("app", &[])),
+                ("src/main.lvt", MAIN),
+                ("src/a b.lvt", ""),
+            ],
+        );
+        assert!(error(&dir).contains("whitespace"));
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let dir = workspace(
+            "dependencies",
+            &[
+                ("app/build.lvt.toml", &config("app", &[("lib", "../lib")])),
+                ("app/src/main.lvt", MAIN),
+                ("lib/build.lvt.toml", &config("lib", &[("util", "../util")])),
+                ("lib/src/main.lvt", ""),
+                ("util/build.lvt.toml", &config("util", &[("lib", "../lib")])),
+                ("util/src/str.lvt", ""),
+            ],
+        );
+        let project = read_project(&dir.join("app")).unwrap();
+        let dependencies: Vec<_> = project
+            .dependencies
+            .iter()
+            .map(|dependency| (dependency.name.as_str(), dependency.path.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            dependencies,
+            [("lib", "../lib"), ("util", "../lib/../util")]
+        );
+        assert_eq!(module_names(&project), ["lib::main", "main", "util::str"]);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_dependency_conflict() {
+        let dir = workspace(
+            "dependency-conflict",
+            &[
+                (
+                    "app/build.lvt.toml",
+                    &config("app", &[("a", "../a"), ("b", "../b")]),
+                ),
+                ("app/src/main.lvt", MAIN),
+                ("a/build.lvt.toml", &config("a", &[])),
+                ("b/build.lvt.toml", &config("b", &[("a", "../c")])),
+                ("c/build.lvt.toml", &config("c", &[])),
+            ],
+        );
+        assert!(error(&dir.join("app")).contains("The dependency 'a' refers to both"));
+        remove_dir_all(dir).unwrap();
+        let dir = workspace(
+            "module-conflict",
+            &[
+                ("app/build.lvt.toml", &config("app", &[("lib", "../lib")])),
+                ("app/src/main.lvt", MAIN),
+                ("app/src/lib/io.lvt", ""),
+                ("lib/build.lvt.toml", &config("lib", &[])),
+                ("lib/src/io.lvt", ""),
+            ],
+        );
+        assert!(error(&dir.join("app"))
+            .contains("The module 'lib::io' conflicts with the dependency 'lib'"));
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_self_dependency() {
+        let dir = workspace(
+            "self-dependency",
+            &[
+                ("app/build.lvt.toml", &config("app", &[("me", "../app")])),
+                ("app/src/main.lvt", MAIN),
+            ],
+        );
+        assert!(error(&dir.join("app")).contains("The package cannot depend on itself as 'me'"));
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_lock_file() {
+        let dir = workspace(
+            "lock-file",
+            &[
+                ("app/build.lvt.toml", &config("app", &[("lib", "../lib")])),
+                ("app/src/main.lvt", MAIN),
+                ("lib/build.lvt.toml", &config("lib", &[])),
+                ("lib/src/io.lvt", ""),
+            ],
+        );
+        let root = dir.join("app");
+        let project = read_project(&root).unwrap();
+        write_lock_file(&root, &project.dependencies).unwrap();
+        assert_eq!(
+            read_to_string(root.join("build.lvt.lock")).unwrap(),
+            "# Generated by lvt build, records the resolved dependencies\n\
+             [[package]]\nname = \"lib\"\nversion = \"0.1.0\"\npath = \"../lib\"\n"
+        );
+        // Projects without dependencies get no lock file
+        let root = dir.join("lib");
+        write_lock_file(&root, &[]).unwrap();
+        assert!(!root.join("build.lvt.lock").exists());
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,905 @@
+use std::{
+    fs,
+    io::{stderr, stdout, Write},
+    path::PathBuf,
+};
+
+use clap::{
+    error::{ErrorKind, Result},
+    ArgMatches, Error,
+};
+use leviathan_ir::binary::OffsetTable;
+use urban_common::bus::InstructionBus;
+
+const HEADER_LENGTH: usize = 16;
+const ENTRYPOINT_OFFSET: usize = 8;
+const STACK_SIZE: usize = 1 << 20;
+const STACK_POINTER: usize = 31;
+/// Exits the process with the code in register 0
+const INT_EXIT: u64 = 0;
+/// Writes register 2 bytes starting at the address in register 1 to the file descriptor in
+/// register 0
+const INT_WRITE: u64 = 1;
+
+/// Interpreter for the instructions emitted by `Binary::assemble`.
+///
+/// The program is loaded at address 0 followed by the stack, register 31 starts at the end
+/// of the stack. Return addresses are kept on a separate callstack. The write interrupt and
+/// `dbg` go to `stdout` and `stderr`.
+struct Interpreter<O: Write, E: Write> {
+    regs: [u64; 32],
+    memory: Vec<u8>,
+    callstack: Vec<usize>,
+    pc: usize,
+    next_pc: usize,
+    exit: Option<i32>,
+    fault: Option<String>,
+    stdout: O,
+    stderr: E,
+}
+
+impl<O: Write, E: Write> Interpreter<O, E> {
+    /// Loads a binary, or returns `None` if it is not an executable.
+    fn load_binary(binary: &[u8], stdout: O, stderr: E) -> Option<Self> {
+        if binary.len() < HEADER_LENGTH || &binary[..4] != b"\0urb" {
+            return None;
+        }
+        let mut entrypoint = [0; 8];
+        entrypoint.copy_from_slice(&binary[ENTRYPOINT_OFFSET..HEADER_LENGTH]);
+        let entrypoint = u64::from_le_bytes(entrypoint) as usize;
+        let mut memory = binary[HEADER_LENGTH..].to_vec();
+        memory.resize(memory.len() + STACK_SIZE, 0);
+        let mut regs = [0; 32];
+        regs[STACK_POINTER] = memory.len() as u64;
+        Some(Self {
+            regs,
+            memory,
+            callstack: Vec::with_capacity(0),
+            pc: entrypoint,
+            next_pc: entrypoint,
+            exit: None,
+            fault: None,
+            stdout,
+            stderr,
+        })
+    }
+
+    fn run(&mut self) -> std::result::Result<i32, String> {
+        loop {
+            if let Some(code) = self.exit {
+                return Ok(code);
+            }
+            if let Some(fault) = self.fault.take() {
+                return Err(fault);
+            }
+            let Some(insn) = self.load(self.pc as u64, 4) else {
+                return Err(self.fault.take().unwrap());
+            };
+            self.next_pc = self.pc + 4;
+            self.process(insn as u32);
+            self.pc = self.next_pc;
+        }
+    }
+
+    fn fail(&mut self, message: impl Into<String>) {
+        if self.fault.is_none() {
+            self.fault = Some(message.into());
+        }
+    }
+
+    fn load(&mut self, addr: u64, size: usize) -> Option<u64> {
+        let addr = addr as usize;
+        let Some(bytes) = self.memory.get(addr..addr.wrapping_add(size)) else {
+            self.fail(format!("Invalid read of {size} bytes at 0x{addr:08x}"));
+            return None;
+        };
+        let mut value = [0; 8];
+        value[..size].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(value))
+    }
+
+    fn store(&mut self, addr: u64, size: usize, value: u64) {
+        let addr = addr as usize;
+        let Some(bytes) = self.memory.get_mut(addr..addr.wrapping_add(size)) else {
+            self.fail(format!("Invalid write of {size} bytes at 0x{addr:08x}"));
+            return;
+        };
+        bytes.copy_from_slice(&value.to_le_bytes()[..size]);
+    }
+
+    /// Returns the address `offset` words away from the current instruction.
+    fn relative(&self, offset: i64) -> u64 {
+        (self.pc as i64 + offset * 4) as u64
+    }
+
+    fn jump(&mut self, addr: u64, link: bool) {
+        if link {
+            self.callstack.push(self.pc + 4);
+        }
+        self.next_pc = addr as usize;
+    }
+
+    fn branch_if(&mut self, insn: u32, cond: fn(i64) -> bool) {
+        if cond(self.regs[reg(insn, 22)] as i64) {
+            self.next_pc = self.relative(signed_immediate::<22>(insn, 0)) as usize;
+        }
+    }
+
+    fn immediate_op(&mut self, insn: u32, op: fn(u64, u64) -> Option<u64>) {
+        let value = op(self.regs[reg(insn, 5)], immediate::<17>(insn, 10));
+        self.set_or_fail(reg(insn, 0), value);
+    }
+
+    fn signed_immediate_op(&mut self, insn: u32, op: fn(i64, i64) -> Option<i64>) {
+        let value = op(
+            self.regs[reg(insn, 5)] as i64,
+            signed_immediate::<17>(insn, 10),
+        );
+        self.set_or_fail(reg(insn, 0), value.map(|value| value as u64));
+    }
+
+    fn reg_op(&mut self, insn: u32, op: fn(u64, u64) -> Option<u64>) {
+        let value = op(self.regs[reg(insn, 5)], self.regs[reg(insn, 10)]);
+        self.set_or_fail(reg(insn, 0), value);
+    }
+
+    fn signed_reg_op(&mut self, insn: u32, op: fn(i64, i64) -> Option<i64>) {
+        let value = op(
+            self.regs[reg(insn, 5)] as i64,
+            self.regs[reg(insn, 10)] as i64,
+        );
+        self.set_or_fail(reg(insn, 0), value.map(|value| value as u64));
+    }
+
+    fn float_op(&mut self, insn: u32, op: fn(f64, f64) -> f64) {
+        let value = op(
+            f64::from_bits(self.regs[reg(insn, 5)]),
+            f64::from_bits(self.regs[reg(insn, 10)]),
+        );
+        self.regs[reg(insn, 0)] = value.to_bits();
+    }
+
+    fn set_or_fail(&mut self, dst: usize, value: Option<u64>) {
+        match value {
+            Some(value) => self.regs[dst] = value,
+            None => self.fail("Division by zero"),
+        }
+    }
+
+    fn load_op(&mut self, insn: u32, size: usize) {
+        let addr = self.regs[reg(insn, 5)].wrapping_add(signed_immediate::<11>(insn, 10) as u64);
+        if let Some(value) = self.load(addr, size) {
+            self.regs[reg(insn, 0)] = value;
+        }
+    }
+
+    fn store_op(&mut self, insn: u32, size: usize) {
+        let addr = self.regs[reg(insn, 0)].wrapping_add(signed_immediate::<11>(insn, 10) as u64);
+        self.store(addr, size, self.regs[reg(insn, 5)]);
+    }
+
+    /// Branches to the address in a register, `ld` loads the target from that address.
+    ///
+    /// The program is loaded at address 0, so adding the base offset has no effect.
+    fn branch_reg(&mut self, insn: u32, link: bool, ld: bool) {
+        let mut addr = self.regs[reg(insn, 0)];
+        if ld {
+            let Some(target) = self.load(addr, 8) else {
+                return;
+            };
+            addr = target;
+        }
+        self.jump(addr, link);
+    }
+
+    fn interrupt(&mut self, id: u64) {
+        match id {
+            INT_EXIT => self.exit = Some(self.regs[0] as i32),
+            INT_WRITE => {
+                let addr = self.regs[1] as usize;
+                let len = self.regs[2] as usize;
+                let Some(bytes) = self.memory.get(addr..addr.wrapping_add(len)) else {
+                    self.fail(format!("Invalid read of {len} bytes at 0x{addr:08x}"));
+                    return;
+                };
+                let result = match self.regs[0] {
+                    1 => self.stdout.write_all(bytes),
+                    2 => self.stderr.write_all(bytes),
+                    fd => {
+                        self.fail(format!("Invalid file descriptor {fd}"));
+                        return;
+                    }
+                };
+                if let Err(err) = result {
+                    self.fail(err.to_string());
+                }
+            }
+            _ => self.fail(format!("Unknown interrupt {id}")),
+        }
+    }
+}
+
+impl<O: Write, E: Write> InstructionBus for Interpreter<O, E> {
+    fn l0_add(&mut self, insn: u32) {
+        self.immediate_op(insn, |a, b| Some(a.wrapping_add(b)));
+    }
+
+    fn l0_sub(&mut self, insn: u32) {
+        self.immediate_op(insn, |a, b| Some(a.wrapping_sub(b)));
+    }
+
+    fn l0_mul(&mut self, insn: u32) {
+        self.immediate_op(insn, |a, b| Some(a.wrapping_mul(b)));
+    }
+
+    fn l0_div(&mut self, insn: u32) {
+        self.immediate_op(insn, u64::checked_div);
+    }
+
+    fn l0_rem(&mut self, insn: u32) {
+        self.immediate_op(insn, u64::checked_rem);
+    }
+
+    fn l0_divs(&mut self, insn: u32) {
+        self.signed_immediate_op(insn, i64::checked_div);
+    }
+
+    fn l0_rems(&mut self, insn: u32) {
+        self.signed_immediate_op(insn, i64::checked_rem);
+    }
+
+    fn l0_ldr(&mut self, insn: u32) {
+        let addr = self.relative(signed_immediate::<22>(insn, 5));
+        if let Some(value) = self.load(addr, 8) {
+            self.regs[reg(insn, 0)] = value;
+        }
+    }
+
+    fn l0_str(&mut self, insn: u32) {
+        let addr = self.relative(signed_immediate::<22>(insn, 0));
+        self.store(addr, 8, self.regs[reg(insn, 22)]);
+    }
+
+    fn l0_mov(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = immediate::<22>(insn, 5);
+    }
+
+    fn l0_movs(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = signed_immediate::<22>(insn, 5) as u64;
+    }
+
+    fn l0_branch(&mut self, insn: u32) {
+        self.jump(self.relative(signed_immediate::<27>(insn, 0)), false);
+    }
+
+    fn l0_branch_l(&mut self, insn: u32) {
+        self.jump(self.relative(signed_immediate::<27>(insn, 0)), true);
+    }
+
+    fn l0_branch_ld(&mut self, insn: u32) {
+        if let Some(addr) = self.load(self.relative(signed_immediate::<27>(insn, 0)), 8) {
+            self.jump(addr, false);
+        }
+    }
+
+    fn l0_branch_l_ld(&mut self, insn: u32) {
+        if let Some(addr) = self.load(self.relative(signed_immediate::<27>(insn, 0)), 8) {
+            self.jump(addr, true);
+        }
+    }
+
+    fn l0_branch_eq(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value == 0);
+    }
+
+    fn l0_branch_ne(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value != 0);
+    }
+
+    fn l0_branch_lt(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value < 0);
+    }
+
+    fn l0_branch_gt(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value > 0);
+    }
+
+    fn l0_branch_le(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value <= 0);
+    }
+
+    fn l0_branch_ge(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value >= 0);
+    }
+
+    fn l0_branch_zr(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value == 0);
+    }
+
+    fn l0_branch_nz(&mut self, insn: u32) {
+        self.branch_if(insn, |value| value != 0);
+    }
+
+    fn l0_lea(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = self.relative(signed_immediate::<22>(insn, 5));
+    }
+
+    fn l1_shl(&mut self, insn: u32) {
+        let value = self.regs[reg(insn, 5)].wrapping_shl(immediate::<11>(insn, 10) as u32);
+        self.regs[reg(insn, 0)] = value;
+    }
+
+    fn l1_shr(&mut self, insn: u32) {
+        let value = self.regs[reg(insn, 5)].wrapping_shr(immediate::<11>(insn, 10) as u32);
+        self.regs[reg(insn, 0)] = value;
+    }
+
+    fn l1_shrs(&mut self, insn: u32) {
+        let value = (self.regs[reg(insn, 5)] as i64).wrapping_shr(immediate::<11>(insn, 10) as u32);
+        self.regs[reg(insn, 0)] = value as u64;
+    }
+
+    fn l1_ldr(&mut self, insn: u32) {
+        self.load_op(insn, 8);
+    }
+
+    fn l1_ldrb(&mut self, insn: u32) {
+        self.load_op(insn, 1);
+    }
+
+    fn l1_ldrh(&mut self, insn: u32) {
+        self.load_op(insn, 2);
+    }
+
+    fn l1_ldrw(&mut self, insn: u32) {
+        self.load_op(insn, 4);
+    }
+
+    fn l1_str(&mut self, insn: u32) {
+        self.store_op(insn, 8);
+    }
+
+    fn l1_strb(&mut self, insn: u32) {
+        self.store_op(insn, 1);
+    }
+
+    fn l1_strh(&mut self, insn: u32) {
+        self.store_op(insn, 2);
+    }
+
+    fn l1_strw(&mut self, insn: u32) {
+        self.store_op(insn, 4);
+    }
+
+    fn l1_int(&mut self, insn: u32) {
+        self.interrupt(immediate::<16>(insn, 0));
+    }
+
+    fn l1_ncall(&mut self, insn: u32) {
+        self.fail(format!("Unknown native call {}", immediate::<21>(insn, 0)));
+    }
+
+    fn l1_vcall(&mut self, insn: u32) {
+        self.fail(format!("Unknown virtual call {}", immediate::<21>(insn, 0)));
+    }
+
+    fn l2_add(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a.wrapping_add(b)));
+    }
+
+    fn l2_sub(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a.wrapping_sub(b)));
+    }
+
+    fn l2_mul(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a.wrapping_mul(b)));
+    }
+
+    fn l2_div(&mut self, insn: u32) {
+        self.reg_op(insn, u64::checked_div);
+    }
+
+    fn l2_rem(&mut self, insn: u32) {
+        self.reg_op(insn, u64::checked_rem);
+    }
+
+    fn l2_divs(&mut self, insn: u32) {
+        self.signed_reg_op(insn, i64::checked_div);
+    }
+
+    fn l2_rems(&mut self, insn: u32) {
+        self.signed_reg_op(insn, i64::checked_rem);
+    }
+
+    fn l2_addf(&mut self, insn: u32) {
+        self.float_op(insn, |a, b| a + b);
+    }
+
+    fn l2_subf(&mut self, insn: u32) {
+        self.float_op(insn, |a, b| a - b);
+    }
+
+    fn l2_mulf(&mut self, insn: u32) {
+        self.float_op(insn, |a, b| a * b);
+    }
+
+    fn l2_divf(&mut self, insn: u32) {
+        self.float_op(insn, |a, b| a / b);
+    }
+
+    fn l2_remf(&mut self, insn: u32) {
+        self.float_op(insn, |a, b| a % b);
+    }
+
+    fn l2_and(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a & b));
+    }
+
+    fn l2_or(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a | b));
+    }
+
+    fn l2_xor(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a ^ b));
+    }
+
+    fn l2_shl(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a.wrapping_shl(b as u32)));
+    }
+
+    fn l2_shr(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a.wrapping_shr(b as u32)));
+    }
+
+    fn l2_shrs(&mut self, insn: u32) {
+        self.signed_reg_op(insn, |a, b| Some(a.wrapping_shr(b as u32)));
+    }
+
+    fn l2_cmp(&mut self, insn: u32) {
+        self.reg_op(insn, |a, b| Some(a.cmp(&b) as i64 as u64));
+    }
+
+    fn l2_cmps(&mut self, insn: u32) {
+        self.signed_reg_op(insn, |a, b| Some(a.cmp(&b) as i64));
+    }
+
+    fn l2_cmpf(&mut self, insn: u32) {
+        let lhs = f64::from_bits(self.regs[reg(insn, 5)]);
+        let rhs = f64::from_bits(self.regs[reg(insn, 10)]);
+        let Some(ordering) = lhs.partial_cmp(&rhs) else {
+            self.fail("Comparison with NaN");
+            return;
+        };
+        self.regs[reg(insn, 0)] = ordering as i64 as u64;
+    }
+
+    fn l3_not(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = !self.regs[reg(insn, 5)];
+    }
+
+    fn l3_mov(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = self.regs[reg(insn, 5)];
+    }
+
+    fn l3_fti(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = f64::from_bits(self.regs[reg(insn, 5)]) as i64 as u64;
+    }
+
+    fn l3_itf(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = (self.regs[reg(insn, 5)] as i64 as f64).to_bits();
+    }
+
+    fn l4_branch(&mut self, insn: u32) {
+        self.branch_reg(insn, false, false);
+    }
+
+    fn l4_branch_l(&mut self, insn: u32) {
+        self.branch_reg(insn, true, false);
+    }
+
+    fn l4_branch_ld(&mut self, insn: u32) {
+        self.branch_reg(insn, false, true);
+    }
+
+    fn l4_branch_l_ld(&mut self, insn: u32) {
+        self.branch_reg(insn, true, true);
+    }
+
+    fn l4_branch_bo(&mut self, insn: u32) {
+        self.branch_reg(insn, false, false);
+    }
+
+    fn l4_branch_l_bo(&mut self, insn: u32) {
+        self.branch_reg(insn, true, false);
+    }
+
+    fn l4_branch_ld_bo(&mut self, insn: u32) {
+        self.branch_reg(insn, false, true);
+    }
+
+    fn l4_branch_bo_ld(&mut self, insn: u32) {
+        self.branch_reg(insn, false, true);
+    }
+
+    fn l4_branch_bo_ld_bo(&mut self, insn: u32) {
+        self.branch_reg(insn, false, true);
+    }
+
+    fn l4_branch_l_ld_bo(&mut self, insn: u32) {
+        self.branch_reg(insn, true, true);
+    }
+
+    fn l4_branch_l_bo_ld(&mut self, insn: u32) {
+        self.branch_reg(insn, true, true);
+    }
+
+    fn l4_branch_l_bo_ld_bo(&mut self, insn: u32) {
+        self.branch_reg(insn, true, true);
+    }
+
+    fn l4_ncall(&mut self, insn: u32) {
+        self.fail(format!("Unknown native call {}", self.regs[reg(insn, 0)]));
+    }
+
+    fn l4_vcall(&mut self, insn: u32) {
+        self.fail(format!("Unknown virtual call {}", self.regs[reg(insn, 0)]));
+    }
+
+    fn l4_ldbo(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = 0;
+    }
+
+    fn l4_ldpc(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = self.pc as u64;
+    }
+
+    fn l4_zero(&mut self, insn: u32) {
+        self.regs[reg(insn, 0)] = 0;
+    }
+
+    fn l4_dbg(&mut self, insn: u32) {
+        let reg = reg(insn, 0);
+        let value = self.regs[reg];
+        let result = writeln!(
+            self.stderr,
+            "[0x{:08x}] r{reg} = {value} (0x{value:x})",
+            self.pc
+        );
+        if let Err(err) = result {
+            self.fail(err.to_string());
+        }
+    }
+
+    fn l4_inc(&mut self, insn: u32) {
+        let reg = reg(insn, 0);
+        self.regs[reg] = self.regs[reg].wrapping_add(1);
+    }
+
+    fn l5_nop(&mut self, _insn: u32) {}
+
+    fn l5_halt(&mut self, _insn: u32) {
+        self.exit = Some(0);
+    }
+
+    fn l5_ret(&mut self, _insn: u32) {
+        match self.callstack.pop() {
+            Some(addr) => self.next_pc = addr,
+            None => self.exit = Some(0),
+        }
+    }
+
+    fn unknown(&mut self, insn: u32) {
+        self.fail(format!("Unknown instruction 0x{insn:08x}"));
+    }
+}
+
+/// Runs a binary and returns its exit code.
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    let file: &PathBuf = matches.get_one("FILE").unwrap();
+    let binary = fs::read(file);
+    let Ok(binary) = binary else {
+        return Err(Error::raw(ErrorKind::Io, binary.unwrap_err()));
+    };
+    let Some(mut interpreter) = Interpreter::load_binary(&binary, stdout(), stderr()) else {
+        return Err(Error::raw(ErrorKind::Io, "Invalid binary format"));
+    };
+    match interpreter.run() {
+        Ok(code) => Ok(code),
+        Err(fault) => {
+            let location = match matches.get_one::<PathBuf>("OFFSETS") {
+                Some(offsets) => source_location(offsets, interpreter.pc)?,
+                None => String::new(),
+            };
+            Err(Error::raw(
+                ErrorKind::Io,
+                format!("{fault} at 0x{:08x}{location}", interpreter.pc),
+            ))
+        }
+    }
+}
+
+/// Returns ` (file:line)` of the instruction at `addr` if it is in the offset map.
+fn source_location(offsets: &PathBuf, addr: usize) -> Result<String> {
+    let offsets = OffsetTable::read_offset_key(&fs::read_to_string(offsets)?)?;
+    let Some((file, span)) = offsets.source(addr) else {
+        return Ok(String::new());
+    };
+    let Ok(source) = fs::read_to_string(file) else {
+        return Ok(format!(" ({file})"));
+    };
+    let start = span.start.min(source.len());
+    let line = source.as_bytes()[..start]
+        .iter()
+        .filter(|c| **c == b'\n')
+        .count()
+        + 1;
+    Ok(format!(" ({file}:{line})"))
+}
+
+fn reg(insn: u32, bit_pos: usize) -> usize {
+    (insn as usize >> bit_pos) & 0x1F
+}
+
+fn immediate<const BITS: usize>(insn: u32, bit_pos: usize) -> u64 {
+    (insn as u64 >> bit_pos) & ((1 << BITS) - 1)
+}
+
+fn signed_immediate<const BITS: usize>(insn: u32, bit_pos: usize) -> i64 {
+    let value = (insn >> bit_pos) & ((1 << BITS) - 1);
+    if ((value >> (BITS - 1)) & 1) != 0 {
+        (value | (!0) << BITS) as i32 as _
+    } else {
+        value as _
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use leviathan_ir::{
+        binary::{Binary, BinaryFunc, BinaryModule, BinaryStatic},
+        layers::{
+            lower::{LowOp, Reg},
+            Coord,
+        },
+    };
+
+    use leviathan_compiler::build::Build;
+
+    use super::Interpreter;
+
+    struct Output {
+        result: Result<i32, String>,
+        stdout: String,
+        stderr: String,
+    }
+
+    /// Assembles the functions and statics into one module and runs the first function.
+    fn run(funcs: Vec<Vec<LowOp>>, statics: Vec<BinaryStatic>) -> Output {
+        let module = BinaryModule {
+            statics: statics.into_iter().enumerate().collect(),
+            funcs: funcs
+                .into_iter()
+                .map(|ops| BinaryFunc {
+                    ops,
+                    ..Default::default()
+                })
+                .enumerate()
+                .collect(),
+            ..Default::default()
+        };
+        let mut binary = Binary::default();
+        binary.modules.insert(0, module);
+        let mut out = Cursor::new(Vec::new());
+        let main = Coord {
+            module: 0,
+            element: 0,
+        };
+        binary
+            .assemble(&mut out, None::<&mut Vec<u8>>, main)
+            .unwrap();
+        let mut interpreter =
+            Interpreter::load_binary(out.get_ref(), Vec::new(), Vec::new()).unwrap();
+        let result = interpreter.run();
+        Output {
+            result,
+            stdout: String::from_utf8(interpreter.stdout).unwrap(),
+            stderr: String::from_utf8(interpreter.stderr).unwrap(),
+        }
+    }
+
+    /// Compiles the code dialect module `main` and runs it, `(exit code)` ends the program.
+    fn run_code(main: &str) -> Output {
+        let lib = "(mod asm)\n(+label exit [:code int] (do\n  (int 0u)\n))\n";
+        let output = Build::new()
+            .source("main", "main.lvt", format!("(mod code)\n(use lib)\n{main}"))
+            .source("lib", "lib.lvt", lib)
+            .compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        let binary = output.binary.unwrap();
+        let mut interpreter = Interpreter::load_binary(&binary, Vec::new(), Vec::new()).unwrap();
+        let result = interpreter.run();
+        Output {
+            result,
+            stdout: String::from_utf8(interpreter.stdout).unwrap(),
+            stderr: String::from_utf8(interpreter.stderr).unwrap(),
+        }
+    }
+
+    fn exit(code: u32) -> [LowOp; 2] {
+        [
+            LowOp::MoveImmediate {
+                dst: Reg::new(0),
+                immediate: code,
+            },
+            LowOp::InterruptImmediate { id: 0 },
+        ]
+    }
+
+    #[test]
+    fn test_write_interrupt() {
+        let mut ops = vec![
+            LowOp::MoveImmediate {
+                dst: Reg::new(0),
+                immediate: 1,
+            },
+            LowOp::LoadStaticAddress {
+                dst: Reg::new(1),
+                coord: Coord {
+                    module: 0,
+                    element: 0,
+                },
+            },
+            LowOp::MoveImmediate {
+                dst: Reg::new(2),
+                immediate: 6,
+            },
+            LowOp::InterruptImmediate { id: 1 },
+        ];
+        ops.extend(exit(0));
+        let value = "Hello\n".to_string();
+        let output = run(vec![ops], vec![BinaryStatic::String { name: None, value }]);
+        assert_eq!(output.result, Ok(0));
+        assert_eq!(output.stdout, "Hello\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn test_exit_interrupt() {
+        let output = run(vec![exit(42).into()], Vec::new());
+        assert_eq!(output.result, Ok(42));
+    }
+
+    #[test]
+    fn test_halt() {
+        let mut ops = vec![LowOp::Halt];
+        ops.extend(exit(1));
+        let output = run(vec![ops], Vec::new());
+        assert_eq!(output.result, Ok(0));
+    }
+
+    #[test]
+    fn test_dbg() {
+        let ops = vec![
+            LowOp::MoveImmediate {
+                dst: Reg::new(3),
+                immediate: 255,
+            },
+            LowOp::Debug { reg: Reg::new(3) },
+            LowOp::Halt,
+        ];
+        let output = run(vec![ops], Vec::new());
+        assert_eq!(output.result, Ok(0));
+        assert!(output.stderr.ends_with("r3 = 255 (0xff)\n"));
+    }
+
+    #[test]
+    fn test_branch() {
+        // Adds up the numbers from 10 to 1
+        let ops = vec![
+            LowOp::Zero { dst: Reg::new(0) },
+            LowOp::MoveImmediate {
+                dst: Reg::new(1),
+                immediate: 10,
+            },
+            LowOp::PutCoord { coord: 0 },
+            LowOp::Add {
+                dst: Reg::new(0),
+                lhs: Reg::new(0),
+                rhs: Reg::new(1),
+            },
+            LowOp::SubImmediate {
+                dst: Reg::new(1),
+                lhs: Reg::new(1),
+                rhs: 1,
+            },
+            LowOp::BranchCoordIfNonZero {
+                reg: Reg::new(1),
+                coord: 0,
+            },
+            LowOp::InterruptImmediate { id: 0 },
+        ];
+        let output = run(vec![ops], Vec::new());
+        assert_eq!(output.result, Ok(55));
+    }
+
+    #[test]
+    fn test_call() {
+        let main = vec![
+            LowOp::Call {
+                coord: Coord {
+                    module: 0,
+                    element: 1,
+                },
+            },
+            LowOp::InterruptImmediate { id: 0 },
+        ];
+        let func = vec![
+            LowOp::MoveImmediate {
+                dst: Reg::new(0),
+                immediate: 7,
+            },
+            LowOp::Return,
+        ];
+        let output = run(vec![main, func], Vec::new());
+        assert_eq!(output.result, Ok(7));
+    }
+
+    #[test]
+    fn test_invalid_instruction() {
+        let output = run(vec![vec![LowOp::InvalidInstruction]], Vec::new());
+        assert_eq!(
+            output.result,
+            Err("Unknown instruction 0xffffffff".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_read() {
+        let ops = vec![
+            LowOp::MoveImmediate {
+                dst: Reg::new(1),
+                immediate: 0x3F_FFFF,
+            },
+            LowOp::Load64 {
+                dst: Reg::new(0),
+                src: Reg::new(1),
+                offset: 0,
+            },
+            LowOp::Halt,
+        ];
+        let output = run(vec![ops], Vec::new());
+        assert_eq!(
+            output.result,
+            Err("Invalid read of 8 bytes at 0x003fffff".to_string())
+        );
+    }
+
+    #[test]
+    fn test_code_call() {
+        let f = "(fn f [:n int] int {\n  (return (* n 2))\n})\n";
+        let main = format!("{f}(fn main [] {{\n  (exit (+ (f 20) 2))\n}})\n");
+        assert_eq!(run_code(&main).result, Ok(42));
+    }
+
+    #[test]
+    fn test_code_stack_arguments() {
+        let names: Vec<_> = (0..17).map(|i| format!("a{i}")).collect();
+        let params: Vec<_> = names.iter().map(|name| format!(":{name} int")).collect();
+        let args: Vec<_> = (1..=17).map(|i| i.to_string()).collect();
+        let main = format!(
+            "(fn f [{}] int {{\n  (return (- a16 a0))\n}})\n\
+             (fn main [] {{\n  (exit (f {}))\n}})\n",
+            params.join(" "),
+            args.join(" "),
+        );
+        assert_eq!(run_code(&main).result, Ok(16));
+    }
+
+    #[test]
+    fn test_code_asm_call() {
+        let main = "(fn main [] {\n  (exit 7)\n})\n";
+        assert_eq!(run_code(main).result, Ok(7));
+    }
+}
@@ -0,0 +1,375 @@
+use std::io::Cursor;
+
+use crate::{
+    compiler::{
+        error::Error,
+        warning::{Lint, LintLevel},
+        CompileTask,
+    },
+    diagnostics::Diagnostics,
+    parser::parse,
+    util::message::MessageFormat,
+};
+
+/// Compiles a set of in-memory sources without touching the file system.
+#[derive(Debug, Default)]
+pub struct Build {
+    sources: Vec<BuildSource>,
+    packages: Vec<String>,
+    collect_offsets: bool,
+    diagnostics: Diagnostics,
+}
+
+#[derive(Debug)]
+struct BuildSource {
+    module_name: String,
+    path: String,
+    source: String,
+}
+
+/// The result of a build.
+#[derive(Debug)]
+pub struct BuildOutput {
+    /// The assembled binary, if no errors were found
+    pub binary: Option<Vec<u8>>,
+    /// The offset map, if offsets were collected and no errors were found
+    pub offsets: Option<Vec<u8>>,
+    pub diagnostics: Diagnostics,
+}
+
+impl BuildOutput {
+    pub fn is_success(&self) -> bool {
+        self.binary.is_some()
+    }
+}
+
+impl Build {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source file, which is the main module if `module_name` is `main`.
+    pub fn source(
+        mut self,
+        module_name: impl Into<String>,
+        path: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Self {
+        self.sources.push(BuildSource {
+            module_name: module_name.into(),
+            path: path.into(),
+            source: source.into(),
+        });
+        self
+    }
+
+    /// Registers a dependency package, whose modules are prefixed with `name::`.
+    ///
+    /// Imports in these modules prefer the modules of the same package.
+    pub fn package(mut self, name: impl Into<String>) -> Self {
+        self.packages.push(name.into());
+        self
+    }
+
+    pub fn offsets(mut self, collect_offsets: bool) -> Self {
+        self.collect_offsets = collect_offsets;
+        self
+    }
+
+    pub fn lint(mut self, lint: Lint, level: LintLevel) -> Self {
+        self.diagnostics.lints.insert(lint, level);
+        self
+    }
+
+    /// Turns all warnings into errors.
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.diagnostics.deny_warnings = deny_warnings;
+        self
+    }
+
+    pub fn message_format(mut self, format: MessageFormat) -> Self {
+        self.diagnostics.format = format;
+        self
+    }
+
+    /// Compiles and assembles the sources.
+    pub fn compile(self) -> BuildOutput {
+        let mut task = self.compile_task();
+        if task.diagnostics.has_errors() {
+            return BuildOutput {
+                binary: None,
+                offsets: None,
+                diagnostics: task.diagnostics,
+            };
+        }
+        let mut binary = Cursor::new(Vec::new());
+        let mut offsets = if task.collect_offsets {
+            Some(Vec::new())
+        } else {
+            None
+        };
+        let result = task
+            .filter()
+            .and_then(|_| task.assemble(&mut binary, offsets.as_mut()));
+        if let Err(err) = result {
+            task.diagnostics.push(err);
+            return BuildOutput {
+                binary: None,
+                offsets: None,
+                diagnostics: task.diagnostics,
+            };
+        }
+        BuildOutput {
+            binary: Some(binary.into_inner()),
+            offsets,
+            diagnostics: task.diagnostics,
+        }
+    }
+
+    /// Compiles and filters the sources like [`Build::compile`] without assembling them.
+    pub fn check(self) -> Diagnostics {
+        let mut task = self.compile_task();
+        if task.diagnostics.has_errors() {
+            return task.diagnostics;
+        }
+        if let Err(err) = task.filter() {
+            task.diagnostics.push(err);
+        }
+        task.diagnostics
+    }
+
+    /// Includes the sources in the order they were added and compiles them.
+    fn compile_task(self) -> CompileTask {
+        let mut task = CompileTask::with_offsets(self.collect_offsets);
+        task.diagnostics = self.diagnostics;
+        task.packages = self.packages;
+        let mut main_found = false;
+        for BuildSource {
+            module_name,
+            path,
+            source,
+        } in self.sources
+        {
+            let main = module_name == "main";
+            main_found |= main;
+            let (module, errors) = parse(module_name, path, source);
+            if !errors.is_empty() {
+                for err in errors {
+                    task.exclude(err);
+                }
+                continue;
+            }
+            if let Err(err) = task.include(module, main) {
+                task.diagnostics.push(err);
+            }
+        }
+        if !main_found {
+            task.exclude(Error::NoMainFound {
+                file: String::new(),
+            });
+        }
+        if let Err(err) = task.compile() {
+            task.diagnostics.push(err);
+        }
+        task
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        compiler::error::Error, diagnostics::Diagnostic, parser::error::Error as ParseError,
+    };
+
+    use super::Build;
+
+    const MAIN: &str = "(mod asm)\n(-label main (do\n  (halt)\n))\n";
+
+    #[test]
+    fn test_compile() {
+        let output = Build::new()
+            .source("main", "main.lvt", MAIN)
+            .offsets(true)
+            .compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        assert!(output.diagnostics.diagnostics.is_empty());
+        let binary = output.binary.unwrap();
+        assert_eq!(&binary[..4], b"\0urb");
+        // The header is followed by `halt` and the `ret` ending the label
+        assert_eq!(binary.len(), 16 + 2 * 4);
+        let offsets = String::from_utf8(output.offsets.unwrap()).unwrap();
+        assert_eq!(offsets, "f main::main 0\nd main.lvt:29..35 0\n");
+    }
+
+    /// Compiles a single `main` module and returns its diagnostics.
+    fn compile_main(src: &str) -> Vec<Diagnostic> {
+        let output = Build::new().source("main", "main.lvt", src).compile();
+        assert_eq!(output.is_success(), output.binary.is_some());
+        output.diagnostics.diagnostics
+    }
+
+    #[test]
+    fn test_compile_code() {
+        let body = "(let x 1)\n  (while (< x 10) {\n    (set x (* x 2))\n  })";
+        let main = format!("(mod code)\n(fn main [] {{\n  {body}\n}})\n");
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .offsets(true)
+            .compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        let offsets = String::from_utf8(output.offsets.unwrap()).unwrap();
+        assert!(offsets.starts_with("f main::main 0\n"), "{offsets}");
+        let main = "(mod code)\n(fn main [] {\n  (set x 1)\n})\n";
+        assert!(matches!(
+            compile_main(main)[..],
+            [Diagnostic::Compile(Error::UnknownVariable { .. })]
+        ));
+    }
+
+    #[test]
+    fn test_for_scope() {
+        let for_ = "(for (let i 0) (< i 10) (set i (+ i 1)) {\n    (let x i)\n  })";
+        let main = format!("(mod code)\n(fn main [] {{\n  {for_}\n}})\n");
+        assert!(compile_main(&main).is_empty());
+        // The variable declared by the init statement is not visible after the loop
+        let main = format!("(mod code)\n(fn main [] {{\n  {for_}\n  (set i 0)\n}})\n");
+        let diagnostics = compile_main(&main);
+        let [Diagnostic::Compile(Error::UnknownVariable { span, .. })] = &diagnostics[..] else {
+            panic!("{diagnostics:?}");
+        };
+        assert_eq!(&main[span.clone()], "i");
+        assert!(span.start > main.find("(set i 0)").unwrap());
+    }
+
+    #[test]
+    fn test_stack_arguments() {
+        // 17 arguments, the last two of them are passed on the stack
+        let names: Vec<_> = (0..17).map(|i| format!("a{i}")).collect();
+        let params: Vec<_> = names.iter().map(|name| format!(":{name} int")).collect();
+        let args: Vec<_> = (0..17).map(|i| i.to_string()).collect();
+        let main = format!(
+            "(mod code)\n(fn f [{}] int {{\n  (return (+ {}))\n}})\n\
+             (fn main [] {{\n  (let x (f {}))\n}})\n",
+            params.join(" "),
+            names.join(" "),
+            args.join(" "),
+        );
+        assert!(compile_main(&main).is_empty());
+        let main = main.replace("(f 0 1 ", "(f 1 ");
+        assert!(matches!(
+            compile_main(&main)[..],
+            [Diagnostic::Compile(Error::InvalidCallSignature { .. })]
+        ));
+    }
+
+    #[test]
+    fn test_asm_label_call() {
+        let main = "(mod code)\n(use lib)\n(fn main [] {\n  (let x (add 1 2))\n})\n";
+        let lib = "(mod asm)\n(+label add [:a int :b int] int (do\n  (add r0 r0 r1)\n))\n";
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .source("lib", "lib.lvt", lib)
+            .offsets(true)
+            .compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        let offsets = String::from_utf8(output.offsets.unwrap()).unwrap();
+        assert!(offsets.contains("f lib::add "), "{offsets}");
+        let main = main.replace("(add 1 2)", "(add 1 2.0)");
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .source("lib", "lib.lvt", lib)
+            .compile();
+        assert!(matches!(
+            output.diagnostics.diagnostics[..],
+            [Diagnostic::Compile(Error::MismatchedTypes { .. })]
+        ));
+    }
+
+    #[test]
+    fn test_return_value() {
+        let f = "(fn f [:n int] int {\n  (return (* n 2))\n})\n";
+        let main = format!("(mod code)\n{f}(fn main [] {{\n  (let x (+ (f 1) 1))\n}})\n");
+        assert!(compile_main(&main).is_empty());
+        // A returned value has the declared return type of the callee
+        let main = format!("(mod code)\n{f}(fn main [] {{\n  (let x (+ (f 1) 1.0))\n}})\n");
+        assert!(matches!(
+            compile_main(&main)[..],
+            [Diagnostic::Compile(Error::InvalidBinaryOperation { .. })]
+        ));
+    }
+
+    #[test]
+    fn test_compile_without_offsets() {
+        let output = Build::new()
+            .source("main", "main.lvt", MAIN)
+            .offsets(false)
+            .compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        assert!(output.offsets.is_none());
+    }
+
+    #[test]
+    fn test_compile_without_main() {
+        let output = Build::new().source("lib", "lib.lvt", MAIN).compile();
+        assert!(!output.is_success());
+        assert!(output.offsets.is_none());
+        let diagnostics = &output.diagnostics.diagnostics;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert!(matches!(
+            diagnostics[0],
+            Diagnostic::Compile(Error::NoMainFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_check() {
+        let diagnostics = Build::new().source("main", "main.lvt", MAIN).check();
+        assert!(diagnostics.diagnostics.is_empty(), "{diagnostics:?}");
+        let main = "(mod asm)\n(-label main (do\n  (f)\n))\n";
+        let diagnostics = Build::new().source("main", "main.lvt", main).check();
+        assert!(diagnostics.has_errors());
+        assert!(matches!(
+            diagnostics.diagnostics[..],
+            [Diagnostic::Compile(Error::UnknownFunc { .. })]
+        ));
+    }
+
+    #[test]
+    fn test_compile_despite_excluded_module() {
+        // The unknown function in `main` is only found if it is compiled
+        let main = "(mod asm)\n(-label main (do\n  (f)\n))\n";
+        let broken = "(mod asm)\n(-label f (do (halt)]\n";
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .source("broken", "broken.lvt", broken)
+            .compile();
+        assert!(!output.is_success());
+        let diagnostics = &output.diagnostics.diagnostics;
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert!(matches!(
+            &diagnostics[0],
+            Diagnostic::Parse(ParseError::MissmatchBrackets { file, .. }) if file == "broken.lvt"
+        ));
+        assert!(matches!(
+            &diagnostics[1],
+            Diagnostic::Compile(Error::UnknownFunc { file, .. }) if file == "main.lvt"
+        ));
+    }
+
+    #[test]
+    fn test_ambiguous_call() {
+        let main = "(mod code)\n(use a b)\n(fn main [] {\n  (f)\n})\n";
+        let lib = "(mod asm)\n(+label f (do\n  (halt)\n))\n";
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .source("a", "a.lvt", lib)
+            .source("b", "b.lvt", lib)
+            .compile();
+        let diagnostics = &output.diagnostics.diagnostics;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert!(matches!(
+            diagnostics[0],
+            Diagnostic::Compile(Error::AmbiguousName { .. })
+        ));
+    }
+}
@@ -1,5 +1,3 @@
-use std::mem;
-
 use leviathan_ir::layers::lower::{LowOp, LowerLayer, Reg};
 use phf::{phf_map, Map};
 
@@ -399,8 +397,8 @@ pub fn find(
                     let max_value = (1 << *bits) - 1;
                     if *value > max_value {
                         return Err(Error::NotInSizeRange {
-                            file: mem::take(&mut module.file),
-                            src: mem::take(&mut module.src),
+                            file: module.file.clone(),
+                            src: module.src.clone(),
                             span: span.clone(),
                             range: 0..max_value as usize,
                         });
@@ -414,8 +412,8 @@ pub fn find(
                     let min_value = -(1 << (*bits - 1));
                     if *value > max_value || *value < min_value {
                         return Err(Error::NotInI64Range {
-                            file: mem::take(&mut module.file),
-                            src: mem::take(&mut module.src),
+                            file: module.file.clone(),
+                            src: module.src.clone(),
                             span: span.clone(),
                             range: min_value..max_value,
                         });
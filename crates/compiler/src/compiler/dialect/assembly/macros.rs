@@ -1,21 +1,19 @@
-use std::mem;
-
-use leviathan_ir::layers::{
-    lower::{LowOp, LowerLayer, Reg},
-    Coord,
-};
+use leviathan_ir::layers::lower::{LowOp, LowerLayer, Reg};
 use phf::{phf_map, Map};
 
 use crate::{
     compiler::{
         error::{Error, Result},
-        CompileTask,
+        CompileTask, Module,
     },
     parser::Node,
     util::source::Span,
 };
 
-use super::AssemblyLanguage;
+use super::{
+    insns::{self, INSN_MACROS},
+    AssemblyLanguage,
+};
 
 pub type Macro = fn(
     data: &mut AssemblyLanguage,
@@ -28,6 +26,7 @@ pub type Macro = fn(
 
 pub const MACROS: Map<&'static str, Macro> = phf_map! {
     "lea" => r#ref,
+    "ldr" => ldr,
     "ref" => r#ref,
 };
 
@@ -42,62 +41,97 @@ fn r#ref(
     let module = &mut task.modules[module_index];
     if sub_nodes.len() != 3 {
         return Err(Error::InvalidStatement {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         });
     }
-    let Node::Ident { span: dst_span } = &sub_nodes[1] else {
+    let dst = parse_dst(module, &sub_nodes[1])?;
+    let Node::Ident { span: static_span } = &sub_nodes[2] else {
         return Err(Error::UnexpectedToken {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
-            span: sub_nodes[1].span(),
+            file: module.file.clone(),
+            src: module.src.clone(),
+            span: sub_nodes[2].span(),
+        });
+    };
+    let coord = dialect.resolve_static(task, module_index, static_span)?;
+    binary_func
+        .ops
+        .push(LowOp::LoadStaticAddress { dst, coord });
+    Ok(())
+}
+
+/// Loads the value of a static, or falls back to the `ldr` instruction.
+fn ldr(
+    dialect: &mut AssemblyLanguage,
+    task: &mut CompileTask,
+    module_index: usize,
+    binary_func: &mut LowerLayer,
+    span: Span,
+    sub_nodes: Vec<Node>,
+) -> Result<()> {
+    if sub_nodes.len() != 3 {
+        let insn = insns::find(
+            task,
+            module_index,
+            INSN_MACROS["ldr"],
+            span.clone(),
+            &sub_nodes,
+        )?;
+        let module = &mut task.modules[module_index];
+        let Some(insn) = insn else {
+            return Err(Error::InvalidStatement {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span,
+            });
+        };
+        insn(module, binary_func, sub_nodes);
+        return Ok(());
+    }
+    let module = &mut task.modules[module_index];
+    let dst = parse_dst(module, &sub_nodes[1])?;
+    let Node::Ident { span: static_span } = &sub_nodes[2] else {
+        return Err(Error::UnexpectedToken {
+            file: module.file.clone(),
+            src: module.src.clone(),
+            span: sub_nodes[2].span(),
+        });
+    };
+    let coord = dialect.resolve_static(task, module_index, static_span)?;
+    binary_func.ops.push(LowOp::LoadStatic64 { dst, coord });
+    Ok(())
+}
+
+fn parse_dst(module: &Module, node: &Node) -> Result<Reg> {
+    let Node::Ident { span: dst_span } = node else {
+        return Err(Error::UnexpectedToken {
+            file: module.file.clone(),
+            src: module.src.clone(),
+            span: node.span(),
         });
     };
     let dst = &module.src[dst_span.clone()];
     if !dst.starts_with('r') && !dst.starts_with('R') {
         return Err(Error::InvalidRegister {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: dst_span.clone(),
         });
     }
     let Ok(dst) = dst[1..].parse::<usize>() else {
         return Err(Error::InvalidRegister {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: dst_span.clone(),
         });
     };
     if dst > 31 {
         return Err(Error::InvalidRegister {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: dst_span.clone(),
         });
     }
-    let dst = Reg::new(dst as u8);
-    let Node::Ident { span: static_span } = &sub_nodes[2] else {
-        return Err(Error::UnexpectedToken {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
-            span: sub_nodes[2].span(),
-        });
-    };
-    let static_ = &module.src[static_span.clone()];
-    let Some(static_) = dialect.static_indices.get(static_) else {
-        return Err(Error::UnknownStaticVariable {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
-            span: static_span.clone(),
-        });
-    };
-    binary_func.ops.push(LowOp::LoadStaticAddress {
-        dst,
-        coord: Coord {
-            module: module_index,
-            element: *static_,
-        },
-    });
-    Ok(())
+    Ok(Reg::new(dst as u8))
 }
@@ -14,8 +14,13 @@ use leviathan_ir::{
 
 use crate::{
     compiler::{
-        dialect::assembly::{insns::INSN_MACROS, macros::MACROS},
+        dialect::{
+            assembly::{insns::INSN_MACROS, macros::MACROS},
+            code::keywords::{parse_params, parse_return},
+        },
         error::{Error, Result},
+        lint::unrestored_writes,
+        warning::Warning,
         CompileTask, Dialect, Func, FuncData, Static, Type, UncollectedModule,
     },
     parser::{BracketType, Node},
@@ -27,6 +32,7 @@ use self::static_funcs::STATIC_FUNCS;
 pub struct AssemblyLanguage {
     pub unresolved_imports: Vec<Span>,
     pub imports: Vec<usize>,
+    pub import_spans: Vec<Span>,
     pub label_indices: HashMap<String, usize>,
     pub labels: Vec<Func>,
     pub static_indices: HashMap<String, usize>,
@@ -46,17 +52,18 @@ impl AssemblyLanguage {
         } else {
             None
         };
-        let static_ = mem::take(&mut self.statics[static_index]);
-        match static_.node {
+        let node = mem::take(&mut self.statics[static_index].node);
+        match node {
             Node::Ident { span } => Err(Error::UnexpectedToken {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span,
             }),
             Node::Int { value, .. } => Ok(BinaryStatic::Int { name, value }),
             Node::UInt { value, .. } => Ok(BinaryStatic::UInt { name, value }),
             Node::Float { value, .. } => Ok(BinaryStatic::Float { name, value }),
             Node::String { value, .. } => Ok(BinaryStatic::String { name, value }),
+            Node::Bytes { value, .. } => Ok(BinaryStatic::Bytes { name, value }),
             Node::Node {
                 span,
                 type_,
@@ -64,30 +71,30 @@ impl AssemblyLanguage {
             } => {
                 if type_ != BracketType::Round {
                     return Err(Error::InvalidBracketType {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
                 if sub_nodes.is_empty() {
                     return Err(Error::EmptyNode {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
                 let Node::Ident { span } = &sub_nodes[0] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span
                     });
                 };
                 let keyword = &module.src[span.clone()];
                 let Some(static_func) = STATIC_FUNCS.get(keyword) else {
                     return Err(Error::UnknownStaticFunc {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: span.clone(),
                     });
                 };
@@ -105,12 +112,288 @@ impl AssemblyLanguage {
         }
     }
 
+    /// Resolves a call to a label of an imported module.
+    ///
+    /// The name is either qualified with the path of the module or must be exported by exactly
+    /// one import.
+    fn resolve_callable(
+        &self,
+        task: &CompileTask,
+        module_index: usize,
+        span: &Span,
+    ) -> Result<Coord> {
+        let module = &task.modules[module_index];
+        let name = &module.src[span.clone()];
+        let coord = if let Some((path, name)) = name.rsplit_once("::") {
+            let import = self.resolve_import(task, module_index, path, span)?;
+            let dialect = task.modules[import].dialect.as_deref().unwrap();
+            dialect.lookup_callable(name).map(|element| Coord {
+                module: import,
+                element,
+            })
+        } else {
+            self.lookup_imported(task, module_index, span, |dialect| {
+                dialect.lookup_callable(name)
+            })?
+        };
+        let Some(coord) = coord else {
+            return Err(Error::UnknownFunc {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: span.clone(),
+            });
+        };
+        Ok(coord)
+    }
+
+    /// Resolves a static of the module or a public static of an imported module.
+    ///
+    /// Names of imported statics are resolved like the names of calls.
+    pub fn resolve_static(
+        &self,
+        task: &CompileTask,
+        module_index: usize,
+        span: &Span,
+    ) -> Result<Coord> {
+        let module = &task.modules[module_index];
+        let name = &module.src[span.clone()];
+        let coord = if let Some((path, name)) = name.rsplit_once("::") {
+            let import = self.resolve_import(task, module_index, path, span)?;
+            let dialect = task.modules[import].dialect.as_deref().unwrap();
+            dialect.lookup_static(name).map(|element| Coord {
+                module: import,
+                element,
+            })
+        } else if let Some(element) = self.static_indices.get(name) {
+            Some(Coord {
+                module: module_index,
+                element: *element,
+            })
+        } else {
+            self.lookup_imported(task, module_index, span, |dialect| {
+                dialect.lookup_static(name)
+            })?
+        };
+        let Some(coord) = coord else {
+            return Err(Error::UnknownStaticVariable {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: span.clone(),
+            });
+        };
+        Ok(coord)
+    }
+
+    /// Returns the index of the imported module with the given path.
+    fn resolve_import(
+        &self,
+        task: &CompileTask,
+        module_index: usize,
+        path: &str,
+        span: &Span,
+    ) -> Result<usize> {
+        match task.lookup_module(module_index, path) {
+            Some(import) if self.imports.contains(&import) => Ok(import),
+            _ => {
+                let module = &task.modules[module_index];
+                Err(Error::UnknownModule {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
+                    span: span.start..span.start + path.len(),
+                })
+            }
+        }
+    }
+
+    /// Looks up an unqualified name in all imported modules.
+    fn lookup_imported(
+        &self,
+        task: &CompileTask,
+        module_index: usize,
+        span: &Span,
+        lookup: impl Fn(&dyn Dialect) -> Option<usize>,
+    ) -> Result<Option<Coord>> {
+        let mut found = None;
+        for import in self.imports.iter().cloned() {
+            let dialect = task.modules[import].dialect.as_deref().unwrap();
+            let Some(element) = lookup(dialect) else {
+                continue;
+            };
+            if found.is_some() {
+                let module = &task.modules[module_index];
+                return Err(Error::AmbiguousName {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
+                    span: span.clone(),
+                });
+            }
+            found = Some(Coord {
+                module: import,
+                element,
+            });
+        }
+        Ok(found)
+    }
+
+    fn collect_node(
+        &mut self,
+        task: &mut CompileTask,
+        module_index: usize,
+        main: bool,
+        node: Node,
+    ) -> Result<()> {
+        let module = &mut task.modules[module_index];
+        let Node::Node {
+            span,
+            type_: BracketType::Round,
+            mut sub_nodes,
+        } = node
+        else {
+            return Err(Error::UnexpectedToken {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: node.span(),
+            });
+        };
+        if sub_nodes.is_empty() {
+            return Err(Error::EmptyNode {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span,
+            });
+        }
+        let Node::Ident { span: keyword_span } = &sub_nodes[0] else {
+            return Err(Error::UnexpectedToken {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: sub_nodes[0].span(),
+            });
+        };
+        let keyword = &module.src[keyword_span.clone()];
+        match keyword {
+            "use" => {
+                if sub_nodes.len() != 2 {
+                    return Err(Error::InvalidStatement {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span,
+                    });
+                }
+                let Node::Ident { span: import_span } = &sub_nodes[1] else {
+                    return Err(Error::UnexpectedToken {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span: sub_nodes[1].span(),
+                    });
+                };
+                self.unresolved_imports.push(import_span.clone());
+            }
+            "static" | "-static" | "+static" => {
+                let public = keyword.starts_with('+');
+                if sub_nodes.len() != 3 {
+                    return Err(Error::InvalidStatement {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span,
+                    });
+                }
+                let Node::Ident { span: name_span } = &sub_nodes[1] else {
+                    return Err(Error::UnexpectedToken {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span: sub_nodes[1].span(),
+                    });
+                };
+                let name = &module.src[name_span.clone()];
+                if self.static_indices.contains_key(name) {
+                    return Err(Error::DuplicateName {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span: name_span.clone(),
+                    });
+                }
+                self.statics.push(Static {
+                    span: name_span.clone(),
+                    public,
+                    node: sub_nodes.pop().unwrap(),
+                });
+                self.static_indices
+                    .insert(name.to_string(), self.statics.len() - 1);
+            }
+            "-label" | "+label" => {
+                let public = keyword.starts_with('+');
+                if !(3..=5).contains(&sub_nodes.len()) {
+                    return Err(Error::InvalidStatement {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span,
+                    });
+                }
+                let Node::Ident { span: name_span } = &sub_nodes[1] else {
+                    return Err(Error::UnexpectedToken {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span: sub_nodes[1].span(),
+                    });
+                };
+                let name = &module.src[name_span.clone()];
+                if self.label_indices.contains_key(name) {
+                    return Err(Error::DuplicateName {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span: name_span.clone(),
+                    });
+                }
+                let name = name.to_string();
+                let name_span = name_span.clone();
+                let node = sub_nodes.pop().unwrap();
+                let (params, return_) = if sub_nodes.len() > 2 {
+                    let mut signature = sub_nodes.drain(2..);
+                    let params_node = signature.next().unwrap();
+                    let return_node = signature.next();
+                    drop(signature);
+                    let params = parse_params(module, params_node)?;
+                    let return_ = if let Some(return_node) = return_node {
+                        parse_return(module, return_node)?
+                    } else {
+                        Type::Unit
+                    };
+                    (params, return_)
+                } else {
+                    (vec![(None, Type::Unknown)], Type::Unknown)
+                };
+                self.labels.push(Func {
+                    span: name_span,
+                    public,
+                    params,
+                    return_,
+                    data: FuncData { node },
+                });
+                self.label_indices
+                    .insert(name.clone(), self.labels.len() - 1);
+                if main && name == "main" {
+                    task.main = Some(Coord {
+                        module: module_index,
+                        element: self.labels.len() - 1,
+                    });
+                }
+            }
+            _ => {
+                return Err(Error::InvalidKeyword {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
+                    span: keyword_span.clone(),
+                })
+            }
+        }
+        Ok(())
+    }
+
     fn compile_label(
         &mut self,
         task: &mut CompileTask,
         module_index: usize,
         func_index: usize,
-        binary_mod: &mut BinaryModule,
     ) -> Result<BinaryFunc> {
         let mut binary_func = LowerLayer::default();
         if task.collect_offsets {
@@ -119,6 +402,7 @@ impl AssemblyLanguage {
         }
         let module = &mut task.modules[module_index];
         let Func {
+            span: _,
             public: _,
             params: _,
             return_: _,
@@ -127,41 +411,11 @@ impl AssemblyLanguage {
         let FuncData { node } = mem::take(data);
         match node {
             Node::Ident { span } => {
-                let name = &module.src[span.clone()];
-                let Some(static_index) = self.static_indices.get(name) else {
-                    return Err(Error::UnknownStaticVariable {
-                        file: module.take_file(),
-                        src: module.take_src(),
-                        span,
-                    });
-                };
-                let static_ = binary_mod.statics.get_mut(static_index).unwrap();
-                match static_ {
-                    BinaryStatic::Int { .. }
-                    | BinaryStatic::UInt { .. }
-                    | BinaryStatic::Float { .. } => {
-                        binary_func.ops.push(LowOp::LoadStatic64 {
-                            dst: Reg::new(0),
-                            coord: Coord {
-                                module: module_index,
-                                element: *static_index,
-                            },
-                        });
-                    }
-                    BinaryStatic::String { .. }
-                    | BinaryStatic::FilledBuffer { .. }
-                    | BinaryStatic::IntArray { .. }
-                    | BinaryStatic::UIntArray { .. }
-                    | BinaryStatic::FloatArray { .. } => {
-                        binary_func.ops.push(LowOp::LoadStatic64 {
-                            dst: Reg::new(0),
-                            coord: Coord {
-                                module: module_index,
-                                element: *static_index,
-                            },
-                        });
-                    }
-                }
+                let coord = self.resolve_static(task, module_index, &span)?;
+                binary_func.ops.push(LowOp::LoadStatic64 {
+                    dst: Reg::new(0),
+                    coord,
+                });
                 binary_func.ops.push(LowOp::Return);
                 Ok(binary_func.to_func())
             }
@@ -231,6 +485,17 @@ impl AssemblyLanguage {
                 binary_func.ops.push(LowOp::Return);
                 Ok(binary_func.to_func())
             }
+            Node::Bytes { value, .. } => {
+                binary_func
+                    .locals
+                    .push(BinaryStatic::Bytes { name: None, value });
+                binary_func.ops.push(LowOp::LoadLocalStaticAddress {
+                    dst: Reg::new(0),
+                    coord: binary_func.locals.len() - 1,
+                });
+                binary_func.ops.push(LowOp::Return);
+                Ok(binary_func.to_func())
+            }
             Node::Node {
                 span,
                 type_,
@@ -238,12 +503,21 @@ impl AssemblyLanguage {
             } => {
                 if type_ != BracketType::Round {
                     return Err(Error::InvalidBracketType {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
                 self.compile_label_node(task, module_index, &mut binary_func, sub_nodes, span, 0)?;
+                let module = &task.modules[module_index];
+                for (reg, span) in unrestored_writes(&binary_func.ops) {
+                    task.diagnostics.warn(Warning::CalleeSavedWrite {
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span,
+                        reg: reg.value() as u8,
+                    });
+                }
                 Ok(binary_func.to_func())
             }
             _ => unreachable!(),
@@ -266,25 +540,37 @@ impl AssemblyLanguage {
             }
             return Ok(());
         }
+        binary_func.ops.push(LowOp::PutSpan { span: span.clone() });
         let Node::Ident { span: name_span } = &sub_nodes[0] else {
             return Err(Error::UnexpectedToken {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: sub_nodes[0].span(),
             });
         };
         let mut name = &module.src[name_span.clone()];
         match name {
             "do" => {
+                let block_end = sub_nodes.last().unwrap().span().end;
+                let mut unreachable = false;
+                let mut reported = false;
                 let mut sub_nodes = sub_nodes.into_iter().peekable();
                 sub_nodes.next().unwrap();
                 while let Some(node) = sub_nodes.next() {
+                    if unreachable && !reported {
+                        task.diagnostics.warn(Warning::DeadCode {
+                            file: module.file.clone(),
+                            src: module.src.clone(),
+                            span: node.span().start..block_end,
+                        });
+                        reported = true;
+                    }
                     match node {
                         Node::Ident { span } => {
                             if sub_nodes.peek().is_some() {
                                 return Err(Error::UnexpectedToken {
-                                    file: module.take_file(),
-                                    src: module.take_src(),
+                                    file: module.file.clone(),
+                                    src: module.src.clone(),
                                     span,
                                 });
                             }
@@ -295,8 +581,8 @@ impl AssemblyLanguage {
                         | node @ Node::Float { .. } => {
                             if sub_nodes.peek().is_some() {
                                 return Err(Error::UnexpectedToken {
-                                    file: module.take_file(),
-                                    src: module.take_src(),
+                                    file: module.file.clone(),
+                                    src: module.src.clone(),
                                     span: node.span(),
                                 });
                             }
@@ -355,8 +641,8 @@ impl AssemblyLanguage {
                         Node::String { span, value } => {
                             if sub_nodes.peek().is_some() {
                                 return Err(Error::UnexpectedToken {
-                                    file: module.take_file(),
-                                    src: module.take_src(),
+                                    file: module.file.clone(),
+                                    src: module.src.clone(),
                                     span,
                                 });
                             }
@@ -368,6 +654,22 @@ impl AssemblyLanguage {
                                 coord: binary_func.locals.len() - 1,
                             });
                         }
+                        Node::Bytes { span, value } => {
+                            if sub_nodes.peek().is_some() {
+                                return Err(Error::UnexpectedToken {
+                                    file: module.file.clone(),
+                                    src: module.src.clone(),
+                                    span,
+                                });
+                            }
+                            binary_func
+                                .locals
+                                .push(BinaryStatic::Bytes { name: None, value });
+                            binary_func.ops.push(LowOp::LoadLocalStaticAddress {
+                                dst: Reg::new(0),
+                                coord: binary_func.locals.len() - 1,
+                            });
+                        }
                         Node::Node {
                             span,
                             type_,
@@ -375,19 +677,25 @@ impl AssemblyLanguage {
                         } => {
                             if type_ != BracketType::Round {
                                 return Err(Error::InvalidBracketType {
-                                    file: module.take_file(),
-                                    src: module.take_src(),
+                                    file: module.file.clone(),
+                                    src: module.src.clone(),
                                     span,
                                 });
                             }
-                            self.compile_label_node(
+                            if let Some(Node::Ident { span }) = sub_nodes.first() {
+                                unreachable |= matches!(&module.src[span.clone()], "halt" | "ret");
+                            }
+                            // Errors in single instructions don't stop the rest of the block
+                            if let Err(err) = self.compile_label_node(
                                 task,
                                 module_index,
                                 binary_func,
                                 sub_nodes,
                                 span,
                                 depth + 1,
-                            )?;
+                            ) {
+                                task.diagnostics.push(err);
+                            }
                             module = &mut task.modules[module_index];
                         }
                         _ => unreachable!(),
@@ -400,45 +708,45 @@ impl AssemblyLanguage {
             "if" => {
                 if sub_nodes.len() != 4 {
                     return Err(Error::InvalidStatement {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
                 let Node::Ident { span: cond_span } = &sub_nodes[1] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: sub_nodes[1].span(),
                     });
                 };
                 let cond = &module.src[cond_span.clone()];
                 let Node::Ident { span: reg_span } = &sub_nodes[2] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: sub_nodes[2].span(),
                     });
                 };
                 let reg = &module.src[reg_span.clone()];
                 if !reg.starts_with('r') && !reg.starts_with('R') {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 }
                 let Ok(reg) = reg[1..].parse::<usize>() else {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 };
                 if reg > 31 {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 }
@@ -471,8 +779,8 @@ impl AssemblyLanguage {
                         .push(LowOp::BranchCoordIfNonZero { reg, coord: pos }),
                     _ => {
                         return Err(Error::InvalidCondition {
-                            file: module.take_file(),
-                            src: module.take_src(),
+                            file: module.file.clone(),
+                            src: module.src.clone(),
                             span: cond_span.clone(),
                         });
                     }
@@ -480,15 +788,15 @@ impl AssemblyLanguage {
                 let expr = sub_nodes.pop().unwrap();
                 let Node::Node { span, type_, sub_nodes } = expr else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: expr.span(),
                     });
                 };
                 if type_ != BracketType::Round {
                     return Err(Error::InvalidBracketType {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
@@ -508,45 +816,45 @@ impl AssemblyLanguage {
             "while" => {
                 if sub_nodes.len() != 4 {
                     return Err(Error::InvalidStatement {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
                 let Node::Ident { span: cond_span } = &sub_nodes[1] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: sub_nodes[1].span(),
                     });
                 };
                 let cond = &module.src[cond_span.clone()];
                 let Node::Ident { span: reg_span } = &sub_nodes[2] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: sub_nodes[2].span(),
                     });
                 };
                 let reg = &module.src[reg_span.clone()];
                 if !reg.starts_with('r') && !reg.starts_with('R') {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 }
                 let Ok(reg) = reg[1..].parse::<usize>() else {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 };
                 if reg > 31 {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 }
@@ -566,8 +874,8 @@ impl AssemblyLanguage {
                     "=0" => LowOp::BranchCoordIfZero { coord: pos, reg },
                     _ => {
                         return Err(Error::InvalidCondition {
-                            file: module.take_file(),
-                            src: module.take_src(),
+                            file: module.file.clone(),
+                            src: module.src.clone(),
                             span: cond_span.clone(),
                         });
                     }
@@ -575,15 +883,15 @@ impl AssemblyLanguage {
                 let expr = sub_nodes.pop().unwrap();
                 let Node::Node { span, type_, sub_nodes } = expr else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: expr.span(),
                     });
                 };
                 if type_ != BracketType::Round {
                     return Err(Error::InvalidBracketType {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
@@ -604,45 +912,45 @@ impl AssemblyLanguage {
             "do-while" => {
                 if sub_nodes.len() != 4 {
                     return Err(Error::InvalidStatement {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span,
                     });
                 }
                 let Node::Ident { span: cond_span } = &sub_nodes[2] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: sub_nodes[2].span(),
                     });
                 };
                 let cond = &module.src[cond_span.clone()];
                 let Node::Ident { span: reg_span } = &sub_nodes[3] else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: sub_nodes[3].span(),
                     });
                 };
                 let reg = &module.src[reg_span.clone()];
                 if !reg.starts_with('r') && !reg.starts_with('R') {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 }
                 let Ok(reg) = reg[1..].parse::<usize>() else {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 };
                 if reg > 31 {
                     return Err(Error::InvalidRegister {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: reg_span.clone(),
                     });
                 }
@@ -660,25 +968,30 @@ impl AssemblyLanguage {
                     "=0" => LowOp::BranchCoordIfZero { coord: pos, reg },
                     _ => {
                         return Err(Error::InvalidCondition {
-                            file: module.take_file(),
-                            src: module.take_src(),
+                            file: module.file.clone(),
+                            src: module.src.clone(),
                             span: cond_span.clone(),
                         });
                     }
                 };
                 let expr = sub_nodes.remove(1);
-                let Node::Node { span, type_, sub_nodes } = expr else {
+                let Node::Node {
+                    span: expr_span,
+                    type_,
+                    sub_nodes,
+                } = expr
+                else {
                     return Err(Error::UnexpectedToken {
-                        file: module.take_file(),
-                        src: module.take_src(),
+                        file: module.file.clone(),
+                        src: module.src.clone(),
                         span: expr.span(),
                     });
                 };
                 if type_ != BracketType::Round {
                     return Err(Error::InvalidBracketType {
-                        file: module.take_file(),
-                        src: module.take_src(),
-                        span,
+                        file: module.file.clone(),
+                        src: module.src.clone(),
+                        span: expr_span,
                     });
                 }
                 self.compile_label_node(
@@ -686,9 +999,10 @@ impl AssemblyLanguage {
                     module_index,
                     binary_func,
                     sub_nodes,
-                    span,
+                    expr_span,
                     depth + 1,
                 )?;
+                binary_func.ops.push(LowOp::PutSpan { span });
                 binary_func.ops.push(insn);
                 if depth == 0 {
                     binary_func.ops.push(LowOp::Return);
@@ -728,30 +1042,12 @@ impl AssemblyLanguage {
                         return Ok(());
                     }
                 }
-                let name = name.to_string();
-                for i in self.imports.iter().cloned() {
-                    let include = &mut task.modules[i];
-                    if let Some(func_index) =
-                        include.dialect.as_mut().unwrap().lookup_callable(&name)
-                    {
-                        binary_func.ops.push(LowOp::Call {
-                            coord: Coord {
-                                module: i,
-                                element: func_index,
-                            },
-                        });
-                        if depth == 0 {
-                            binary_func.ops.push(LowOp::Return);
-                        }
-                        return Ok(());
-                    }
-                    module = &mut task.modules[module_index];
+                let coord = self.resolve_callable(task, module_index, name_span)?;
+                binary_func.ops.push(LowOp::Call { coord });
+                if depth == 0 {
+                    binary_func.ops.push(LowOp::Return);
                 }
-                return Err(Error::UnknownFunc {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: name_span.clone(),
-                });
+                return Ok(());
             }
         }
         Ok(())
@@ -763,6 +1059,7 @@ impl Default for AssemblyLanguage {
         Self {
             unresolved_imports: Vec::with_capacity(0),
             imports: Vec::with_capacity(0),
+            import_spans: Vec::with_capacity(0),
             label_indices: HashMap::with_capacity(0),
             labels: Vec::with_capacity(0),
             static_indices: HashMap::with_capacity(0),
@@ -779,137 +1076,19 @@ impl Dialect for AssemblyLanguage {
         UncollectedModule { root }: UncollectedModule,
         main: bool,
     ) -> Result<()> {
-        let module = &mut task.modules[module_index];
+        let errors = task.diagnostics.error_count();
         let mut nodes = root.into_iter();
         nodes.next().unwrap();
         for node in nodes {
-            let Node::Node {
-                span,
-                type_: BracketType::Round,
-                mut sub_nodes,
-            } = node else
-            {
-                return Err(Error::UnexpectedToken {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: node.span(),
-                });
-            };
-            if sub_nodes.is_empty() {
-                return Err(Error::EmptyNode {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span,
-                });
-            }
-            let Node::Ident { span: keyword_span } = &sub_nodes[0] else {
-                return Err(Error::UnexpectedToken {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: sub_nodes[0].span(),
-                });
-            };
-            let keyword = &module.src[keyword_span.clone()];
-            match keyword {
-                "use" => {
-                    if sub_nodes.len() != 2 {
-                        return Err(Error::InvalidStatement {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span,
-                        });
-                    }
-                    let Node::Ident { span: import_span } = &sub_nodes[1] else {
-                        return Err(Error::UnexpectedToken {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span: sub_nodes[1].span(),
-                        });
-                    };
-                    self.unresolved_imports.push(import_span.clone());
-                }
-                "static" => {
-                    if sub_nodes.len() != 3 {
-                        return Err(Error::InvalidStatement {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span,
-                        });
-                    }
-                    let Node::Ident { span: name_span } = &sub_nodes[1] else {
-                        return Err(Error::UnexpectedToken {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span: sub_nodes[1].span(),
-                        });
-                    };
-                    let name = &module.src[name_span.clone()];
-                    if self.static_indices.contains_key(name) {
-                        return Err(Error::DuplicateName {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span: name_span.clone(),
-                        });
-                    }
-                    self.statics.push(Static {
-                        node: sub_nodes.pop().unwrap(),
-                    });
-                    self.static_indices
-                        .insert(name.to_string(), self.statics.len() - 1);
-                }
-                "-label" | "+label" => {
-                    let public = keyword.starts_with('+');
-                    if sub_nodes.len() != 3 {
-                        return Err(Error::InvalidStatement {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span,
-                        });
-                    }
-                    let Node::Ident { span: name_span } = &sub_nodes[1] else {
-                        return Err(Error::UnexpectedToken {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span: sub_nodes[1].span(),
-                        });
-                    };
-                    let name = &module.src[name_span.clone()];
-                    if self.label_indices.contains_key(name) {
-                        return Err(Error::DuplicateName {
-                            file: module.take_file(),
-                            src: module.take_src(),
-                            span: name_span.clone(),
-                        });
-                    }
-                    self.labels.push(Func {
-                        public,
-                        params: vec![(None, Type::Unknown)],
-                        return_: Type::Unknown,
-                        data: FuncData {
-                            node: sub_nodes.pop().unwrap(),
-                        },
-                    });
-                    self.label_indices
-                        .insert(name.to_string(), self.labels.len() - 1);
-                    if main && name == "main" {
-                        task.main = Some(Coord {
-                            module: module_index,
-                            element: self.labels.len() - 1,
-                        });
-                    }
-                }
-                _ => {
-                    return Err(Error::InvalidKeyword {
-                        file: module.take_file(),
-                        src: module.take_src(),
-                        span: keyword_span.clone(),
-                    })
-                }
+            if let Err(err) = self.collect_node(task, module_index, main, node) {
+                task.diagnostics.push(err);
             }
         }
-        if main && task.main.is_none() {
+        let module = &mut task.modules[module_index];
+        // The main function might just have failed to collect
+        if main && task.main.is_none() && task.diagnostics.error_count() == errors {
             return Err(Error::NoMainFound {
-                file: module.take_file(),
+                file: module.file.clone(),
             });
         }
         Ok(())
@@ -925,42 +1104,65 @@ impl Dialect for AssemblyLanguage {
             let name = get_key_by_value(&task.module_indices, &module_index);
             binary_mod.name = name.cloned();
         }
-        let module = &mut task.modules[module_index];
+        if task.collect_offsets {
+            binary_mod.file = Some(task.modules[module_index].file.clone());
+        }
         let statics_len = self.statics.len();
         let funcs_len = self.labels.len();
         for import_span in self.unresolved_imports.drain(..) {
+            let module = &task.modules[module_index];
             let name = &module.src[import_span.clone()];
-            let Some(import) = task.module_indices.get(name) else {
-                return Err(Error::UnknownModule {
-                    file: module.take_file(),
-                    src: module.take_src(),
+            let Some(import) = task.lookup_module(module_index, name) else {
+                task.diagnostics.push(Error::UnknownModule {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: import_span,
                 });
+                continue;
             };
-            if *import == module_index {
-                return Err(Error::SelfImport {
-                    file: module.take_file(),
-                    src: module.take_src(),
+            if import == module_index {
+                task.diagnostics.push(Error::SelfImport {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: import_span,
                 });
+                continue;
             }
-            if self.imports.contains(import) {
-                return Err(Error::DuplicateImport {
-                    file: module.take_file(),
-                    src: module.take_src(),
+            if self.imports.contains(&import) {
+                task.diagnostics.push(Error::DuplicateImport {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: import_span,
                 });
+                continue;
             }
-            self.imports.push(*import);
+            self.imports.push(import);
+            self.import_spans.push(import_span);
         }
         self.unresolved_imports.shrink_to_fit();
+        let mut valid_statics = true;
         for static_index in 0..statics_len {
-            let static_ = self.compile_static(task, module_index, static_index)?;
-            binary_mod.statics.insert(static_index, static_);
+            match self.compile_static(task, module_index, static_index) {
+                Ok(static_) => {
+                    binary_mod.statics.insert(static_index, static_);
+                }
+                Err(err) => {
+                    task.diagnostics.push(err);
+                    valid_statics = false;
+                }
+            }
+        }
+        // Labels referring to invalid statics would only cause follow-up errors
+        if !valid_statics {
+            return Ok(binary_mod);
         }
         for func_index in 0..funcs_len {
-            let func = self.compile_label(task, module_index, func_index, &mut binary_mod)?;
-            binary_mod.funcs.insert(func_index, func);
+            match self.compile_label(task, module_index, func_index) {
+                Ok(func) => {
+                    binary_mod.funcs.insert(func_index, func);
+                }
+                Err(err) => task.diagnostics.push(err),
+            }
         }
         Ok(binary_mod)
     }
@@ -974,4 +1176,34 @@ impl Dialect for AssemblyLanguage {
         }
         Some(index)
     }
+
+    fn lookup_static(&self, name: &str) -> Option<usize> {
+        let Some(index) = self.static_indices.get(name).cloned() else {
+            return None;
+        };
+        if !self.statics[index].public {
+            return None;
+        }
+        Some(index)
+    }
+
+    fn callable(&self, index: usize) -> &Func {
+        &self.labels[index]
+    }
+
+    fn callables(&self) -> &[Func] {
+        &self.labels
+    }
+
+    fn statics(&self) -> &[Static] {
+        &self.statics
+    }
+
+    fn imports(&self) -> Vec<(usize, Span)> {
+        self.imports
+            .iter()
+            .cloned()
+            .zip(self.import_spans.iter().cloned())
+            .collect()
+    }
 }
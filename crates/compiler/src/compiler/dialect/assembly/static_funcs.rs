@@ -1,5 +1,3 @@
-use std::mem;
-
 use leviathan_ir::binary::BinaryStatic;
 use phf::{phf_map, Map};
 
@@ -43,8 +41,8 @@ fn static_buffer(
     };
     if nodes.len() != 2 {
         return Err(Error::InvalidCallSignature {
-            file: mem::take(&mut module.file),
-            src: mem::take(&mut module.src),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         });
     }
@@ -52,8 +50,8 @@ fn static_buffer(
         Node::Int { span, value } => {
             if value < 1 {
                 return Err(Error::NotInSizeRangeFrom {
-                    file: mem::take(&mut module.file),
-                    src: mem::take(&mut module.src),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span,
                     range: 1..,
                 });
@@ -63,8 +61,8 @@ fn static_buffer(
         Node::UInt { span, value } => {
             if value < 1 {
                 return Err(Error::NotInSizeRangeFrom {
-                    file: mem::take(&mut module.file),
-                    src: mem::take(&mut module.src),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span,
                     range: 1..,
                 });
@@ -73,8 +71,8 @@ fn static_buffer(
         }
         _ => {
             return Err(Error::InvalidCallSignature {
-                file: mem::take(&mut module.file),
-                src: mem::take(&mut module.src),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span,
             })
         }
@@ -0,0 +1,553 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+};
+
+use leviathan_ir::{
+    binary::{BinaryFunc, BinaryStatic},
+    layers::{
+        self,
+        upper::{Block, Cond, Expr, Stmnt, UpperLayer, Var},
+        BinaryOpType, Coord, Type,
+    },
+};
+
+use crate::{
+    compiler::{
+        error::{Error, Result},
+        warning::Warning,
+        CompileTask, Func, FuncData,
+    },
+    parser::{BracketType, Node},
+    util::source::Span,
+};
+
+use super::CodeLanguage;
+
+pub(super) struct FuncCompiler<'a> {
+    pub(super) dialect: &'a CodeLanguage,
+    pub(super) task: &'a mut CompileTask,
+    pub(super) module_index: usize,
+    statics: &'a BTreeMap<usize, BinaryStatic>,
+    vars: Vec<Var>,
+    scopes: Vec<HashMap<String, usize>>,
+}
+
+pub fn compile_func(
+    dialect: &mut CodeLanguage,
+    task: &mut CompileTask,
+    module_index: usize,
+    func_index: usize,
+    statics: &BTreeMap<usize, BinaryStatic>,
+    name: Option<String>,
+) -> Result<BinaryFunc> {
+    let Func {
+        params,
+        return_,
+        data,
+        ..
+    } = &mut dialect.funcs[func_index];
+    let return_ = return_.to_ir().unwrap();
+    let FuncData { node } = mem::take(data);
+    let mut vars = Vec::with_capacity(params.len());
+    let mut scope = HashMap::with_capacity(params.len());
+    for (name, type_) in params.iter() {
+        vars.push(Var {
+            type_: type_.to_ir().unwrap(),
+        });
+        if let Some(name) = name {
+            scope.insert(name.clone(), vars.len() - 1);
+        }
+    }
+    let param_count = params.len();
+    let mut compiler = FuncCompiler {
+        dialect,
+        task,
+        module_index,
+        statics,
+        vars,
+        scopes: vec![scope],
+    };
+    let layer = match node {
+        Node::Node {
+            span,
+            type_: BracketType::Curly,
+            sub_nodes,
+        } => {
+            let block = compiler.block(span, sub_nodes)?;
+            UpperLayer::Block {
+                vars: mem::take(&mut compiler.vars),
+                block,
+            }
+        }
+        node => {
+            let expr = compiler.expr(node)?;
+            UpperLayer::Expr {
+                vars: mem::take(&mut compiler.vars),
+                expr,
+            }
+        }
+    };
+    compiler.check_layer(&layer, return_)?;
+    let layer = match layer.destructure().const_eval() {
+        Ok(layer) => layer,
+        Err(err) => return Err(compiler.const_eval_error(err)),
+    };
+    Ok(layer.lower(name, param_count).to_func())
+}
+
+impl FuncCompiler<'_> {
+    pub(super) fn error(&mut self, error: impl FnOnce(String, String) -> Error) -> Error {
+        let module = &mut self.task.modules[self.module_index];
+        error(module.file.clone(), module.src.clone())
+    }
+
+    fn warn(&mut self, warning: impl FnOnce(String, String) -> Warning) {
+        let module = &self.task.modules[self.module_index];
+        let warning = warning(module.file.clone(), module.src.clone());
+        self.task.diagnostics.warn(warning);
+    }
+
+    fn const_eval_error(&mut self, error: layers::error::Error) -> Error {
+        match error {
+            layers::error::Error::InvalidBinOp { left, right } => {
+                self.error(|file, src| Error::InvalidBinaryOperation {
+                    file,
+                    src,
+                    left,
+                    right,
+                })
+            }
+            layers::error::Error::InvalidCast { span } => {
+                self.error(|file, src| Error::InvalidCast { file, src, span })
+            }
+            layers::error::Error::InvalidBitNot { span } => {
+                self.error(|file, src| Error::InvalidBitNot { file, src, span })
+            }
+        }
+    }
+
+    fn str(&self, span: &Span) -> &str {
+        &self.task.modules[self.module_index].src[span.clone()]
+    }
+
+    fn lookup_var(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    fn block(&mut self, span: Span, nodes: Vec<Node>) -> Result<Block> {
+        self.scopes.push(HashMap::with_capacity(0));
+        let mut elements = Vec::with_capacity(nodes.len());
+        let block_end = nodes.last().map(|node| node.span().end).unwrap_or_default();
+        let mut reported = false;
+        for node in nodes {
+            if !reported && matches!(elements.last(), Some(Stmnt::Return { .. })) {
+                let span = node.span().start..block_end;
+                self.warn(|file, src| Warning::DeadCode { file, src, span });
+                reported = true;
+            }
+            elements.push(self.stmnt(node)?);
+        }
+        self.scopes.pop();
+        Ok(Block { span, elements })
+    }
+
+    fn expect_block(&mut self, node: Node) -> Result<Block> {
+        let Node::Node {
+            span,
+            type_: BracketType::Curly,
+            sub_nodes,
+        } = node
+        else {
+            let span = node.span();
+            return Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }));
+        };
+        self.block(span, sub_nodes)
+    }
+
+    fn stmnt(&mut self, node: Node) -> Result<Stmnt> {
+        let Node::Node {
+            span,
+            type_: BracketType::Round,
+            sub_nodes,
+        } = node
+        else {
+            let span = node.span();
+            return Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }));
+        };
+        if sub_nodes.is_empty() {
+            return Err(self.error(|file, src| Error::EmptyNode { file, src, span }));
+        }
+        let Node::Ident { span: keyword_span } = &sub_nodes[0] else {
+            let span = sub_nodes[0].span();
+            return Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }));
+        };
+        match self.str(keyword_span) {
+            "let" | "set" => {
+                let let_ = self.str(keyword_span) == "let";
+                if sub_nodes.len() != 3 {
+                    return Err(self.error(|file, src| Error::InvalidStatement {
+                        file,
+                        src,
+                        span,
+                    }));
+                }
+                let mut sub_nodes = sub_nodes.into_iter().skip(1);
+                let name_node = sub_nodes.next().unwrap();
+                let Node::Ident { span: name_span } = name_node else {
+                    let span = name_node.span();
+                    return Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }));
+                };
+                let expr = self.expr(sub_nodes.next().unwrap())?;
+                let name = self.str(&name_span).to_string();
+                if let_ {
+                    self.vars.push(Var {
+                        type_: expr.type_(&self.vars),
+                    });
+                    let index = self.vars.len() - 1;
+                    self.scopes.last_mut().unwrap().insert(name, index);
+                    return Ok(Stmnt::Let { span, index, expr });
+                }
+                let Some(index) = self.lookup_var(&name) else {
+                    return Err(self.error(|file, src| Error::UnknownVariable {
+                        file,
+                        src,
+                        span: name_span,
+                    }));
+                };
+                Ok(Stmnt::Assign { span, index, expr })
+            }
+            "if" | "while" => {
+                let if_ = self.str(keyword_span) == "if";
+                if sub_nodes.len() != 3 {
+                    return Err(self.error(|file, src| Error::InvalidStatement {
+                        file,
+                        src,
+                        span,
+                    }));
+                }
+                let mut sub_nodes = sub_nodes.into_iter().skip(1);
+                let cond = self.cond(sub_nodes.next().unwrap())?;
+                let block = self.expect_block(sub_nodes.next().unwrap())?;
+                if if_ {
+                    return Ok(Stmnt::If { span, cond, block });
+                }
+                Ok(Stmnt::While { span, cond, block })
+            }
+            "for" => {
+                if sub_nodes.len() != 5 {
+                    return Err(self.error(|file, src| Error::InvalidStatement {
+                        file,
+                        src,
+                        span,
+                    }));
+                }
+                let mut sub_nodes = sub_nodes.into_iter().skip(1);
+                // Variables declared by the init statement are only visible inside the loop
+                self.scopes.push(HashMap::with_capacity(1));
+                let init = self.stmnt(sub_nodes.next().unwrap())?;
+                let cond = self.cond(sub_nodes.next().unwrap())?;
+                let step = self.stmnt(sub_nodes.next().unwrap())?;
+                let block = self.expect_block(sub_nodes.next().unwrap())?;
+                self.scopes.pop();
+                Ok(Stmnt::For {
+                    span,
+                    init: Box::new(init),
+                    cond,
+                    step: Box::new(step),
+                    block,
+                })
+            }
+            "return" => match sub_nodes.len() {
+                1 => Ok(Stmnt::Return { span, expr: None }),
+                2 => {
+                    let expr = self.expr(sub_nodes.into_iter().nth(1).unwrap())?;
+                    Ok(Stmnt::Return {
+                        span,
+                        expr: Some(expr),
+                    })
+                }
+                _ => Err(self.error(|file, src| Error::InvalidStatement { file, src, span })),
+            },
+            _ => {
+                let (coord, params, _) = self.call(sub_nodes)?;
+                Ok(Stmnt::Call {
+                    span,
+                    coord,
+                    params,
+                })
+            }
+        }
+    }
+
+    fn expr(&mut self, node: Node) -> Result<Expr> {
+        match node {
+            Node::Ident { span } => {
+                let name = self.str(&span);
+                if let Some(index) = self.lookup_var(name) {
+                    return Ok(Expr::Variable { span, index });
+                }
+                let Some(static_index) = self.dialect.static_indices.get(name).cloned() else {
+                    return Err(self.error(|file, src| Error::UnknownVariable { file, src, span }));
+                };
+                let coord = Coord {
+                    module: self.module_index,
+                    element: static_index,
+                };
+                Ok(match &self.statics[&static_index] {
+                    BinaryStatic::Int { .. } => Expr::Static {
+                        span,
+                        coord,
+                        type_: Type::Int,
+                    },
+                    BinaryStatic::UInt { .. } => Expr::Static {
+                        span,
+                        coord,
+                        type_: Type::UInt,
+                    },
+                    BinaryStatic::Float { .. } => Expr::Static {
+                        span,
+                        coord,
+                        type_: Type::Float,
+                    },
+                    BinaryStatic::String { .. } => Expr::StaticAddress {
+                        span,
+                        coord,
+                        type_: Type::String,
+                    },
+                    BinaryStatic::Bytes { .. }
+                    | BinaryStatic::FilledBuffer { .. }
+                    | BinaryStatic::IntArray { .. }
+                    | BinaryStatic::UIntArray { .. }
+                    | BinaryStatic::FloatArray { .. } => Expr::StaticAddress {
+                        span,
+                        coord,
+                        type_: Type::UInt,
+                    },
+                })
+            }
+            Node::Int { span, value } => Ok(Expr::Int { span, value }),
+            Node::UInt { span, value } => Ok(Expr::UInt { span, value }),
+            Node::Float { span, value } => Ok(Expr::Float { span, value }),
+            Node::String { span, value } => Ok(Expr::String { span, value }),
+            Node::Node {
+                span,
+                type_: BracketType::Round,
+                sub_nodes,
+            } => {
+                if sub_nodes.is_empty() {
+                    return Err(self.error(|file, src| Error::EmptyNode { file, src, span }));
+                }
+                let Node::Ident { span: op_span } = &sub_nodes[0] else {
+                    let span = sub_nodes[0].span();
+                    return Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }));
+                };
+                let op = match self.str(op_span) {
+                    "+" => Some(BinaryOpType::Add),
+                    "-" => Some(BinaryOpType::Sub),
+                    "*" => Some(BinaryOpType::Mul),
+                    "/" => Some(BinaryOpType::Div),
+                    "%" => Some(BinaryOpType::Rem),
+                    "&" => Some(BinaryOpType::BitAnd),
+                    "|" => Some(BinaryOpType::BitOr),
+                    "^" => Some(BinaryOpType::BitXor),
+                    "<<" => Some(BinaryOpType::ShiftLeft),
+                    ">>" => Some(BinaryOpType::ShiftRight),
+                    _ => None,
+                };
+                if let Some(op) = op {
+                    if sub_nodes.len() < 3 {
+                        return Err(self.error(|file, src| Error::InvalidStatement {
+                            file,
+                            src,
+                            span,
+                        }));
+                    }
+                    let mut sub_nodes = sub_nodes.into_iter().skip(1);
+                    let mut expr = self.expr(sub_nodes.next().unwrap())?;
+                    for node in sub_nodes {
+                        let right = self.expr(node)?;
+                        let span = expr.span().start..right.span().end;
+                        expr = op.constructor()(span, Box::new(expr), Box::new(right));
+                    }
+                    return Ok(expr);
+                }
+                let unary: Option<fn(Span, Box<Expr>) -> Expr> = match self.str(op_span) {
+                    "~" => Some(|span, expr| Expr::BitNot { span, expr }),
+                    "int" => Some(|span, expr| Expr::CastInt { span, expr }),
+                    "uint" => Some(|span, expr| Expr::CastUInt { span, expr }),
+                    "float" => Some(|span, expr| Expr::CastFloat { span, expr }),
+                    _ => None,
+                };
+                if let Some(unary) = unary {
+                    if sub_nodes.len() != 2 {
+                        return Err(self.error(|file, src| Error::InvalidStatement {
+                            file,
+                            src,
+                            span,
+                        }));
+                    }
+                    let expr = self.expr(sub_nodes.into_iter().nth(1).unwrap())?;
+                    return Ok(unary(span, Box::new(expr)));
+                }
+                let (coord, params, type_) = self.call(sub_nodes)?;
+                let Some(type_) = type_ else {
+                    return Err(self.error(|file, src| Error::InvalidCallSignature {
+                        file,
+                        src,
+                        span,
+                    }));
+                };
+                Ok(Expr::Call {
+                    span,
+                    coord,
+                    params,
+                    type_,
+                })
+            }
+            node => {
+                let span = node.span();
+                Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }))
+            }
+        }
+    }
+
+    fn cond(&mut self, node: Node) -> Result<Cond> {
+        let Node::Node {
+            span,
+            type_: BracketType::Round,
+            sub_nodes,
+        } = node
+        else {
+            let span = node.span();
+            return Err(self.error(|file, src| Error::InvalidConditionExpr { file, src, span }));
+        };
+        let Some(Node::Ident { span: op_span }) = sub_nodes.first() else {
+            return Err(self.error(|file, src| Error::InvalidConditionExpr { file, src, span }));
+        };
+        let op_span = op_span.clone();
+        type Comparison = fn(Span, Box<Expr>, Box<Expr>) -> Cond;
+        let comparison: Option<Comparison> = match self.str(&op_span) {
+            "=" => Some(|span, left, right| Cond::Equal { span, left, right }),
+            "!=" => Some(|span, left, right| Cond::NotEqual { span, left, right }),
+            "<" => Some(|span, left, right| Cond::Less { span, left, right }),
+            ">" => Some(|span, left, right| Cond::Greater { span, left, right }),
+            "<=" => Some(|span, left, right| Cond::LessEqual { span, left, right }),
+            ">=" => Some(|span, left, right| Cond::GreaterEqual { span, left, right }),
+            _ => None,
+        };
+        if let Some(comparison) = comparison {
+            if sub_nodes.len() != 3 {
+                return Err(self.error(|file, src| Error::InvalidStatement { file, src, span }));
+            }
+            let mut sub_nodes = sub_nodes.into_iter().skip(1);
+            let left = self.expr(sub_nodes.next().unwrap())?;
+            let right = self.expr(sub_nodes.next().unwrap())?;
+            return Ok(comparison(span, Box::new(left), Box::new(right)));
+        }
+        match self.str(&op_span) {
+            "not" => {
+                if sub_nodes.len() != 2 {
+                    return Err(self.error(|file, src| Error::InvalidStatement {
+                        file,
+                        src,
+                        span,
+                    }));
+                }
+                let cond = self.cond(sub_nodes.into_iter().nth(1).unwrap())?;
+                Ok(Cond::Not {
+                    span,
+                    cond: Box::new(cond),
+                })
+            }
+            "and" | "or" => {
+                let and = self.str(&op_span) == "and";
+                if sub_nodes.len() < 3 {
+                    return Err(self.error(|file, src| Error::InvalidStatement {
+                        file,
+                        src,
+                        span,
+                    }));
+                }
+                let mut sub_nodes = sub_nodes.into_iter().skip(1);
+                let mut cond = self.cond(sub_nodes.next().unwrap())?;
+                for node in sub_nodes {
+                    let right = Box::new(self.cond(node)?);
+                    let left = Box::new(cond);
+                    let span = span.start..right.span().end;
+                    cond = if and {
+                        Cond::And { span, left, right }
+                    } else {
+                        Cond::Or { span, left, right }
+                    };
+                }
+                Ok(cond)
+            }
+            _ => Err(self.error(|file, src| Error::InvalidConditionExpr {
+                file,
+                src,
+                span: op_span,
+            })),
+        }
+    }
+
+    /// Resolves a call and returns the callee, the parameters and the return type if known.
+    fn call(&mut self, sub_nodes: Vec<Node>) -> Result<(Coord, Vec<Expr>, Option<Type>)> {
+        let mut sub_nodes = sub_nodes.into_iter();
+        let name_node = sub_nodes.next().unwrap();
+        let Node::Ident { span: name_span } = name_node else {
+            let span = name_node.span();
+            return Err(self.error(|file, src| Error::UnexpectedToken { file, src, span }));
+        };
+        let mut params = Vec::with_capacity(sub_nodes.len());
+        for node in sub_nodes {
+            params.push(self.expr(node)?);
+        }
+        let name = self.str(&name_span);
+        let mut callee = self
+            .dialect
+            .func_indices
+            .get(name)
+            .map(|index| (self.module_index, &self.dialect.funcs[*index], *index));
+        if callee.is_none() {
+            // Unqualified names must be exported by exactly one import
+            for i in self.dialect.imports.iter().cloned() {
+                let include = self.task.modules[i].dialect.as_ref().unwrap();
+                let Some(index) = include.lookup_callable(name) else {
+                    continue;
+                };
+                if callee.is_some() {
+                    return Err(self.error(|file, src| Error::AmbiguousName {
+                        file,
+                        src,
+                        span: name_span,
+                    }));
+                }
+                callee = Some((i, include.callable(index), index));
+            }
+        }
+        let Some((module, func, element)) = callee else {
+            return Err(self.error(|file, src| Error::UnknownFunc {
+                file,
+                src,
+                span: name_span,
+            }));
+        };
+        let coord = Coord { module, element };
+        if !func.has_signature() {
+            return Ok((coord, params, None));
+        }
+        let return_ = func.return_.to_ir();
+        if func.params.len() != params.len() {
+            return Err(self.error(|file, src| Error::InvalidCallSignature {
+                file,
+                src,
+                span: name_span,
+            }));
+        }
+        Ok((coord, params, return_))
+    }
+}
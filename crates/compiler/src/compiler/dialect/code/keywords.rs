@@ -1,3 +1,5 @@
+use leviathan_ir::layers::Coord;
+
 use crate::{
     compiler::{
         error::{Error, Result},
@@ -22,16 +24,16 @@ pub fn collect_use(
     let module = &mut task.modules[module_index];
     if nodes.len() < 2 {
         return Err(Error::InvalidStatement {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         });
     }
     for node in nodes.into_iter().skip(1) {
         let Node::Ident { span: include_span } = &node else {
             return Err(Error::UnexpectedToken {
-                file: module.take_file(),
-                    src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: node.span(),
             });
         };
@@ -40,8 +42,8 @@ pub fn collect_use(
             let import = &module.src[import_span.clone()];
             if include == import {
                 return Err(Error::UnexpectedToken {
-                    file: module.take_file(),
-                    src: module.take_src(),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: include_span.clone(),
                 });
             }
@@ -62,15 +64,15 @@ pub fn collect_static(
     let module = &mut task.modules[module_index];
     if nodes.len() == 1 {
         return Err(Error::InvalidStatement {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         });
     }
     if nodes.len() % 2 != 1 {
         return Err(Error::InvalidStatement {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         });
     }
@@ -80,21 +82,25 @@ pub fn collect_static(
         let name = nodes.next().unwrap();
         let Node::Ident { span: name_span } = name else {
             return Err(Error::UnexpectedToken {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: name.span(),
             });
         };
         let name = &module.src[name_span.clone()];
         if dialect.static_indices.contains_key(name) {
             return Err(Error::DuplicateName {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: name_span,
             });
         }
         let value = nodes.next().unwrap();
-        dialect.statics.push(Static { node: value });
+        dialect.statics.push(Static {
+            span: name_span.clone(),
+            public: false,
+            node: value,
+        });
         dialect
             .static_indices
             .insert(name.to_string(), dialect.statics.len() - 1);
@@ -106,7 +112,7 @@ pub fn collect_fn(
     dialect: &mut CodeLanguage,
     task: &mut CompileTask,
     module_index: usize,
-    _main_module: bool,
+    main_module: bool,
     span: Span,
     nodes: Vec<Node>,
 ) -> Result<()> {
@@ -114,8 +120,8 @@ pub fn collect_fn(
     let node_count = nodes.len();
     if !(4..=5).contains(&node_count) {
         return Err(Error::InvalidStatement {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         });
     }
@@ -127,38 +133,63 @@ pub fn collect_fn(
     let name_node = nodes.next().unwrap();
     let Node::Ident { span: name_span } = name_node else {
         return Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: name_node.span(),
         });
     };
     let name = &module.src[name_span.clone()];
     if dialect.func_indices.contains_key(name) {
         return Err(Error::DuplicateName {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: name_span,
         });
     }
     let name = name.to_string();
-    let params_node = nodes.next().unwrap();
+    let params = parse_params(module, nodes.next().unwrap())?;
+    let return_ = if node_count == 5 {
+        parse_return(module, nodes.next().unwrap())?
+    } else {
+        Type::Unit
+    };
+    let expr_node = nodes.next().unwrap();
+    dialect.funcs.push(Func {
+        span: name_span,
+        public,
+        params,
+        return_,
+        data: FuncData { node: expr_node },
+    });
+    if main_module && name == "main" {
+        task.main = Some(Coord {
+            module: module_index,
+            element: dialect.funcs.len() - 1,
+        });
+    }
+    dialect.func_indices.insert(name, dialect.funcs.len() - 1);
+    Ok(())
+}
+
+/// Parses a parameter list like `[:a int :b float]`.
+pub fn parse_params(module: &mut Module, node: Node) -> Result<Vec<(Option<String>, Type)>> {
     let Node::Node {
         span: params_span,
         type_: BracketType::Square,
         sub_nodes: param_nodes,
-    } = params_node else
-    {
+    } = node
+    else {
         return Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
-            span: params_node.span(),
+            file: module.file.clone(),
+            src: module.src.clone(),
+            span: node.span(),
         });
     };
     let param_count = param_nodes.len();
     if param_count % 2 != 0 {
         return Err(Error::InvalidParams {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: params_span,
         });
     }
@@ -168,15 +199,15 @@ pub fn collect_fn(
         let param_name_node = param_nodes.next().unwrap();
         let Node::Ident { span: param_name_span } = param_name_node else {
             return Err(Error::InvalidParams {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: param_name_node.span(),
             });
         };
         let Some(param_name) = &module.src[param_name_span.clone()].strip_prefix(':') else {
             return Err(Error::InvalidParams {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: param_name_span,
             });
         };
@@ -184,39 +215,29 @@ pub fn collect_fn(
         let param_type_node = param_nodes.next().unwrap();
         let Node::Ident { span: param_type_span } = param_type_node else {
             return Err(Error::InvalidParams {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: param_type_node.span(),
             });
         };
         let param_type = parse_type(module, param_type_span)?;
         params.push((Some(param_name), param_type));
     }
-    let return_ = if node_count == 5 {
-        let return_node = nodes.next().unwrap();
-        let Node::Ident { span: return_span } = return_node else {
-            return Err(Error::UnexpectedToken {
-                file: module.take_file(),
-                src: module.take_src(),
-                span: return_node.span(),
-            });
-        };
-        parse_type(module, return_span)?
-    } else {
-        Type::Unit
+    Ok(params)
+}
+
+pub fn parse_return(module: &mut Module, node: Node) -> Result<Type> {
+    let Node::Ident { span } = node else {
+        return Err(Error::UnexpectedToken {
+            file: module.file.clone(),
+            src: module.src.clone(),
+            span: node.span(),
+        });
     };
-    let expr_node = nodes.next().unwrap();
-    dialect.funcs.push(Func {
-        public,
-        params,
-        return_,
-        data: FuncData { node: expr_node },
-    });
-    dialect.func_indices.insert(name, dialect.funcs.len() - 1);
-    Ok(())
+    parse_type(module, span)
 }
 
-fn parse_type(module: &mut Module, span: Span) -> Result<Type> {
+pub fn parse_type(module: &mut Module, span: Span) -> Result<Type> {
     match &module.src[span.clone()] {
         "unit" => Ok(Type::Unit),
         "int" => Ok(Type::Int),
@@ -224,8 +245,8 @@ fn parse_type(module: &mut Module, span: Span) -> Result<Type> {
         "float" => Ok(Type::Float),
         "str" => Ok(Type::String),
         _ => Err(Error::InvalidType {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         }),
     }
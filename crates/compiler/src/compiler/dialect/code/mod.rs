@@ -1,20 +1,25 @@
+pub mod func;
 pub mod keywords;
+pub mod types;
 
 use std::{collections::HashMap, mem};
 
-use leviathan_ir::binary::{BinaryFunc, BinaryModule, BinaryStatic};
+use leviathan_ir::binary::{BinaryModule, BinaryStatic};
 use phf::{phf_map, Map};
 
 use crate::{
     compiler::{
         error::{Error, Result},
-        CompileTask, Dialect, Func, FuncData, Module, Static, UncollectedModule,
+        CompileTask, Dialect, Func, Module, Static, UncollectedModule,
     },
     parser::{BracketType, Node},
     util::{get_key_by_value, source::Span},
 };
 
-use self::keywords::{collect_fn, collect_static, collect_use};
+use self::{
+    func::compile_func,
+    keywords::{collect_fn, collect_static, collect_use},
+};
 
 type KeywordProc = fn(
     data: &mut CodeLanguage,
@@ -35,6 +40,7 @@ const KEYWORDS: Map<&'static str, KeywordProc> = phf_map! {
 pub struct CodeLanguage {
     pub unresolved_imports: Vec<Span>,
     pub imports: Vec<usize>,
+    pub import_spans: Vec<Span>,
     pub func_indices: HashMap<String, usize>,
     pub funcs: Vec<Func>,
     pub static_indices: HashMap<String, usize>,
@@ -46,6 +52,7 @@ impl Default for CodeLanguage {
         Self {
             unresolved_imports: Vec::with_capacity(0),
             imports: Vec::with_capacity(0),
+            import_spans: Vec::with_capacity(0),
             func_indices: HashMap::with_capacity(0),
             funcs: Vec::with_capacity(0),
             static_indices: HashMap::with_capacity(0),
@@ -62,44 +69,18 @@ impl Dialect for CodeLanguage {
         UncollectedModule { root }: UncollectedModule,
         main: bool,
     ) -> Result<()> {
-        let mut module = &mut task.modules[module_index];
+        let errors = task.diagnostics.error_count();
         for stmnt in root.into_iter().skip(1) {
-            let Node::Node {
-                span: stmnt_span,
-                type_: BracketType::Round,
-                sub_nodes: stmnt_nodes,
-            } = stmnt else
-            {
-                return Err(Error::UnexpectedToken {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: stmnt.span(),
-                });
-            };
-            if stmnt_nodes.is_empty() {
-                return Err(Error::EmptyNode {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: stmnt_span,
-                });
+            if let Err(err) = self.collect_stmnt(task, module_index, main, stmnt) {
+                task.diagnostics.push(err);
             }
-            let Node::Ident { span: keyword_span } = &stmnt_nodes[0] else {
-                return Err(Error::UnexpectedToken {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: stmnt_nodes[0].span(),
-                });
-            };
-            let keyword = &module.src[keyword_span.clone()];
-            let Some(keyword_proc) = KEYWORDS.get(keyword) else {
-                return Err(Error::InvalidKeyword {
-                    file: module.take_file(),
-                    src: module.take_src(),
-                    span: keyword_span.clone(),
-                });
-            };
-            (*keyword_proc)(self, task, module_index, main, stmnt_span, stmnt_nodes)?;
-            module = &mut task.modules[module_index];
+        }
+        let module = &mut task.modules[module_index];
+        // The main function might just have failed to collect
+        if main && task.main.is_none() && task.diagnostics.error_count() == errors {
+            return Err(Error::NoMainFound {
+                file: module.file.clone(),
+            });
         }
         Ok(())
     }
@@ -114,48 +95,75 @@ impl Dialect for CodeLanguage {
             let name = get_key_by_value(&task.module_indices, &module_index);
             binary_mod.name = name.cloned();
         }
-        let module = &mut task.modules[module_index];
+        if task.collect_offsets {
+            binary_mod.file = Some(task.modules[module_index].file.clone());
+        }
         for import_span in self.unresolved_imports.drain(..) {
+            let module = &task.modules[module_index];
             let import = &module.src[import_span.clone()];
-            let Some(import) = task.module_indices.get(import) else {
-                return Err(Error::UnknownModule {
-                    file: module.take_file(),
-                    src: module.take_src(),
+            let Some(import) = task.lookup_module(module_index, import) else {
+                task.diagnostics.push(Error::UnknownModule {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: import_span,
                 });
+                continue;
             };
-            if *import == module_index {
-                return Err(Error::SelfImport {
-                    file: module.take_file(),
-                    src: module.take_src(),
+            if import == module_index {
+                task.diagnostics.push(Error::SelfImport {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: import_span,
                 });
+                continue;
             }
-            if self.imports.contains(import) {
-                return Err(Error::DuplicateImport {
-                    file: module.take_file(),
-                    src: module.take_src(),
+            if self.imports.contains(&import) {
+                task.diagnostics.push(Error::DuplicateImport {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: import_span,
                 });
+                continue;
             }
-            self.imports.push(*import);
+            self.imports.push(import);
+            self.import_spans.push(import_span);
         }
         self.unresolved_imports.shrink_to_fit();
+        let module = &mut task.modules[module_index];
+        let mut valid_statics = true;
         for i in 0..self.statics.len() {
             let name = if task.collect_offsets {
                 get_key_by_value(&self.static_indices, &i).cloned()
             } else {
                 None
             };
-            let static_ = mem::take(&mut self.statics[i]);
-            let value = compile_static(module, static_.node, name)?;
-            binary_mod.statics.insert(i, value);
+            let node = mem::take(&mut self.statics[i].node);
+            match compile_static(module, node, name) {
+                Ok(value) => {
+                    binary_mod.statics.insert(i, value);
+                }
+                Err(err) => {
+                    task.diagnostics.push(err);
+                    valid_statics = false;
+                }
+            }
+        }
+        // Functions look up the compiled statics by index
+        if !valid_statics {
+            return Ok(binary_mod);
         }
         for i in 0..self.funcs.len() {
-            let Func { data, .. } = &mut self.funcs[i];
-            let FuncData { node } = mem::take(data);
-            let value = compile_func(task, module_index, node)?;
-            binary_mod.funcs.insert(i, value);
+            let name = if task.collect_offsets {
+                get_key_by_value(&self.func_indices, &i).cloned()
+            } else {
+                None
+            };
+            match compile_func(self, task, module_index, i, &binary_mod.statics, name) {
+                Ok(value) => {
+                    binary_mod.funcs.insert(i, value);
+                }
+                Err(err) => task.diagnostics.push(err),
+            }
         }
         Ok(binary_mod)
     }
@@ -169,27 +177,105 @@ impl Dialect for CodeLanguage {
         }
         Some(index)
     }
+
+    fn lookup_static(&self, name: &str) -> Option<usize> {
+        let Some(index) = self.static_indices.get(name).cloned() else {
+            return None;
+        };
+        if !self.statics[index].public {
+            return None;
+        }
+        Some(index)
+    }
+
+    fn callable(&self, index: usize) -> &Func {
+        &self.funcs[index]
+    }
+
+    fn callables(&self) -> &[Func] {
+        &self.funcs
+    }
+
+    fn statics(&self) -> &[Static] {
+        &self.statics
+    }
+
+    fn imports(&self) -> Vec<(usize, Span)> {
+        self.imports
+            .iter()
+            .cloned()
+            .zip(self.import_spans.iter().cloned())
+            .collect()
+    }
+}
+
+impl CodeLanguage {
+    fn collect_stmnt(
+        &mut self,
+        task: &mut CompileTask,
+        module_index: usize,
+        main: bool,
+        stmnt: Node,
+    ) -> Result<()> {
+        let module = &mut task.modules[module_index];
+        let Node::Node {
+            span: stmnt_span,
+            type_: BracketType::Round,
+            sub_nodes: stmnt_nodes,
+        } = stmnt
+        else {
+            return Err(Error::UnexpectedToken {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: stmnt.span(),
+            });
+        };
+        if stmnt_nodes.is_empty() {
+            return Err(Error::EmptyNode {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: stmnt_span,
+            });
+        }
+        let Node::Ident { span: keyword_span } = &stmnt_nodes[0] else {
+            return Err(Error::UnexpectedToken {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: stmnt_nodes[0].span(),
+            });
+        };
+        let keyword = &module.src[keyword_span.clone()];
+        let Some(keyword_proc) = KEYWORDS.get(keyword) else {
+            return Err(Error::InvalidKeyword {
+                file: module.file.clone(),
+                src: module.src.clone(),
+                span: keyword_span.clone(),
+            });
+        };
+        (*keyword_proc)(self, task, module_index, main, stmnt_span, stmnt_nodes)
+    }
 }
 
 fn compile_static(module: &mut Module, node: Node, name: Option<String>) -> Result<BinaryStatic> {
     match node {
         Node::Ident { span } => Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span,
         }),
         Node::Int { value, .. } => Ok(BinaryStatic::Int { name, value }),
         Node::UInt { value, .. } => Ok(BinaryStatic::UInt { name, value }),
         Node::Float { value, .. } => Ok(BinaryStatic::Float { name, value }),
         Node::String { value, .. } => Ok(BinaryStatic::String { name, value }),
+        Node::Bytes { value, .. } => Ok(BinaryStatic::Bytes { name, value }),
         Node::Node {
             span,
             type_: BracketType::Curly,
             mut sub_nodes,
         } => match sub_nodes.len() {
             0 => Err(Error::EmptyBuffer {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span,
             }),
             1 => {
@@ -211,13 +297,13 @@ fn compile_static(module: &mut Module, node: Node, name: Option<String>) -> Resu
                 })
             }
             3 => Err(Error::UnexpectedToken {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: sub_nodes[2].span(),
             }),
             _ => Err(Error::UnexpectedTokens {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span: sub_nodes[2].span().start..sub_nodes.last().unwrap().span().end,
             }),
         },
@@ -228,8 +314,8 @@ fn compile_static(module: &mut Module, node: Node, name: Option<String>) -> Resu
         } => {
             if sub_nodes.is_empty() {
                 return Err(Error::EmptyArray {
-                    file: module.take_file(),
-                    src: module.take_src(),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span,
                 });
             }
@@ -256,31 +342,20 @@ fn compile_static(module: &mut Module, node: Node, name: Option<String>) -> Resu
                     Ok(BinaryStatic::FloatArray { name, values })
                 }
                 _ => Err(Error::UnexpectedToken {
-                    file: module.take_file(),
-                    src: module.take_src(),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span: sub_nodes[0].span(),
                 }),
             }
         }
         _ => Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: node.span(),
         }),
     }
 }
 
-fn compile_func(_task: &mut CompileTask, _module_index: usize, node: Node) -> Result<BinaryFunc> {
-    match node {
-        Node::Node { span, type_: BracketType::Curly, sub_nodes } => {
-            todo!("Parse statement")
-        }
-        _ => {
-            todo!("Parse expression")
-        }
-    }
-}
-
 pub fn expect_byte(module: &mut Module, node: Node) -> Result<u8> {
     match node {
         Node::Int { span, value } => {
@@ -288,8 +363,8 @@ pub fn expect_byte(module: &mut Module, node: Node) -> Result<u8> {
                 return Ok(value as u8);
             }
             Err(Error::InvalidByte {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span,
             })
         }
@@ -298,14 +373,14 @@ pub fn expect_byte(module: &mut Module, node: Node) -> Result<u8> {
                 return Ok(value as u8);
             }
             Err(Error::InvalidByte {
-                file: module.take_file(),
-                src: module.take_src(),
+                file: module.file.clone(),
+                src: module.src.clone(),
                 span,
             })
         }
         _ => Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: node.span(),
         }),
     }
@@ -316,8 +391,8 @@ pub fn expect_unsigned_num(module: &mut Module, node: Node) -> Result<u64> {
         Node::Int { span, value } => {
             if value < 0 {
                 return Err(Error::NegativeNumber {
-                    file: module.take_file(),
-                    src: module.take_src(),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span,
                 });
             }
@@ -325,8 +400,8 @@ pub fn expect_unsigned_num(module: &mut Module, node: Node) -> Result<u64> {
         }
         Node::UInt { value, .. } => Ok(value),
         _ => Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: node.span(),
         }),
     }
@@ -338,16 +413,16 @@ pub fn expect_signed_num(module: &mut Module, node: Node) -> Result<i64> {
         Node::UInt { span, value } => {
             if value > i64::MAX as u64 {
                 return Err(Error::OversizedNumber {
-                    file: module.take_file(),
-                    src: module.take_src(),
+                    file: module.file.clone(),
+                    src: module.src.clone(),
                     span,
                 });
             }
             Ok(value as i64)
         }
         _ => Err(Error::UnexpectedToken {
-            file: module.take_file(),
-            src: module.take_src(),
+            file: module.file.clone(),
+            src: module.src.clone(),
             span: node.span(),
         }),
     }
@@ -358,8 +433,8 @@ pub fn expect_float(module: &mut Module, node: Node) -> Result<f64> {
         return Ok(value);
     }
     Err(Error::UnexpectedToken {
-        file: module.take_file(),
-        src: module.take_src(),
+        file: module.file.clone(),
+        src: module.src.clone(),
         span: node.span(),
     })
 }
@@ -0,0 +1,339 @@
+use leviathan_ir::layers::{
+    upper::{Block, Cond, Expr, Stmnt, UpperLayer, Var},
+    BinaryOpType, Coord, Type,
+};
+
+use crate::{
+    compiler::error::{Error, Result},
+    util::source::Span,
+};
+
+use super::func::FuncCompiler;
+
+impl FuncCompiler<'_> {
+    /// Checks the types of all expressions, calls and returns of a function.
+    pub(super) fn check_layer(&mut self, layer: &UpperLayer, return_: Type) -> Result<()> {
+        match layer {
+            UpperLayer::Expr { vars, expr } => {
+                let type_ = self.check_expr(vars, expr)?;
+                // The value of a function without return type is discarded
+                if return_ != Type::Unit && type_ != return_ {
+                    return Err(self.mismatch(expr.span(), return_, type_));
+                }
+                Ok(())
+            }
+            UpperLayer::Block { vars, block } => {
+                self.check_block(vars, block, return_)?;
+                if return_ != Type::Unit && !block_returns(block) {
+                    // Point at the closing bracket of the body
+                    let span = block.span.end - 1..block.span.end;
+                    return Err(self.error(|file, src| Error::MissingReturn { file, src, span }));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_block(&mut self, vars: &[Var], block: &Block, return_: Type) -> Result<()> {
+        for stmnt in &block.elements {
+            self.check_stmnt(vars, stmnt, return_)?;
+        }
+        Ok(())
+    }
+
+    fn check_stmnt(&mut self, vars: &[Var], stmnt: &Stmnt, return_: Type) -> Result<()> {
+        match stmnt {
+            Stmnt::If { cond, block, .. } | Stmnt::While { cond, block, .. } => {
+                self.check_cond(vars, cond)?;
+                self.check_block(vars, block, return_)
+            }
+            Stmnt::For {
+                init,
+                cond,
+                step,
+                block,
+                ..
+            } => {
+                self.check_stmnt(vars, init, return_)?;
+                self.check_cond(vars, cond)?;
+                self.check_stmnt(vars, step, return_)?;
+                self.check_block(vars, block, return_)
+            }
+            Stmnt::Let { expr, .. } => {
+                if self.check_expr(vars, expr)? == Type::Unit {
+                    let span = expr.span();
+                    return Err(self.error(|file, src| Error::UnitValue { file, src, span }));
+                }
+                Ok(())
+            }
+            Stmnt::Assign { index, expr, .. } => {
+                let type_ = self.check_expr(vars, expr)?;
+                if type_ != vars[*index].type_ {
+                    return Err(self.mismatch(expr.span(), vars[*index].type_, type_));
+                }
+                Ok(())
+            }
+            Stmnt::Return { span, expr } => {
+                let (span, type_) = match expr {
+                    Some(expr) => (expr.span(), self.check_expr(vars, expr)?),
+                    None => (span.clone(), Type::Unit),
+                };
+                if type_ != return_ {
+                    return Err(self.mismatch(span, return_, type_));
+                }
+                Ok(())
+            }
+            Stmnt::Call { coord, params, .. } => self.check_call(vars, *coord, params),
+        }
+    }
+
+    fn check_cond(&mut self, vars: &[Var], cond: &Cond) -> Result<()> {
+        match cond {
+            Cond::Not { cond, .. } => self.check_cond(vars, cond),
+            Cond::And { left, right, .. } | Cond::Or { left, right, .. } => {
+                self.check_cond(vars, left)?;
+                self.check_cond(vars, right)
+            }
+            comparison => {
+                let (left, right) = comparison.operands().unwrap();
+                let left_type = self.check_expr(vars, left)?;
+                let right_type = self.check_expr(vars, right)?;
+                if left_type != right_type || left_type == Type::Unit {
+                    return Err(self.invalid_binary_operation(left, right));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_expr(&mut self, vars: &[Var], expr: &Expr) -> Result<Type> {
+        if let Some((op, left, right)) = expr.binary_op() {
+            let left_type = self.check_expr(vars, left)?;
+            let right_type = self.check_expr(vars, right)?;
+            let valid = match op {
+                BinaryOpType::Add
+                | BinaryOpType::Sub
+                | BinaryOpType::Mul
+                | BinaryOpType::Div
+                | BinaryOpType::Rem => {
+                    left_type == right_type
+                        && matches!(left_type, Type::Int | Type::UInt | Type::Float)
+                }
+                BinaryOpType::BitAnd | BinaryOpType::BitOr | BinaryOpType::BitXor => {
+                    left_type == right_type && matches!(left_type, Type::Int | Type::UInt)
+                }
+                BinaryOpType::ShiftLeft | BinaryOpType::ShiftRight => {
+                    matches!(left_type, Type::Int | Type::UInt)
+                        && matches!(right_type, Type::Int | Type::UInt)
+                }
+            };
+            if !valid {
+                return Err(self.invalid_binary_operation(left, right));
+            }
+            return Ok(left_type);
+        }
+        match expr {
+            Expr::Static { type_, .. } | Expr::StaticAddress { type_, .. } => Ok(*type_),
+            Expr::Variable { index, .. } => Ok(vars[*index].type_),
+            Expr::Int { .. } => Ok(Type::Int),
+            Expr::UInt { .. } => Ok(Type::UInt),
+            Expr::Float { .. } => Ok(Type::Float),
+            Expr::String { .. } => Ok(Type::String),
+            Expr::CastInt { span, expr: inner } | Expr::CastFloat { span, expr: inner } => {
+                let from = self.check_expr(vars, inner)?;
+                if !matches!(from, Type::Int | Type::UInt | Type::Float) {
+                    let span = span.clone();
+                    return Err(self.error(|file, src| Error::InvalidCast { file, src, span }));
+                }
+                Ok(expr.type_(vars))
+            }
+            Expr::CastUInt { span, expr } => {
+                let from = self.check_expr(vars, expr)?;
+                if !matches!(from, Type::Int | Type::UInt | Type::Float | Type::String) {
+                    let span = span.clone();
+                    return Err(self.error(|file, src| Error::InvalidCast { file, src, span }));
+                }
+                Ok(Type::UInt)
+            }
+            Expr::BitNot { span, expr } => {
+                let type_ = self.check_expr(vars, expr)?;
+                if !matches!(type_, Type::Int | Type::UInt) {
+                    let span = span.clone();
+                    return Err(self.error(|file, src| Error::InvalidBitNot { file, src, span }));
+                }
+                Ok(type_)
+            }
+            Expr::Call {
+                coord,
+                params,
+                type_,
+                ..
+            } => {
+                self.check_call(vars, *coord, params)?;
+                Ok(*type_)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn check_call(&mut self, vars: &[Var], coord: Coord, params: &[Expr]) -> Result<()> {
+        let param_types = self.param_types(coord);
+        for (i, param) in params.iter().enumerate() {
+            let type_ = self.check_expr(vars, param)?;
+            let Some(param_types) = &param_types else {
+                continue;
+            };
+            if type_ != param_types[i] {
+                return Err(self.mismatch(param.span(), param_types[i], type_));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the parameter types of the callee if it has a signature.
+    fn param_types(&self, coord: Coord) -> Option<Vec<Type>> {
+        let func = if coord.module == self.module_index {
+            &self.dialect.funcs[coord.element]
+        } else {
+            let dialect = self.task.modules[coord.module].dialect.as_ref().unwrap();
+            dialect.callable(coord.element)
+        };
+        if !func.has_signature() {
+            return None;
+        }
+        let types = func
+            .params
+            .iter()
+            .map(|(_, type_)| type_.to_ir().unwrap())
+            .collect();
+        Some(types)
+    }
+
+    fn mismatch(&mut self, span: Span, expected: Type, found: Type) -> Error {
+        self.error(|file, src| Error::MismatchedTypes {
+            file,
+            src,
+            span,
+            expected,
+            found,
+        })
+    }
+
+    fn invalid_binary_operation(&mut self, left: &Expr, right: &Expr) -> Error {
+        let left = left.span();
+        let right = right.span();
+        self.error(|file, src| Error::InvalidBinaryOperation {
+            file,
+            src,
+            left,
+            right,
+        })
+    }
+}
+
+/// Whether every path through the block ends in a return.
+///
+/// The condition of an `if` or a loop may be false, so only a return directly in the block counts.
+fn block_returns(block: &Block) -> bool {
+    block
+        .elements
+        .iter()
+        .any(|stmnt| matches!(stmnt, Stmnt::Return { .. }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        build::Build,
+        compiler::error::Error,
+        diagnostics::{Diagnostic, Diagnostics},
+    };
+
+    fn check(src: &str) -> Diagnostics {
+        Build::new().source("main", "main.lvt", src).check()
+    }
+
+    fn check_error(src: &str) -> Error {
+        let mut diagnostics = check(src).diagnostics;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        match diagnostics.pop().unwrap() {
+            Diagnostic::Compile(err) => err,
+            diagnostic => panic!("{diagnostic:?}"),
+        }
+    }
+
+    #[test]
+    fn test_binary_operation() {
+        let ok = "(mod code)\n(fn main [] {\n  (let x (+ 1 2))\n  (let y (<< 1 2u))\n})\n";
+        assert!(!check(ok).has_errors());
+        for expr in ["(+ 1 2.0)", "(& 1.0 2.0)", "(- \"a\" \"b\")", "(<< 1.0 2)"] {
+            let src = format!("(mod code)\n(fn main [] {{\n  (let x {expr})\n}})\n");
+            assert!(
+                matches!(check_error(&src), Error::InvalidBinaryOperation { .. }),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_cast() {
+        let ok = "(mod code)\n(fn main [] {\n  (let x (int 1.0))\n  (let y (float 1u))\n})\n";
+        assert!(!check(ok).has_errors());
+        for expr in ["(int \"a\")", "(float \"a\")"] {
+            let src = format!("(mod code)\n(fn main [] {{\n  (let x {expr})\n}})\n");
+            assert!(
+                matches!(check_error(&src), Error::InvalidCast { .. }),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_arguments() {
+        let f = "(fn f [:n int] int {\n  (return n)\n})\n";
+        let count = format!("(mod code)\n{f}(fn main [] {{\n  (f 1 2)\n}})\n");
+        assert!(matches!(
+            check_error(&count),
+            Error::InvalidCallSignature { .. }
+        ));
+        let type_ = format!("(mod code)\n{f}(fn main [] {{\n  (f 1.0)\n}})\n");
+        assert!(matches!(
+            check_error(&type_),
+            Error::MismatchedTypes {
+                expected: leviathan_ir::layers::Type::Int,
+                found: leviathan_ir::layers::Type::Float,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_empty_return() {
+        let src = "(mod code)\n(fn f [] int {\n  (return)\n})\n(fn main [] {\n  (f)\n})\n";
+        assert!(matches!(check_error(src), Error::MismatchedTypes { .. }));
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let main = "(fn main [] {\n  (f 1)\n})\n";
+        let src = format!("(mod code)\n(fn f [:n int] int {{\n  (let x n)\n}})\n{main}");
+        let Error::MissingReturn { span, .. } = check_error(&src) else {
+            panic!();
+        };
+        assert_eq!(&src[span], "}");
+        // A return inside of an `if` or a loop might not be reached
+        for stmnt in [
+            "(if (< n 0) {\n    (return n)\n  })",
+            "(while (< n 0) {\n    (return n)\n  })",
+        ] {
+            let src = format!("(mod code)\n(fn f [:n int] int {{\n  {stmnt}\n}})\n{main}");
+            assert!(
+                matches!(check_error(&src), Error::MissingReturn { .. }),
+                "{stmnt}"
+            );
+        }
+        let body = "(if (< n 0) {\n    (return 0)\n  })\n  (return n)";
+        let src = format!("(mod code)\n(fn f [:n int] int {{\n  {body}\n}})\n{main}");
+        assert!(!check(&src).has_errors());
+    }
+}
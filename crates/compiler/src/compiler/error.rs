@@ -1,14 +1,8 @@
-use std::{
-    ops::{Range, RangeFrom},
-    process::exit,
-};
+use std::ops::{Range, RangeFrom};
 
-use ariadne::Source;
+use leviathan_ir::layers::Type;
 
-use crate::util::{
-    ariadne::{error_report, span_error_report, span_error_report_with_note},
-    source::Span,
-};
+use crate::util::{ariadne::report, message::Message, source::Span};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -106,6 +100,11 @@ pub enum Error {
         src: String,
         span: Span,
     },
+    AmbiguousName {
+        file: String,
+        src: String,
+        span: Span,
+    },
     InvalidCallSignature {
         file: String,
         src: String,
@@ -169,6 +168,49 @@ pub enum Error {
         src: String,
         span: Span,
     },
+    UnknownVariable {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    InvalidConditionExpr {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    InvalidBinaryOperation {
+        file: String,
+        src: String,
+        left: Span,
+        right: Span,
+    },
+    InvalidCast {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    InvalidBitNot {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    MismatchedTypes {
+        file: String,
+        src: String,
+        span: Span,
+        expected: Type,
+        found: Type,
+    },
+    UnitValue {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    MissingReturn {
+        file: String,
+        src: String,
+        span: Span,
+    },
     IoError(std::io::Error),
 }
 
@@ -179,217 +221,320 @@ impl From<std::io::Error> for Error {
 }
 
 impl Error {
-    pub fn report(&self) {
-        let report;
-        let source;
-        let file: &str = match self {
-            Error::InvalidOperation => panic!("InvalidOperation"),
+    /// Describes the error independently of how it is rendered.
+    pub fn message(&self) -> Message<'_> {
+        match self {
+            Error::InvalidOperation => Message::error(
+                "invalid_operation",
+                "",
+                "The compile task was used in an invalid order",
+            ),
             Error::NoMainFound { file } => {
-                report = error_report(file, "No main function was found");
-                source = Source::from("");
-                file
-            }
-            Error::DuplicateModule { file, name } => {
-                report = error_report(
-                    file,
-                    &format!("A module with the name '{name}' already exists"),
-                );
-                source = Source::from("");
-                file
-            }
-            Error::EmptyModule { name, file } => {
-                report = error_report(file, &format!("The module '{name}' is empty"));
-                source = Source::from("");
-                file
-            }
-            Error::InvalidModuleDeclaration { file, src, span } => {
-                report = span_error_report(file, span, "This module declaration is not valid");
-                source = Source::from(src);
-                file
-            }
-            Error::UnknownModuleDialect { file, src, span } => {
-                report = span_error_report(file, span, "This module dialect is unknown");
-                source = Source::from(src);
-                file
+                Message::error("no_main_found", file, "No main function was found")
             }
+            Error::DuplicateModule { file, name } => Message::error(
+                "duplicate_module",
+                file,
+                format!("A module with the name '{name}' already exists"),
+            ),
+            Error::EmptyModule { name, file } => Message::error(
+                "empty_module",
+                file,
+                format!("The module '{name}' is empty"),
+            ),
+            Error::InvalidModuleDeclaration { file, src, span } => Message::span_error(
+                "invalid_module_declaration",
+                file,
+                src,
+                span,
+                "This module declaration is not valid",
+            ),
+            Error::UnknownModuleDialect { file, src, span } => Message::span_error(
+                "unknown_module_dialect",
+                file,
+                src,
+                span,
+                "This module dialect is unknown",
+            ),
             Error::EmptyNode { file, src, span } => {
-                report = span_error_report(file, span, "This node must not be empty");
-                source = Source::from(src);
-                file
-            }
-            Error::UnexpectedToken { file, src, span } => {
-                report = span_error_report(file, span, "This token is not valid here");
-                source = Source::from(src);
-                file
-            }
-            Error::UnexpectedTokens { file, src, span } => {
-                report = span_error_report(file, span, "These tokens are not valid here");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidKeyword { file, src, span } => {
-                report = span_error_report(file, span, "This keyword is not valid");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidStatement { file, src, span } => {
-                report = span_error_report(file, span, "This statement is not valid");
-                source = Source::from(src);
-                file
+                Message::span_error("empty_node", file, src, span, "This node must not be empty")
             }
+            Error::UnexpectedToken { file, src, span } => Message::span_error(
+                "unexpected_token",
+                file,
+                src,
+                span,
+                "This token is not valid here",
+            ),
+            Error::UnexpectedTokens { file, src, span } => Message::span_error(
+                "unexpected_tokens",
+                file,
+                src,
+                span,
+                "These tokens are not valid here",
+            ),
+            Error::InvalidKeyword { file, src, span } => Message::span_error(
+                "invalid_keyword",
+                file,
+                src,
+                span,
+                "This keyword is not valid",
+            ),
+            Error::InvalidStatement { file, src, span } => Message::span_error(
+                "invalid_statement",
+                file,
+                src,
+                span,
+                "This statement is not valid",
+            ),
             Error::InvalidType { file, src, span } => {
-                report = span_error_report(file, span, "This type is not valid");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidBracketType { file, src, span } => {
-                report = span_error_report(file, span, "This bracket type is not allowed here");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidParams { file, src, span } => {
-                report = span_error_report(file, span, "These function parameters are not valid");
-                source = Source::from(src);
-                file
-            }
-            Error::DuplicateName { file, src, span } => {
-                report = span_error_report(file, span, "This name is already in use");
-                source = Source::from(src);
-                file
-            }
-            Error::DuplicateImport { file, src, span } => {
-                report = span_error_report(file, span, "This module is already imported");
-                source = Source::from(src);
-                file
-            }
-            Error::UnknownModule { file, src, span } => {
-                report = span_error_report(file, span, "This module could not be found");
-                source = Source::from(src);
-                file
-            }
-            Error::UnknownFunc { file, src, span } => {
-                report = span_error_report(file, span, "This function could not be found");
-                source = Source::from(src);
-                file
-            }
-            Error::UnknownStaticFunc { file, src, span } => {
-                report = span_error_report(file, span, "This static function is not known");
-                source = Source::from(src);
-                file
-            }
-            Error::UnknownStaticVariable { file, src, span } => {
-                report = span_error_report(file, span, "This static variable does not exist");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidCallSignature { file, src, span } => {
-                report = span_error_report(
-                    file,
-                    span,
-                    "This call signature does not match the function signature",
-                );
-                source = Source::from(src);
-                file
+                Message::span_error("invalid_type", file, src, span, "This type is not valid")
             }
+            Error::InvalidBracketType { file, src, span } => Message::span_error(
+                "invalid_bracket_type",
+                file,
+                src,
+                span,
+                "This bracket type is not allowed here",
+            ),
+            Error::InvalidParams { file, src, span } => Message::span_error(
+                "invalid_params",
+                file,
+                src,
+                span,
+                "These function parameters are not valid",
+            ),
+            Error::DuplicateName { file, src, span } => Message::span_error(
+                "duplicate_name",
+                file,
+                src,
+                span,
+                "This name is already in use",
+            ),
+            Error::DuplicateImport { file, src, span } => Message::span_error(
+                "duplicate_import",
+                file,
+                src,
+                span,
+                "This module is already imported",
+            ),
+            Error::UnknownModule { file, src, span } => Message::span_error(
+                "unknown_module",
+                file,
+                src,
+                span,
+                "This module could not be found",
+            ),
+            Error::UnknownFunc { file, src, span } => Message::span_error(
+                "unknown_func",
+                file,
+                src,
+                span,
+                "This function could not be found",
+            ),
+            Error::UnknownStaticFunc { file, src, span } => Message::span_error(
+                "unknown_static_func",
+                file,
+                src,
+                span,
+                "This static function is not known",
+            ),
+            Error::UnknownStaticVariable { file, src, span } => Message::span_error(
+                "unknown_static_variable",
+                file,
+                src,
+                span,
+                "This static variable does not exist",
+            ),
+            Error::AmbiguousName { file, src, span } => Message::span_error(
+                "ambiguous_name",
+                file,
+                src,
+                span,
+                "This name is exported by more than one imported module",
+            )
+            .with_note("The name can be qualified with the module path, like 'module::name'"),
+            Error::InvalidCallSignature { file, src, span } => Message::span_error(
+                "invalid_call_signature",
+                file,
+                src,
+                span,
+                "This call signature does not match the function signature",
+            ),
             Error::InvalidCondition { file, src, span } => {
-                report = span_error_report_with_note(
-                    file,
-                    span,
-                    "Invalid condition",
-                    "Valid conditions are '=', '!=', '<', '>', '<=', '>=', '!0' and '=0'",
-                );
-                source = Source::from(src);
-                file
+                Message::span_error("invalid_condition", file, src, span, "Invalid condition")
+                    .with_note(
+                        "Valid conditions are '=', '!=', '<', '>', '<=', '>=', '!0' and '=0'",
+                    )
             }
             Error::InvalidRegister { file, src, span } => {
-                report = span_error_report(file, span, "Invalid register");
-                source = Source::from(src);
-                file
-            }
-            Error::SelfImport { file, src, span } => {
-                report =
-                    span_error_report(file, span, "A module cannot be imported inside of itself");
-                source = Source::from(src);
-                file
+                Message::span_error("invalid_register", file, src, span, "Invalid register")
             }
+            Error::SelfImport { file, src, span } => Message::span_error(
+                "self_import",
+                file,
+                src,
+                span,
+                "A module cannot be imported inside of itself",
+            ),
             Error::NotInSizeRangeFrom {
                 file,
                 src,
                 span,
                 range,
-            } => {
-                report = span_error_report(
-                    file,
-                    span,
-                    &format!("This number must be bigger or equal to {}", range.start),
-                );
-                source = Source::from(src);
-                file
-            }
+            } => Message::span_error(
+                "not_in_size_range_from",
+                file,
+                src,
+                span,
+                format!("This number must be bigger or equal to {}", range.start),
+            ),
             Error::NotInSizeRange {
                 file,
                 src,
                 span,
                 range,
-            } => {
-                report = span_error_report(
-                    file,
-                    span,
-                    &format!("This number must be in range {range:?}"),
-                );
-                source = Source::from(src);
-                file
-            }
+            } => Message::span_error(
+                "not_in_size_range",
+                file,
+                src,
+                span,
+                format!("This number must be in range {range:?}"),
+            ),
             Error::NotInI64Range {
                 file,
                 src,
                 span,
                 range,
-            } => {
-                report = span_error_report(
-                    file,
-                    span,
-                    &format!("This number must be in range {range:?}"),
-                );
-                source = Source::from(src);
-                file
-            }
-            Error::NegativeNumber { file, src, span } => {
-                report = span_error_report(file, span, "This number must not be negative");
-                source = Source::from(src);
-                file
-            }
-            Error::OversizedNumber { file, src, span } => {
-                report = span_error_report(file, span, "This number is too big");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidByte { file, src, span } => {
-                report = span_error_report(file, span, "This number does not fit into a byte");
-                source = Source::from(src);
-                file
-            }
-            Error::EmptyBuffer { file, src, span } => {
-                report = span_error_report(file, span, "A buffer initialization must not be empty");
-                source = Source::from(src);
-                file
-            }
-            Error::EmptyArray { file, src, span } => {
-                report = span_error_report(file, span, "This array must not be empty");
-                source = Source::from(src);
-                file
-            }
-            Error::IoError(err) => {
-                report = error_report("", &format!("I/O: {err}"));
-                source = Source::from("");
-                ""
-            }
-        };
-        report.eprint((file, source)).unwrap();
+            } => Message::span_error(
+                "not_in_i64_range",
+                file,
+                src,
+                span,
+                format!("This number must be in range {range:?}"),
+            ),
+            Error::NegativeNumber { file, src, span } => Message::span_error(
+                "negative_number",
+                file,
+                src,
+                span,
+                "This number must not be negative",
+            ),
+            Error::OversizedNumber { file, src, span } => Message::span_error(
+                "oversized_number",
+                file,
+                src,
+                span,
+                "This number is too big",
+            ),
+            Error::InvalidByte { file, src, span } => Message::span_error(
+                "invalid_byte",
+                file,
+                src,
+                span,
+                "This number does not fit into a byte",
+            ),
+            Error::EmptyBuffer { file, src, span } => Message::span_error(
+                "empty_buffer",
+                file,
+                src,
+                span,
+                "A buffer initialization must not be empty",
+            ),
+            Error::EmptyArray { file, src, span } => Message::span_error(
+                "empty_array",
+                file,
+                src,
+                span,
+                "This array must not be empty",
+            ),
+            Error::UnknownVariable { file, src, span } => Message::span_error(
+                "unknown_variable",
+                file,
+                src,
+                span,
+                "This variable does not exist",
+            ),
+            Error::InvalidConditionExpr { file, src, span } => Message::span_error(
+                "invalid_condition_expr",
+                file,
+                src,
+                span,
+                "Invalid condition",
+            )
+            .with_note(
+                "Valid conditions are '=', '!=', '<', '>', '<=', '>=', 'not', 'and' and 'or'",
+            ),
+            Error::InvalidBinaryOperation {
+                file,
+                src,
+                left,
+                right,
+            } => Message::double_span_error(
+                "invalid_binary_operation",
+                file,
+                src,
+                (left, right),
+                ("left", "right"),
+                "This operation is not valid for these operands",
+            ),
+            Error::InvalidCast { file, src, span } => {
+                Message::span_error("invalid_cast", file, src, span, "This cast is not valid")
+            }
+            Error::InvalidBitNot { file, src, span } => Message::span_error(
+                "invalid_bit_not",
+                file,
+                src,
+                span,
+                "This value cannot be inverted",
+            ),
+            Error::MismatchedTypes {
+                file,
+                src,
+                span,
+                expected,
+                found,
+            } => Message::span_error(
+                "mismatched_types",
+                file,
+                src,
+                span,
+                format!(
+                    "Expected a value of type '{}' but found '{}'",
+                    type_name(*expected),
+                    type_name(*found)
+                ),
+            ),
+            Error::UnitValue { file, src, span } => Message::span_error(
+                "unit_value",
+                file,
+                src,
+                span,
+                "This expression has no value",
+            ),
+            Error::MissingReturn { file, src, span } => Message::span_error(
+                "missing_return",
+                file,
+                src,
+                span,
+                "The function can reach its end without returning a value",
+            ),
+            Error::IoError(err) => Message::error("io_error", "", format!("I/O: {err}")),
+        }
     }
 
-    pub fn abort(&self) -> ! {
-        self.report();
-        exit(1);
+    pub fn report(&self) {
+        report(&self.message());
+    }
+}
+
+/// Returns the name of a type as written in the code dialect.
+fn type_name(type_: Type) -> &'static str {
+    match type_ {
+        Type::Unit => "unit",
+        Type::Int => "int",
+        Type::UInt => "uint",
+        Type::Float => "float",
+        Type::String => "str",
     }
 }
@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+
+use leviathan_ir::layers::{
+    lower::{LowOp, Reg},
+    Coord,
+};
+
+use crate::util::source::Span;
+
+use super::{warning::Warning, CompileTask};
+
+impl CompileTask {
+    /// Warns about statics, functions and imports that are never used.
+    ///
+    /// Only private functions and statics are reported, as public ones might be used by other
+    /// projects.
+    pub(super) fn lint(&mut self) {
+        let mut used_statics = HashSet::new();
+        let mut used_funcs = HashSet::new();
+        let mut used_imports = HashSet::new();
+        for (module_index, module) in &self.binary.modules {
+            for op in module.funcs.values().flat_map(|func| &func.ops) {
+                match op {
+                    LowOp::Call { coord } => {
+                        used_funcs.insert(*coord);
+                        used_imports.insert((*module_index, coord.module));
+                    }
+                    LowOp::LoadStatic64 { coord, .. } | LowOp::LoadStaticAddress { coord, .. } => {
+                        used_statics.insert(*coord);
+                        used_imports.insert((*module_index, coord.module));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (module_index, module) in self.modules.iter().enumerate() {
+            let dialect = module.dialect.as_ref().unwrap();
+            for (element, static_) in dialect.statics().iter().enumerate() {
+                let coord = Coord {
+                    module: module_index,
+                    element,
+                };
+                if static_.public || used_statics.contains(&coord) {
+                    continue;
+                }
+                self.diagnostics.warn(Warning::UnusedStatic {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
+                    span: static_.span.clone(),
+                });
+            }
+            for (element, func) in dialect.callables().iter().enumerate() {
+                let coord = Coord {
+                    module: module_index,
+                    element,
+                };
+                if func.public || used_funcs.contains(&coord) || self.main == Some(coord) {
+                    continue;
+                }
+                self.diagnostics.warn(Warning::UnusedFunction {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
+                    span: func.span.clone(),
+                });
+            }
+            for (import, span) in dialect.imports() {
+                if used_imports.contains(&(module_index, import)) {
+                    continue;
+                }
+                self.diagnostics.warn(Warning::UnusedImport {
+                    file: module.file.clone(),
+                    src: module.src.clone(),
+                    span,
+                });
+            }
+        }
+    }
+}
+
+/// Finds the persistent registers r16 to r31 that are written without being restored.
+///
+/// A register counts as restored if it is both stored and loaded again somewhere in the
+/// function, while the stack pointer r31 may only be moved by immediates. The order of the
+/// store, the load and the writes is not checked, so a register that is loaded before it is
+/// stored is still considered restored.
+/// The span of the first offending write is returned for every register.
+pub fn unrestored_writes(ops: &[LowOp]) -> Vec<(Reg, Span)> {
+    let mut stored = HashSet::new();
+    let mut loaded = HashSet::new();
+    for op in ops {
+        match op {
+            LowOp::Store8 { src, .. }
+            | LowOp::Store16 { src, .. }
+            | LowOp::Store32 { src, .. }
+            | LowOp::Store64 { src, .. } => {
+                stored.insert(src.value());
+            }
+            LowOp::Load8 { dst, .. }
+            | LowOp::Load16 { dst, .. }
+            | LowOp::Load32 { dst, .. }
+            | LowOp::Load64 { dst, .. } => {
+                loaded.insert(dst.value());
+            }
+            _ => {}
+        }
+    }
+    let mut writes: Vec<(Reg, Span)> = Vec::with_capacity(0);
+    let mut span = None;
+    for op in ops {
+        if let LowOp::PutSpan { span: op_span } = op {
+            span = Some(op_span.clone());
+            continue;
+        }
+        let Some(dst) = written_reg(op) else {
+            continue;
+        };
+        if dst.value() < 16 || writes.iter().any(|(reg, _)| *reg == dst) {
+            continue;
+        }
+        let restored = if dst.value() == 31 {
+            matches!(op, LowOp::AddImmediate { .. } | LowOp::SubImmediate { .. })
+        } else {
+            stored.contains(&dst.value()) && loaded.contains(&dst.value())
+        };
+        if restored {
+            continue;
+        }
+        if let Some(span) = &span {
+            writes.push((dst, span.clone()));
+        }
+    }
+    writes
+}
+
+fn written_reg(op: &LowOp) -> Option<Reg> {
+    match op {
+        LowOp::LoadStatic64 { dst, .. }
+        | LowOp::LoadLocalStatic64 { dst, .. }
+        | LowOp::LoadStaticAddress { dst, .. }
+        | LowOp::LoadLocalStaticAddress { dst, .. }
+        | LowOp::AddImmediate { dst, .. }
+        | LowOp::SubImmediate { dst, .. }
+        | LowOp::MulImmediate { dst, .. }
+        | LowOp::DivImmediate { dst, .. }
+        | LowOp::RemImmediate { dst, .. }
+        | LowOp::DivSignedImmediate { dst, .. }
+        | LowOp::RemSignedImmediate { dst, .. }
+        | LowOp::MoveImmediate { dst, .. }
+        | LowOp::MoveSignedImmediate { dst, .. }
+        | LowOp::ShiftLeftImmediate { dst, .. }
+        | LowOp::ShiftRightImmediate { dst, .. }
+        | LowOp::ShiftRightSignedImmediate { dst, .. }
+        | LowOp::Load8 { dst, .. }
+        | LowOp::Load16 { dst, .. }
+        | LowOp::Load32 { dst, .. }
+        | LowOp::Load64 { dst, .. }
+        | LowOp::Add { dst, .. }
+        | LowOp::Sub { dst, .. }
+        | LowOp::Mul { dst, .. }
+        | LowOp::Div { dst, .. }
+        | LowOp::Rem { dst, .. }
+        | LowOp::DivSigned { dst, .. }
+        | LowOp::RemSigned { dst, .. }
+        | LowOp::AddFloat { dst, .. }
+        | LowOp::SubFloat { dst, .. }
+        | LowOp::MulFloat { dst, .. }
+        | LowOp::DivFloat { dst, .. }
+        | LowOp::RemFloat { dst, .. }
+        | LowOp::And { dst, .. }
+        | LowOp::Or { dst, .. }
+        | LowOp::Xor { dst, .. }
+        | LowOp::ShiftLeft { dst, .. }
+        | LowOp::ShiftRight { dst, .. }
+        | LowOp::ShiftRightSigned { dst, .. }
+        | LowOp::Compare { dst, .. }
+        | LowOp::CompareSigned { dst, .. }
+        | LowOp::CompareFloat { dst, .. }
+        | LowOp::Not { dst, .. }
+        | LowOp::Move { dst, .. }
+        | LowOp::FloatToInt { dst, .. }
+        | LowOp::IntToFloat { dst, .. }
+        | LowOp::LoadBaseOffset { dst }
+        | LowOp::LoadProgramCounter { dst }
+        | LowOp::Zero { dst } => Some(*dst),
+        LowOp::Increment { reg } => Some(*reg),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use leviathan_ir::layers::lower::{LowOp, Reg};
+
+    use crate::{
+        build::Build,
+        compiler::warning::{Lint, LintLevel, Warning},
+        diagnostics::Diagnostic,
+    };
+
+    use super::unrestored_writes;
+
+    /// Compiles `main` and returns its only diagnostic, which has to be a warning.
+    fn warning(main: &str) -> Warning {
+        let output = Build::new().source("main", "main.lvt", main).compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        let mut diagnostics = output.diagnostics.diagnostics;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        let Diagnostic::Warning { warning, deny } = diagnostics.pop().unwrap() else {
+            panic!();
+        };
+        assert!(!deny);
+        warning
+    }
+
+    #[test]
+    fn test_unused_static() {
+        let main = "(mod asm)\n(-static x 1)\n(-label main (do\n  (halt)\n))\n";
+        assert!(matches!(warning(main), Warning::UnusedStatic { .. }));
+    }
+
+    #[test]
+    fn test_unused_function() {
+        let main = "(mod asm)\n(-label f (do\n  (halt)\n))\n(-label main (do\n  (halt)\n))\n";
+        assert!(matches!(warning(main), Warning::UnusedFunction { .. }));
+    }
+
+    #[test]
+    fn test_unused_import() {
+        let main = "(mod asm)\n(use lib)\n(-label main (do\n  (halt)\n))\n";
+        let lib = "(mod asm)\n(+label f (do\n  (halt)\n))\n";
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .source("lib", "lib.lvt", lib)
+            .compile();
+        assert!(output.is_success(), "{:?}", output.diagnostics);
+        assert!(matches!(
+            output.diagnostics.diagnostics[..],
+            [Diagnostic::Warning {
+                warning: Warning::UnusedImport { .. },
+                deny: false,
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_dead_code() {
+        let main = "(mod asm)\n(-label main (do\n  (halt)\n  (nop)\n))\n";
+        assert!(matches!(warning(main), Warning::DeadCode { .. }));
+    }
+
+    #[test]
+    fn test_callee_saved_write() {
+        let main = "(mod asm)\n(-label main (do\n  (mov r16 1u)\n  (halt)\n))\n";
+        assert!(matches!(warning(main), Warning::CalleeSavedWrite { .. }));
+    }
+
+    #[test]
+    fn test_deny() {
+        let main = "(mod asm)\n(-static x 1)\n(-label main (do\n  (halt)\n))\n";
+        let output = Build::new()
+            .source("main", "main.lvt", main)
+            .lint(Lint::UnusedStatic, LintLevel::Deny)
+            .compile();
+        assert!(!output.is_success());
+        assert!(output.binary.is_none());
+        assert!(matches!(
+            output.diagnostics.diagnostics[..],
+            [Diagnostic::Warning {
+                warning: Warning::UnusedStatic { .. },
+                deny: true,
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_unrestored_writes() {
+        let reg = Reg::new(16);
+        let sp = Reg::new(31);
+        let write = || {
+            vec![
+                LowOp::PutSpan { span: 0..1 },
+                LowOp::MoveImmediate {
+                    dst: reg,
+                    immediate: 1,
+                },
+            ]
+        };
+        let restore = || {
+            vec![
+                LowOp::Store64 {
+                    dst: sp,
+                    src: reg,
+                    offset: -8,
+                },
+                LowOp::Load64 {
+                    dst: reg,
+                    src: sp,
+                    offset: -8,
+                },
+            ]
+        };
+        assert_eq!(unrestored_writes(&write()), [(reg, 0..1)]);
+        let ops: Vec<_> = write().into_iter().chain(restore()).collect();
+        assert!(unrestored_writes(&ops).is_empty());
+        // The order is not checked
+        let ops: Vec<_> = restore().into_iter().chain(write()).collect();
+        assert!(unrestored_writes(&ops).is_empty());
+    }
+}
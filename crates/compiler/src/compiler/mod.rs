@@ -2,7 +2,6 @@ use std::{
     collections::HashMap,
     fmt::Debug,
     io::{Seek, Write},
-    mem,
 };
 
 use leviathan_ir::{
@@ -11,7 +10,11 @@ use leviathan_ir::{
 };
 use phf::{phf_map, Map};
 
-use crate::parser::{BareModule, BracketType, Node};
+use crate::{
+    diagnostics::{Diagnostic, Diagnostics},
+    parser::{BareModule, BracketType, Node},
+    util::source::Span,
+};
 
 use self::{
     dialect::{assembly::AssemblyLanguage, code::CodeLanguage},
@@ -20,6 +23,8 @@ use self::{
 
 pub mod dialect;
 pub mod error;
+pub mod lint;
+pub mod warning;
 
 pub const DIALECTS: Map<&str, fn() -> Box<dyn Dialect>> = phf_map! {
     "asm" => || Box::<AssemblyLanguage>::default(),
@@ -42,6 +47,18 @@ pub trait Dialect {
     ) -> Result<BinaryModule>;
 
     fn lookup_callable(&self, name: &str) -> Option<usize>;
+
+    /// Returns the index of a public static.
+    fn lookup_static(&self, name: &str) -> Option<usize>;
+
+    fn callable(&self, index: usize) -> &Func;
+
+    fn callables(&self) -> &[Func];
+
+    fn statics(&self) -> &[Static];
+
+    /// Returns the imported modules with the spans of their `use` statements.
+    fn imports(&self) -> Vec<(usize, Span)>;
 }
 
 #[derive(Debug)]
@@ -52,6 +69,9 @@ pub struct CompileTask {
     pub main: Option<Coord>,
     pub collect_offsets: bool,
     pub binary: Binary,
+    pub diagnostics: Diagnostics,
+    /// Dependency packages, whose modules are prefixed with the package name
+    pub packages: Vec<String>,
 }
 
 impl Default for CompileTask {
@@ -63,6 +83,8 @@ impl Default for CompileTask {
             main: None,
             collect_offsets: false,
             binary: Binary::default(),
+            diagnostics: Diagnostics::default(),
+            packages: Vec::with_capacity(0),
         }
     }
 }
@@ -75,7 +97,26 @@ impl CompileTask {
         }
     }
 
-    pub fn include(
+    /// Adds a module to the task.
+    ///
+    /// Errors in the module are collected in `diagnostics`, so further modules can still be
+    /// included.
+    pub fn include(&mut self, module: BareModule, main: bool) -> Result<()> {
+        if self.status != Status::Open || main && self.main.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+        if let Err(err) = self.include_module(module, main) {
+            self.exclude(err);
+        }
+        Ok(())
+    }
+
+    /// Records a module that could not be parsed or included.
+    pub fn exclude(&mut self, err: impl Into<Diagnostic>) {
+        self.diagnostics.push(err);
+    }
+
+    fn include_module(
         &mut self,
         BareModule {
             name,
@@ -85,10 +126,6 @@ impl CompileTask {
         }: BareModule,
         main: bool,
     ) -> Result<()> {
-        if self.status != Status::Open || main && self.main.is_some() {
-            return Err(Error::InvalidOperation);
-        }
-        self.status = Status::Invalid;
         if self.module_indices.contains_key(&name) {
             return Err(Error::DuplicateModule { file, name });
         }
@@ -140,15 +177,23 @@ impl CompileTask {
         };
         let dialect = dialect_supplier();
         let module_index = self.modules.len();
-        self.modules.push(Module::new(file, src, dialect));
+        let package = self
+            .packages
+            .iter()
+            .find(|package| name.starts_with(&format!("{package}::")))
+            .cloned();
+        self.modules.push(Module::new(file, src, package, dialect));
         self.module_indices.insert(name, module_index);
         let mut dialect = self.modules[module_index].take_dialect();
-        dialect.collect(self, module_index, UncollectedModule { root }, main)?;
+        let result = dialect.collect(self, module_index, UncollectedModule { root }, main);
         self.modules[module_index].dialect = Some(dialect);
-        self.status = Status::Open;
-        Ok(())
+        result
     }
 
+    /// Compiles all included modules and collects the errors and warnings in `diagnostics`.
+    ///
+    /// Modules are compiled even if others could not be parsed or included. The task can only
+    /// be filtered and assembled if no errors were found.
     pub fn compile(&mut self) -> Result<()> {
         if self.status != Status::Open {
             return Err(Error::InvalidOperation);
@@ -156,30 +201,46 @@ impl CompileTask {
         self.status = Status::Invalid;
         for i in 0..self.modules.len() {
             let mut dialect = self.modules[i].take_dialect();
-            let binary_module = dialect.compile_module(self, i)?;
-            self.binary.modules.insert(i, binary_module);
+            match dialect.compile_module(self, i) {
+                Ok(binary_module) => {
+                    self.binary.modules.insert(i, binary_module);
+                }
+                Err(err) => self.diagnostics.push(err),
+            }
             self.modules[i].dialect = Some(dialect);
         }
-        self.status = Status::Compiled;
+        if self.diagnostics.has_errors() {
+            return Ok(());
+        }
+        // Denied lints prevent the task from being assembled as well
+        self.lint();
+        if !self.diagnostics.has_errors() {
+            self.status = Status::Compiled;
+        }
         Ok(())
     }
 
+    /// Looks up an imported module, preferring modules of the same package.
+    pub fn lookup_module(&self, module_index: usize, path: &str) -> Option<usize> {
+        if let Some(package) = &self.modules[module_index].package {
+            if let Some(index) = self.module_indices.get(&format!("{package}::{path}")) {
+                return Some(*index);
+            }
+        }
+        self.module_indices.get(path).cloned()
+    }
+
     pub fn filter(&mut self) -> Result<()> {
         if self.status != Status::Compiled {
             return Err(Error::InvalidOperation);
         }
         self.status = Status::Invalid;
-        assert!(self.main.is_some());
-        let _main = self.main.unwrap();
-        for _module in &mut self.modules {
-            // module.used = true;
-            // for static_ in &mut module.statics {
-            //     static_.used = true;
-            // }
-            // for func in &mut module.funcs {
-            //     func.used = true;
-            // }
-        }
+        let Some(main) = self.main else {
+            return Err(Error::NoMainFound {
+                file: String::new(),
+            });
+        };
+        self.binary.filter(main);
         self.status = Status::Filtered;
         Ok(())
     }
@@ -193,8 +254,12 @@ impl CompileTask {
             return Err(Error::InvalidOperation);
         }
         self.status = Status::Invalid;
-        assert!(self.main.is_some());
-        self.binary.assemble(out, offset_out, self.main.unwrap())?;
+        let Some(main) = self.main else {
+            return Err(Error::NoMainFound {
+                file: String::new(),
+            });
+        };
+        self.binary.assemble(out, offset_out, main)?;
         Ok(())
     }
 }
@@ -202,6 +267,8 @@ impl CompileTask {
 pub struct Module {
     pub file: String,
     pub src: String,
+    /// The dependency package the module belongs to
+    pub package: Option<String>,
     pub dialect: Option<Box<dyn Dialect>>,
 }
 
@@ -210,27 +277,26 @@ impl Debug for Module {
         f.debug_struct("Module")
             .field("file", &self.file)
             .field("src", &self.src)
+            .field("package", &self.package)
             .finish()
     }
 }
 
 impl Module {
-    pub fn new(file: String, src: String, dialect: Box<dyn Dialect>) -> Self {
+    pub fn new(
+        file: String,
+        src: String,
+        package: Option<String>,
+        dialect: Box<dyn Dialect>,
+    ) -> Self {
         Self {
             file,
             src,
+            package,
             dialect: Some(dialect),
         }
     }
 
-    pub fn take_file(&mut self) -> String {
-        mem::replace(&mut self.file, String::with_capacity(0))
-    }
-
-    pub fn take_src(&mut self) -> String {
-        mem::replace(&mut self.src, String::with_capacity(0))
-    }
-
     pub fn take_dialect(&mut self) -> Box<dyn Dialect> {
         self.dialect.take().unwrap()
     }
@@ -238,14 +304,26 @@ impl Module {
 
 #[derive(Debug)]
 pub struct Func {
+    /// The span of the name
+    pub span: Span,
     pub public: bool,
     pub params: Vec<(Option<String>, Type)>,
     pub return_: Type,
     pub data: FuncData,
 }
 
+impl Func {
+    /// Returns false for assembly labels declared without a signature.
+    pub fn has_signature(&self) -> bool {
+        self.return_ != Type::Unknown
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Static {
+    /// The span of the name
+    pub span: Span,
+    pub public: bool,
     pub node: Node,
 }
 
@@ -254,7 +332,7 @@ pub struct FuncData {
     pub node: Node,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Type {
     Unit,
     Int,
@@ -264,6 +342,19 @@ pub enum Type {
     Unknown,
 }
 
+impl Type {
+    pub fn to_ir(self) -> Option<leviathan_ir::layers::Type> {
+        match self {
+            Type::Unit => Some(leviathan_ir::layers::Type::Unit),
+            Type::Int => Some(leviathan_ir::layers::Type::Int),
+            Type::UInt => Some(leviathan_ir::layers::Type::UInt),
+            Type::Float => Some(leviathan_ir::layers::Type::Float),
+            Type::String => Some(leviathan_ir::layers::Type::String),
+            Type::Unknown => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Status {
     Open,
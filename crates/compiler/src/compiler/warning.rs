@@ -0,0 +1,148 @@
+use crate::util::{
+    ariadne::report,
+    message::{Message, Severity},
+    source::Span,
+};
+
+pub const LINTS: [Lint; 5] = [
+    Lint::UnusedStatic,
+    Lint::UnusedFunction,
+    Lint::UnusedImport,
+    Lint::DeadCode,
+    Lint::CalleeSavedWrite,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedStatic,
+    UnusedFunction,
+    UnusedImport,
+    DeadCode,
+    CalleeSavedWrite,
+}
+
+impl Lint {
+    /// The name used for the lint in the `[lints]` table of `build.lvt.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedStatic => "unused_static",
+            Lint::UnusedFunction => "unused_function",
+            Lint::UnusedImport => "unused_import",
+            Lint::DeadCode => "dead_code",
+            Lint::CalleeSavedWrite => "callee_saved_write",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        LINTS.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(LintLevel::Allow),
+            "warn" => Some(LintLevel::Warn),
+            "deny" => Some(LintLevel::Deny),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Warning {
+    UnusedStatic {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    UnusedFunction {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    UnusedImport {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    DeadCode {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    CalleeSavedWrite {
+        file: String,
+        src: String,
+        span: Span,
+        reg: u8,
+    },
+}
+
+impl Warning {
+    pub fn lint(&self) -> Lint {
+        match self {
+            Warning::UnusedStatic { .. } => Lint::UnusedStatic,
+            Warning::UnusedFunction { .. } => Lint::UnusedFunction,
+            Warning::UnusedImport { .. } => Lint::UnusedImport,
+            Warning::DeadCode { .. } => Lint::DeadCode,
+            Warning::CalleeSavedWrite { .. } => Lint::CalleeSavedWrite,
+        }
+    }
+
+    /// Describes the warning, which is an error if its lint is denied.
+    pub fn message(&self, deny: bool) -> Message<'_> {
+        let name = self.lint().name();
+        let note = if deny {
+            format!("'{name}' is set to deny")
+        } else {
+            format!("'{name}' can be allowed in the [lints] table of build.lvt.toml")
+        };
+        let (file, src, span, msg) = match self {
+            Warning::UnusedStatic { file, src, span } => {
+                (file, src, span, "This static is never used".to_string())
+            }
+            Warning::UnusedFunction { file, src, span } => {
+                (file, src, span, "This function is never called".to_string())
+            }
+            Warning::UnusedImport { file, src, span } => {
+                (file, src, span, "This import is never used".to_string())
+            }
+            Warning::DeadCode { file, src, span } => {
+                (file, src, span, "This code is unreachable".to_string())
+            }
+            Warning::CalleeSavedWrite {
+                file,
+                src,
+                span,
+                reg,
+            } => (
+                file,
+                src,
+                span,
+                format!("This writes the persistent register r{reg} without restoring it"),
+            ),
+        };
+        let severity = if deny {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        Message::span_error(name, file, src, span, msg)
+            .with_severity(severity)
+            .with_note(note)
+    }
+
+    /// Reports the warning, or an error if its lint is denied.
+    pub fn report(&self, deny: bool) {
+        report(&self.message(deny));
+    }
+}
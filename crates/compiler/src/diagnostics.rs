@@ -0,0 +1,226 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::{
+    compiler::{
+        self,
+        warning::{Lint, LintLevel, Warning},
+    },
+    parser,
+    util::{
+        ariadne::report,
+        message::{Message, MessageFormat},
+    },
+};
+
+#[derive(Debug)]
+pub enum Diagnostic {
+    Parse(parser::error::Error),
+    Compile(compiler::error::Error),
+    Warning { warning: Warning, deny: bool },
+}
+
+impl Diagnostic {
+    pub fn message(&self) -> Message<'_> {
+        match self {
+            Diagnostic::Parse(err) => err.message(),
+            Diagnostic::Compile(err) => err.message(),
+            Diagnostic::Warning { warning, deny } => warning.message(*deny),
+        }
+    }
+
+    pub fn report(&self) {
+        report(&self.message());
+    }
+
+    /// Returns false for warnings of lints that are not denied.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Diagnostic::Warning { deny: false, .. })
+    }
+}
+
+impl From<parser::error::Error> for Diagnostic {
+    fn from(value: parser::error::Error) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl From<compiler::error::Error> for Diagnostic {
+    fn from(value: compiler::error::Error) -> Self {
+        Self::Compile(value)
+    }
+}
+
+/// Collects the errors and warnings of all modules of a build, so they can be reported at once.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Lints without an entry are warned about
+    pub lints: HashMap<Lint, LintLevel>,
+    /// Turns all warnings into errors
+    pub deny_warnings: bool,
+    pub format: MessageFormat,
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: impl Into<Diagnostic>) {
+        self.diagnostics.push(diagnostic.into());
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        match self.lints.get(&lint).cloned().unwrap_or_default() {
+            LintLevel::Warn if self.deny_warnings => LintLevel::Deny,
+            level => level,
+        }
+    }
+
+    /// Records the warning according to the level of its lint.
+    pub fn warn(&mut self, warning: Warning) {
+        let deny = match self.level(warning.lint()) {
+            LintLevel::Allow => return,
+            LintLevel::Warn => false,
+            LintLevel::Deny => true,
+        };
+        self.diagnostics.push(Diagnostic::Warning { warning, deny });
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.diagnostics.len() - self.error_count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    /// Writes all diagnostics to `out` in the configured format.
+    pub fn report(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        for diagnostic in &self.diagnostics {
+            diagnostic.message().emit(self.format, out)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the amount of errors and warnings, like `Aborting due to 1 error`.
+    pub fn summary(&self) -> Option<String> {
+        let warnings = match self.warning_count() {
+            0 => String::new(),
+            1 => "1 warning".to_string(),
+            count => format!("{count} warnings"),
+        };
+        let summary = match self.error_count() {
+            0 if !warnings.is_empty() => format!("Emitted {warnings}"),
+            0 => return None,
+            1 if !warnings.is_empty() => format!("Aborting due to 1 error and {warnings}"),
+            1 => "Aborting due to 1 error".to_string(),
+            count if !warnings.is_empty() => {
+                format!("Aborting due to {count} errors and {warnings}")
+            }
+            count => format!("Aborting due to {count} errors"),
+        };
+        Some(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parser::error::Error, util::message::MessageFormat};
+
+    use super::Diagnostics;
+
+    /// Reports an unclosed parenthesis at each of the offsets as JSON.
+    fn report(src: &str, offsets: &[usize]) -> String {
+        report_file("main.lvt", src, offsets)
+    }
+
+    fn report_file(file: &str, src: &str, offsets: &[usize]) -> String {
+        let mut diagnostics = Diagnostics {
+            format: MessageFormat::Json,
+            ..Default::default()
+        };
+        for offset in offsets {
+            diagnostics.push(Error::UnclosedParenthesis {
+                file: file.to_string(),
+                src: src.to_string(),
+                span: *offset..offset + 1,
+            });
+        }
+        let mut out = Vec::new();
+        diagnostics.report(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            report("(nop)\n(do", &[6]),
+            "{\"severity\":\"error\",\"code\":\"unclosed_parenthesis\",\"file\":\"main.lvt\",\
+            \"span\":{\"start\":6,\"end\":7,\"line\":2,\"column\":1,\"end_line\":2,\"end_column\":2},\
+            \"message\":\"This parenthesis must be closed\",\"labels\":[{\"span\":{\"start\":6,\
+            \"end\":7,\"line\":2,\"column\":1,\"end_line\":2,\"end_column\":2},\
+            \"message\":\"This parenthesis must be closed\"}],\"notes\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn test_json_line_per_diagnostic() {
+        let out = report("((", &[0, 1]);
+        assert_eq!(out.lines().count(), 2);
+        assert!(out
+            .lines()
+            .all(|line| line.starts_with('{') && line.ends_with('}')));
+    }
+
+    #[test]
+    fn test_json_column_counts_chars() {
+        // "ä" is two bytes long, but one column wide
+        let out = report("\"ä\" (", &[5]);
+        assert!(out.contains("\"start\":5,\"end\":6,\"line\":1,\"column\":5,"));
+    }
+
+    #[test]
+    fn test_json_escapes() {
+        let out = report_file("a \"b\"\\c.lvt", "(", &[0]);
+        assert!(out.contains("\"file\":\"a \\\"b\\\"\\\\c.lvt\""));
+    }
+
+    #[test]
+    fn test_json_double_span() {
+        let mut diagnostics = Diagnostics {
+            format: MessageFormat::Json,
+            ..Default::default()
+        };
+        diagnostics.push(Error::MissmatchBrackets {
+            file: "main.lvt".to_string(),
+            src: "(]".to_string(),
+            span_a: 0..1,
+            span_b: 1..2,
+        });
+        let mut out = Vec::new();
+        diagnostics.report(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\"message\":\"These brackets do not match\",\"labels\""));
+        assert!(out.contains("\"message\":\"start\"}"));
+        assert!(out.contains("\"message\":\"end\"}"));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut diagnostics = Diagnostics::default();
+        assert_eq!(diagnostics.summary(), None);
+        diagnostics.push(Error::UnclosedParenthesis {
+            file: String::new(),
+            src: "(".to_string(),
+            span: 0..1,
+        });
+        assert_eq!(
+            diagnostics.summary().as_deref(),
+            Some("Aborting due to 1 error")
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::parser::{cst::build_cst, error::Error, parse, BracketType, ConcreteModule, CstNode};
+
+const INDENT: &str = "  ";
+
+/// Formats a module in the canonical layout.
+///
+/// The source is parsed first, so that invalid modules are reported instead of being changed.
+pub fn format_source(name: String, file: String, src: String) -> Result<String, Vec<Error>> {
+    let (_, errors) = parse(name.clone(), file.clone(), src.clone());
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    match build_cst(name, file, src) {
+        Ok(module) => Ok(format_module(&module)),
+        Err(err) => Err(vec![err]),
+    }
+}
+
+/// Pretty-prints a concrete syntax tree in the canonical layout:
+///
+/// - top-level forms and the statements of `(do ...)` and `{...}` blocks go on their own
+///   lines, and blocks are indented by two spaces
+/// - the closing bracket of a block goes on its own line, after which the enclosing
+///   brackets follow directly, as in `))`
+/// - all other lists are written on one line, unless a comment forces a line break
+/// - comments stay on their line and single blank lines between statements are kept
+///
+/// Formatting a formatted module does not change it.
+pub fn format_module(module: &ConcreteModule) -> String {
+    let mut printer = Printer {
+        src: &module.src,
+        out: String::new(),
+    };
+    printer.body(&items(&module.src, &module.root), 0);
+    let mut out = printer.out.trim_start().to_string();
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+enum Item<'a> {
+    Node(&'a CstNode),
+    Comment { node: &'a CstNode, trailing: bool },
+    BlankLine,
+}
+
+/// Drops the whitespace between nodes, remembering only what matters to the layout.
+fn items<'a>(src: &str, nodes: &'a [CstNode]) -> Vec<Item<'a>> {
+    let mut items = Vec::with_capacity(nodes.len());
+    let mut newlines = 0;
+    for node in nodes {
+        match node {
+            CstNode::Whitespace { span } => {
+                newlines += src[span.clone()].matches('\n').count();
+                continue;
+            }
+            _ => {
+                if newlines > 1 && !items.is_empty() {
+                    items.push(Item::BlankLine);
+                }
+                if let CstNode::Comment { .. } = node {
+                    items.push(Item::Comment {
+                        node,
+                        trailing: newlines == 0,
+                    });
+                } else {
+                    items.push(Item::Node(node));
+                }
+            }
+        }
+        newlines = 0;
+    }
+    items
+}
+
+struct Printer<'a> {
+    src: &'a str,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn line(&mut self, indent: usize) {
+        self.out.push('\n');
+        for _ in 0..indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn comment(&mut self, node: &CstNode) {
+        self.out.push_str(self.src[node.span()].trim_end());
+    }
+
+    /// Writes one statement per line.
+    fn body(&mut self, items: &[Item], indent: usize) {
+        for item in items {
+            match item {
+                Item::Node(node) => {
+                    self.line(indent);
+                    self.node(node, indent);
+                }
+                Item::Comment {
+                    node,
+                    trailing: true,
+                } => {
+                    self.out.push(' ');
+                    self.comment(node);
+                }
+                Item::Comment { node, .. } => {
+                    self.line(indent);
+                    self.comment(node);
+                }
+                Item::BlankLine => self.out.push('\n'),
+            }
+        }
+    }
+
+    /// Writes a node starting on a line indented by `indent`.
+    fn node(&mut self, node: &CstNode, indent: usize) {
+        let CstNode::List {
+            type_, children, ..
+        } = node
+        else {
+            self.out.push_str(&self.src[node.span()]);
+            return;
+        };
+        self.out.push(type_.open());
+        if let Some(body) = self.block_body(type_, children) {
+            let mut body = items(self.src, body);
+            if let Some(Item::BlankLine) = body.last() {
+                body.pop();
+            }
+            if !body.is_empty() {
+                self.body(&body, indent + 1);
+                self.line(indent);
+            }
+            self.out.push(type_.close());
+            return;
+        }
+        let mut line_indent = indent;
+        let mut first = true;
+        let mut after_comment = false;
+        for item in items(self.src, children) {
+            match item {
+                Item::Node(node) => {
+                    if after_comment {
+                        self.line(indent + 1);
+                        line_indent = indent + 1;
+                    } else if !first {
+                        self.out.push(' ');
+                    }
+                    self.node(node, line_indent);
+                    after_comment = false;
+                }
+                Item::Comment {
+                    node,
+                    trailing: true,
+                } if !first => {
+                    self.out.push(' ');
+                    self.comment(node);
+                    after_comment = true;
+                }
+                Item::Comment { node, .. } => {
+                    self.line(indent + 1);
+                    self.comment(node);
+                    after_comment = true;
+                }
+                Item::BlankLine => continue,
+            }
+            first = false;
+        }
+        if after_comment {
+            self.line(indent);
+        }
+        self.out.push(type_.close());
+    }
+
+    /// Returns the statements of `(do ...)` and `{...}` blocks, writing the `do`.
+    fn block_body<'b>(
+        &mut self,
+        type_: &BracketType,
+        children: &'b [CstNode],
+    ) -> Option<&'b [CstNode]> {
+        match type_ {
+            BracketType::Curly => Some(children),
+            BracketType::Round => {
+                let start = children
+                    .iter()
+                    .position(|node| !matches!(node, CstNode::Whitespace { .. }))?;
+                let head @ CstNode::Atom { .. } = &children[start] else {
+                    return None;
+                };
+                if &self.src[head.span()] != "do" {
+                    return None;
+                }
+                self.out.push_str("do");
+                Some(&children[start + 1..])
+            }
+            BracketType::Square => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_source;
+
+    fn format(src: &str) -> String {
+        format_source("main".to_string(), "main.lvt".to_string(), src.to_string())
+            .unwrap_or_else(|errors| panic!("{src:?}: {errors:?}"))
+    }
+
+    /// Formats the source twice and returns the result, which must not change the second time.
+    fn idempotent(src: &str) -> String {
+        let formatted = format(src);
+        assert_eq!(format(&formatted), formatted, "{src:?}");
+        formatted
+    }
+
+    #[test]
+    fn test_closing_brackets() {
+        assert_eq!(
+            idempotent("(mod asm)  (-label main (do (nop r0) (halt)))"),
+            "(mod asm)\n(-label main (do\n  (nop r0)\n  (halt)\n))\n"
+        );
+    }
+
+    #[test]
+    fn test_nested_blocks() {
+        let src = "(mod code)\n(fn main [] { (if (x) (do (a) { (b) (c) })) (d) })";
+        assert_eq!(
+            idempotent(src),
+            "(mod code)\n(fn main [] {\n  (if (x) (do\n    (a)\n    {\n      (b)\n      (c)\n    }\n  \
+            ))\n  (d)\n})\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_in_lists() {
+        idempotent("(mod asm)\n(-label main [; first\n  :a u64 # second\n  :b u64] (do (halt)))");
+        idempotent("(mod asm)\n(-label main (do\n  (mov ; register\n    r0 1u)\n))");
+        idempotent("(mod asm)\n(-label main (do\n  (mov r0\n    ; value\n    1u)\n))");
+    }
+
+    #[test]
+    fn test_trailing_comments() {
+        assert_eq!(
+            idempotent("(mod asm)\n(-label main (do\n  (mov r0 1u ; one\n      )))"),
+            "(mod asm)\n(-label main (do\n  (mov r0 1u ; one\n  )\n))\n"
+        );
+        assert_eq!(
+            idempotent("(mod asm)\n(-label main (do (halt) ; end\n) ; block\n) ; label"),
+            "(mod asm)\n(-label main (do\n  (halt) ; end\n) ; block\n) ; label\n"
+        );
+        idempotent("(mod asm) ; dialect\n; between\n(-label main (do (halt)))");
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        assert_eq!(
+            idempotent("(mod asm)\n\n\n(-label main (do\n\n  (nop r0)\n\n\n  (halt)\n\n))\n\n"),
+            "(mod asm)\n\n(-label main (do\n  (nop r0)\n\n  (halt)\n))\n"
+        );
+        idempotent("(mod asm)\n(-label main (do\n  (halt)\n  ; end\n\n))");
+        idempotent("(mod asm)\n(-label main (do\n  (halt) ; end\n\n))");
+    }
+
+    #[test]
+    fn test_empty_blocks() {
+        idempotent("(mod code)\n(fn main [] {})\n(fn f [] {\n\n})\n(fn g [] (do))");
+    }
+}
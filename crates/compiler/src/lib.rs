@@ -1,3 +1,6 @@
+pub mod build;
 pub mod compiler;
+pub mod diagnostics;
+pub mod format;
 pub mod parser;
 pub mod util;
@@ -9,110 +9,284 @@ struct StackFrame {
     nodes: Vec<Node>,
 }
 
-pub fn build_ast(
+pub fn build_ast(tokens: TokenList) -> Result<BareModule> {
+    let (module, errors) = build_ast_recovering(tokens, Vec::with_capacity(0));
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(module),
+    }
+}
+
+/// Builds the syntax tree of all top-level forms without errors.
+///
+/// `errors` are the errors of the tokenizer, which leave out the forms they occur in. After
+/// a form with unbalanced brackets, parsing resumes at the first `(` at the start of a line
+/// inside of it, or at the next one after it.
+pub fn build_ast_recovering(
     TokenList {
         name,
         file,
         src,
         tokens,
     }: TokenList,
-) -> Result<BareModule> {
-    let mut stack = vec![StackFrame {
-        index: 0,
-        type_: BracketType::Round,
-        nodes: Vec::with_capacity(0),
-    }];
-    for token in tokens {
-        match token {
-            Token::LeftBracket { span, type_ } => {
-                if stack.len() < 2 && type_ != BracketType::Round {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+    mut errors: Vec<Error>,
+) -> (BareModule, Vec<Error>) {
+    let error_starts: Vec<usize> = errors.iter().map(|err| err.span().start).collect();
+    let unclosed_literals: Vec<usize> = errors
+        .iter()
+        .filter(|err| matches!(err, Error::UnexpectedEndOfSource { .. }))
+        .map(|err| err.span().start)
+        .collect();
+    let has_error = |start: usize, end: usize| {
+        error_starts
+            .iter()
+            .any(|error_start| (start..end).contains(error_start))
+    };
+    let mut forms = Vec::with_capacity(0);
+    let mut index = 0;
+    while index < tokens.len() {
+        let span = tokens[index].span();
+        let Token::LeftBracket { type_, .. } = &tokens[index] else {
+            // Invalid literals were already reported by the tokenizer
+            if !has_error(span.start, span.end) {
+                errors.push(Error::IllegalTokenAtRootLevel {
+                    file: file.clone(),
+                    src: src.clone(),
+                    span,
+                });
+            }
+            index += 1;
+            continue;
+        };
+        // Other brackets are skipped as a whole
+        let round = *type_ == BracketType::Round;
+        if !round {
+            errors.push(Error::IllegalTokenAtRootLevel {
+                file: file.clone(),
+                src: src.clone(),
+                span: span.clone(),
+            });
+        }
+        match scan_form(&tokens, index, &file, &src) {
+            Ok(end) => {
+                if round && !has_error(span.start, tokens[end - 1].span().end) {
+                    forms.push(index..end);
                 }
-                stack.push(StackFrame {
-                    index: span.start,
-                    type_,
-                    nodes: Vec::with_capacity(0),
-                })
+                index = end;
             }
-            Token::RightBracket { span, type_ } => {
-                if stack.len() < 2 {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+            Err((err, resume_index)) => {
+                // A literal running to the end of the source leaves the brackets around it open
+                let unclosed_literal = matches!(err, Error::UnclosedParenthesis { .. })
+                    && resume_index == tokens.len()
+                    && unclosed_literals.iter().any(|start| *start > span.start);
+                // Resuming inside of a broken form can find the same mismatch again
+                let reported = errors.iter().any(|reported| reported.span() == err.span());
+                if !unclosed_literal && !reported {
+                    errors.push(err);
                 }
+                index = resume_index;
+            }
+        }
+    }
+    let mut root = Vec::with_capacity(forms.len());
+    let mut forms = forms.into_iter().peekable();
+    let mut stack: Vec<StackFrame> = Vec::with_capacity(0);
+    for (index, token) in tokens.into_iter().enumerate() {
+        let Some(form) = forms.peek() else {
+            break;
+        };
+        if index < form.start {
+            continue;
+        }
+        if index + 1 == form.end {
+            forms.next();
+        }
+        match token {
+            Token::LeftBracket { span, type_ } => stack.push(StackFrame {
+                index: span.start,
+                type_,
+                nodes: Vec::with_capacity(0),
+            }),
+            Token::RightBracket { span, .. } => {
                 let last = stack.pop().unwrap();
-                if last.type_ != type_ {
-                    return Err(Error::MissmatchBrackets {
-                        file,
-                        src,
-                        span_a: last.index..last.index + 1,
-                        span_b: span,
-                    });
-                }
-                stack.last_mut().unwrap().nodes.push(Node::Node {
+                let node = Node::Node {
                     span: last.index..span.end,
-                    type_,
+                    type_: last.type_,
                     sub_nodes: last.nodes,
-                });
-            }
-            Token::Ident { span } => {
-                if stack.len() < 2 {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+                };
+                match stack.last_mut() {
+                    Some(frame) => frame.nodes.push(node),
+                    None => root.push(node),
                 }
-                stack.last_mut().unwrap().nodes.push(Node::Ident { span });
             }
-            Token::Int { span, value } => {
-                if stack.len() < 2 {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
-                }
-                stack
-                    .last_mut()
-                    .unwrap()
-                    .nodes
-                    .push(Node::Int { span, value });
-            }
-            Token::UInt { span, value } => {
-                if stack.len() < 2 {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+            token => stack.last_mut().unwrap().nodes.push(leaf_node(token)),
+        }
+    }
+    let module = BareModule {
+        file,
+        name,
+        src,
+        root,
+    };
+    (module, errors)
+}
+
+/// Finds the end of the form starting at `start`, or returns the error and the index of the
+/// token to resume at.
+fn scan_form(
+    tokens: &[Token],
+    start: usize,
+    file: &str,
+    src: &str,
+) -> std::result::Result<usize, (Error, usize)> {
+    let mut stack = Vec::new();
+    // The first line starting with `(` inside of the form, and the bracket left open before it
+    let mut line_start = None;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::LeftBracket { span, type_ } => {
+                if line_start.is_none() && !stack.is_empty() && starts_form(token, src) {
+                    line_start = stack.last().map(|(open, _)| (index, *open));
                 }
-                stack
-                    .last_mut()
-                    .unwrap()
-                    .nodes
-                    .push(Node::UInt { span, value });
+                stack.push((span.start, type_));
             }
-            Token::Float { span, value } => {
-                if stack.len() < 2 {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+            Token::RightBracket { span, type_ } => {
+                let (open, open_type) = stack.pop().unwrap();
+                if open_type != type_ {
+                    let err = Error::MissmatchBrackets {
+                        file: file.to_string(),
+                        src: src.to_string(),
+                        span_a: open..open + 1,
+                        span_b: span.clone(),
+                    };
+                    let resume_index = match line_start {
+                        Some((line_start, _)) => line_start,
+                        None => next_form(tokens, index + 1, src),
+                    };
+                    return Err((err, resume_index));
                 }
-                stack
-                    .last_mut()
-                    .unwrap()
-                    .nodes
-                    .push(Node::Float { span, value });
-            }
-            Token::String { span, value } => {
-                if stack.len() < 2 {
-                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+                if stack.is_empty() {
+                    return Ok(index + 1);
                 }
-                stack
-                    .last_mut()
-                    .unwrap()
-                    .nodes
-                    .push(Node::String { span, value });
             }
+            _ => {}
         }
     }
-    if stack.len() != 1 {
-        let last = stack.pop().unwrap();
-        return Err(Error::UnclosedParenthesis {
-            file,
-            src,
-            span: last.index..last.index + 1,
-        });
+    let (resume_index, open) = match line_start {
+        Some(line_start) => line_start,
+        None => (tokens.len(), stack.last().unwrap().0),
+    };
+    let err = Error::UnclosedParenthesis {
+        file: file.to_string(),
+        src: src.to_string(),
+        span: open..open + 1,
+    };
+    Err((err, resume_index))
+}
+
+/// Whether the token is a `(` at the start of a line, which likely starts a top-level form.
+fn starts_form(token: &Token, src: &str) -> bool {
+    matches!(
+        token,
+        Token::LeftBracket { span, type_: BracketType::Round }
+            if span.start == 0 || src[..span.start].ends_with('\n')
+    )
+}
+
+fn next_form(tokens: &[Token], start: usize, src: &str) -> usize {
+    (start..tokens.len())
+        .find(|index| starts_form(&tokens[*index], src))
+        .unwrap_or(tokens.len())
+}
+
+fn leaf_node(token: Token) -> Node {
+    match token {
+        Token::Ident { span } => Node::Ident { span },
+        Token::Int { span, value } => Node::Int { span, value },
+        Token::UInt { span, value } => Node::UInt { span, value },
+        Token::Float { span, value } => Node::Float { span, value },
+        Token::String { span, value } => Node::String { span, value },
+        Token::Bytes { span, value } => Node::Bytes { span, value },
+        Token::LeftBracket { .. } | Token::RightBracket { .. } => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{error::Error, parse};
+
+    /// Parses the source and returns the source text of the top-level forms and the errors.
+    fn parse_forms(src: &str) -> (Vec<&str>, Vec<Error>) {
+        let (module, errors) = parse("main".to_string(), "main.lvt".to_string(), src.to_string());
+        let forms = module.root.iter().map(|node| &src[node.span()]).collect();
+        (forms, errors)
+    }
+
+    #[test]
+    fn test_unclosed_form() {
+        let src = "(mod asm)\n(-label f (do\n  (nop r0)\n(-label main (do (halt)))\n(nop";
+        let (forms, errors) = parse_forms(src);
+        assert_eq!(forms, ["(mod asm)", "(-label main (do (halt)))"]);
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(matches!(&errors[0], Error::UnclosedParenthesis { span, .. } if span == &(20..21)));
+        assert!(matches!(&errors[1], Error::UnclosedParenthesis { span, .. } if span == &(61..62)));
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let src = "(mod asm)\n(-label f (do\n  (nop r0]\n))\n(-label main (do (halt)))";
+        let (forms, errors) = parse_forms(src);
+        assert_eq!(forms, ["(mod asm)", "(-label main (do (halt)))"]);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(
+            matches!(&errors[0], Error::MissmatchBrackets { span_b, .. } if span_b == &(33..34))
+        );
+    }
+
+    #[test]
+    fn test_mismatch_resumes_inside_form() {
+        // The form of `f` is never closed, so `main` is only found inside of it
+        let src = "(mod asm)\n(-label f (do\n(-label main (do (halt)))\n  (nop r0]\n";
+        let (forms, errors) = parse_forms(src);
+        assert_eq!(forms, ["(mod asm)", "(-label main (do (halt)))"]);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(errors[0], Error::MissmatchBrackets { .. }));
+    }
+
+    #[test]
+    fn test_illegal_tokens_at_root() {
+        let src = "(mod asm))\n[a b]\n{c}\nd\n(-label main (do (halt)))";
+        let (forms, errors) = parse_forms(src);
+        assert_eq!(forms, ["(mod asm)", "(-label main (do (halt)))"]);
+        let starts: Vec<usize> = errors.iter().map(|err| err.span().start).collect();
+        assert_eq!(starts, [9, 11, 17, 21]);
+        assert!(errors
+            .iter()
+            .all(|err| matches!(err, Error::IllegalTokenAtRootLevel { .. })));
+    }
+
+    #[test]
+    fn test_tokenizer_errors_reported_once() {
+        let src = "(mod asm)\n1abc\n(-label f (do (mov r0 1abc)))\n(-label main (do (halt)))";
+        let (forms, errors) = parse_forms(src);
+        assert_eq!(forms, ["(mod asm)", "(-label main (do (halt)))"]);
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors
+            .iter()
+            .all(|err| matches!(err, Error::IdentStartingWithDigit { .. })));
+        // The unclosed string leaves the brackets around it open
+        let (forms, errors) = parse_forms("(mod asm)\n(-label main (do (mov r0 \"abc)))\n");
+        assert_eq!(forms, ["(mod asm)"]);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(errors[0], Error::UnexpectedEndOfSource { .. }));
+    }
+
+    #[test]
+    fn test_errors_sorted() {
+        let src = "]\n(a 1abc)\n(b (c]\n(d)\n(e '')\n(f";
+        let (forms, errors) = parse_forms(src);
+        assert_eq!(forms, ["(d)"]);
+        let starts: Vec<usize> = errors.iter().map(|err| err.span().start).collect();
+        assert_eq!(starts, [0, 5, 16, 25, 29]);
     }
-    Ok(BareModule {
-        file,
-        name,
-        src,
-        root: stack.pop().unwrap().nodes,
-    })
 }
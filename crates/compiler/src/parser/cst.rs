@@ -0,0 +1,279 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::util::source::Source;
+
+use super::{
+    error::{Error, Result},
+    BracketType, ConcreteModule, CstNode,
+};
+
+struct StackFrame {
+    index: usize,
+    type_: BracketType,
+    nodes: Vec<CstNode>,
+}
+
+/// Parses a module into a lossless concrete syntax tree.
+///
+/// Only the bracket structure and the ends of quoted literals are checked, everything else is left
+/// to [`tokenize`](super::tokenizer::tokenize) and [`build_ast`](super::ast::build_ast).
+pub fn build_cst(name: String, file: String, src: String) -> Result<ConcreteModule> {
+    let mut source = Source::new(&src);
+    let mut stack = vec![StackFrame {
+        index: 0,
+        type_: BracketType::Round,
+        nodes: Vec::with_capacity(0),
+    }];
+    while source.has_next() {
+        let index = source.index;
+        let c = source.peek();
+        let node = match c {
+            '#' | ';' => {
+                while source.has_next() && source.peek() != '\n' {
+                    source.eat();
+                }
+                CstNode::Comment {
+                    span: index..source.index,
+                }
+            }
+            '(' | '[' | '{' => {
+                source.eat();
+                stack.push(StackFrame {
+                    index,
+                    type_: BracketType::from(c),
+                    nodes: Vec::with_capacity(0),
+                });
+                continue;
+            }
+            ')' | ']' | '}' => {
+                source.eat();
+                let span = index..source.index;
+                if stack.len() < 2 {
+                    return Err(Error::IllegalTokenAtRootLevel { file, src, span });
+                }
+                let last = stack.pop().unwrap();
+                let type_ = BracketType::from(c);
+                if last.type_ != type_ {
+                    return Err(Error::MissmatchBrackets {
+                        file,
+                        src,
+                        span_a: last.index..last.index + 1,
+                        span_b: span,
+                    });
+                }
+                CstNode::List {
+                    span: last.index..span.end,
+                    type_,
+                    children: last.nodes,
+                }
+            }
+            // Character literals are quoted like strings, so that `' '` stays one atom
+            '"' | '\'' => {
+                source.eat();
+                if !skip_quoted(&mut source, c, false) {
+                    let source_index = source.index;
+                    return Err(Error::UnexpectedEndOfSource {
+                        file,
+                        src,
+                        span: index..source_index,
+                    });
+                }
+                let span = index..source.index;
+                if c == '"' {
+                    CstNode::String { span }
+                } else {
+                    CstNode::Atom { span }
+                }
+            }
+            _ if c.is_whitespace() => {
+                while source.has_next() && source.peek().is_whitespace() {
+                    source.eat();
+                }
+                CstNode::Whitespace {
+                    span: index..source.index,
+                }
+            }
+            _ => {
+                while source.has_next() && !is_atom_end(source.peek()) {
+                    source.eat();
+                }
+                let prefix = &src[index..source.index];
+                let prefixed =
+                    matches!(prefix, "r" | "b" | "br") && source.has_next() && source.peek() == '"';
+                if prefixed {
+                    let raw = prefix.contains('r');
+                    source.eat();
+                    if !skip_quoted(&mut source, '"', raw) {
+                        let source_index = source.index;
+                        return Err(Error::UnexpectedEndOfSource {
+                            file,
+                            src,
+                            span: index..source_index,
+                        });
+                    }
+                    CstNode::String {
+                        span: index..source.index,
+                    }
+                } else {
+                    CstNode::Atom {
+                        span: index..source.index,
+                    }
+                }
+            }
+        };
+        stack.last_mut().unwrap().nodes.push(node);
+    }
+    if stack.len() != 1 {
+        let last = stack.pop().unwrap();
+        return Err(Error::UnclosedParenthesis {
+            file,
+            src,
+            span: last.index..last.index + 1,
+        });
+    }
+    Ok(ConcreteModule {
+        name,
+        file,
+        src,
+        root: stack.pop().unwrap().nodes,
+    })
+}
+
+/// Skips the rest of a quoted literal after its opening quote, or returns `false` if it is
+/// not closed.
+fn skip_quoted(source: &mut Source, quote: char, raw: bool) -> bool {
+    while source.has_next() {
+        let c = source.peek();
+        source.eat();
+        if c == quote {
+            return true;
+        }
+        if c == '\\' && !raw && source.has_next() {
+            source.eat();
+        }
+    }
+    false
+}
+
+fn is_atom_end(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '#' | ';' | '"' | '(' | ')' | '[' | ']' | '{' | '}')
+}
+
+impl ConcreteModule {
+    /// Returns the source text of a node.
+    pub fn text(&self, node: &CstNode) -> &str {
+        &self.src[node.span()]
+    }
+
+    fn write_node(&self, f: &mut Formatter<'_>, node: &CstNode) -> FmtResult {
+        match node {
+            CstNode::List {
+                type_, children, ..
+            } => {
+                write!(f, "{}", type_.open())?;
+                for child in children {
+                    self.write_node(f, child)?;
+                }
+                write!(f, "{}", type_.close())
+            }
+            _ => f.write_str(self.text(node)),
+        }
+    }
+}
+
+impl Display for ConcreteModule {
+    /// Reproduces the original source byte for byte.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for node in &self.root {
+            self.write_node(f, node)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::CstNode;
+
+    use super::build_cst;
+
+    fn round_trip(src: &str) -> Vec<CstNode> {
+        let module = build_cst("main".to_string(), "main.lvt".to_string(), src.to_string())
+            .unwrap_or_else(|err| panic!("{src:?}: {err:?}"));
+        assert_eq!(module.to_string(), src);
+        module.root
+    }
+
+    /// Returns the source text of the children of the first list.
+    fn children(src: &str) -> Vec<&str> {
+        let root = round_trip(src);
+        let Some(CstNode::List { children, .. }) = root.iter().find(|node| !node.is_trivia())
+        else {
+            panic!("{src:?} has no list");
+        };
+        children
+            .iter()
+            .filter(|node| !node.is_trivia())
+            .map(|node| &src[node.span()])
+            .collect()
+    }
+
+    #[test]
+    fn test_comments() {
+        round_trip("; module\n(mod asm) # dialect\n(-label main (do ; body\n  (halt) #end\n))");
+        assert_eq!(children("(a ; (b)\n c # ]\n)"), ["a", "c"]);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let root = round_trip("(mod asm)\r\n\r\n(-label main (do\r\n  (halt) ; end\r\n))\r\n");
+        assert!(matches!(root[1], CstNode::Whitespace { .. }));
+    }
+
+    #[test]
+    fn test_utf8() {
+        round_trip("; äöü 🦀\n(mov r0 \"日本語\") # ✓\n");
+        assert_eq!(children("(a \"🦀\" 'ä' ü)"), ["a", "\"🦀\"", "'ä'", "ü"]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(
+            children("(mov r0 0x10u 16u -0x10 0b1_0 1.5f)"),
+            ["mov", "r0", "0x10u", "16u", "-0x10", "0b1_0", "1.5f"]
+        );
+    }
+
+    #[test]
+    fn test_chars() {
+        assert_eq!(
+            children(r"(a '(' ')' ';' '#' ' ' '\'' '\n' '\u{1F980}')"),
+            [
+                "a",
+                "'('",
+                "')'",
+                "';'",
+                "'#'",
+                "' '",
+                r"'\''",
+                r"'\n'",
+                r"'\u{1F980}'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            children(r#"(a "(\" ;" r"\" b"\x00)" br"\" "")"#),
+            [
+                "a",
+                r#""(\" ;""#,
+                r#"r"\""#,
+                r#"b"\x00)""#,
+                r#"br"\""#,
+                r#""""#
+            ]
+        );
+    }
+}
@@ -1,11 +1,4 @@
-use std::process::exit;
-
-use ariadne::Source;
-
-use crate::util::{
-    ariadne::{span_double_error_report, span_error_report},
-    source::Span,
-};
+use crate::util::{ariadne::report, message::Message, source::Span};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -52,71 +45,141 @@ pub enum Error {
         span_a: Span,
         span_b: Span,
     },
+    InvalidNumber {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    NumberOverflow {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    NegativeUnsigned {
+        file: String,
+        src: String,
+        span: Span,
+    },
+    InvalidCharLiteral {
+        file: String,
+        src: String,
+        span: Span,
+    },
 }
 
 impl Error {
-    pub fn report(&self) {
-        let report;
-        let source;
-        let file: &str = match self {
-            Error::IdentStartingWithDigit { file, src, span } => {
-                report = span_error_report(file, span, "Identifiers cannot start with a digit");
-                source = Source::from(src);
-                file
-            }
-            Error::NoWhitespaceBetweenTokens { file, src, span } => {
-                report = span_error_report(file, span, "There must be whitespace between token");
-                source = Source::from(src);
-                file
-            }
-            Error::UnexpectedEndOfSource { file, src, span } => {
-                report = span_error_report(file, span, "The code is not allowed to end here");
-                source = Source::from(src);
-                file
-            }
-            Error::InvalidStringEscapeCode { file, src, span } => {
-                report = span_error_report(file, span, "This escape code is not valid");
-                source = Source::from(src);
-                file
-            }
-            Error::IllegalTokenAtRootLevel { file, src, span } => {
-                report =
-                    span_error_report(file, span, "This token is not allowed on the root-level");
-                source = Source::from(src);
-                file
-            }
-            Error::UnclosedParenthesis { file, src, span } => {
-                report = span_error_report(file, span, "This parenthesis must be closed");
-                source = Source::from(src);
-                file
-            }
+    /// Returns the span of the error, which is the closing bracket for mismatched brackets.
+    pub fn span(&self) -> Span {
+        match self {
+            Error::IdentStartingWithDigit { span, .. }
+            | Error::NoWhitespaceBetweenTokens { span, .. }
+            | Error::UnexpectedEndOfSource { span, .. }
+            | Error::InvalidStringEscapeCode { span, .. }
+            | Error::IllegalTokenAtRootLevel { span, .. }
+            | Error::UnclosedParenthesis { span, .. }
+            | Error::InvalidUtf8 { span, .. }
+            | Error::InvalidNumber { span, .. }
+            | Error::NumberOverflow { span, .. }
+            | Error::NegativeUnsigned { span, .. }
+            | Error::InvalidCharLiteral { span, .. } => span.clone(),
+            Error::MissmatchBrackets { span_b, .. } => span_b.clone(),
+        }
+    }
+
+    /// Describes the error independently of how it is rendered.
+    pub fn message(&self) -> Message<'_> {
+        match self {
+            Error::IdentStartingWithDigit { file, src, span } => Message::span_error(
+                "ident_starting_with_digit",
+                file,
+                src,
+                span,
+                "Identifiers cannot start with a digit",
+            ),
+            Error::NoWhitespaceBetweenTokens { file, src, span } => Message::span_error(
+                "no_whitespace_between_tokens",
+                file,
+                src,
+                span,
+                "There must be whitespace between token",
+            ),
+            Error::UnexpectedEndOfSource { file, src, span } => Message::span_error(
+                "unexpected_end_of_source",
+                file,
+                src,
+                span,
+                "The code is not allowed to end here",
+            ),
+            Error::InvalidStringEscapeCode { file, src, span } => Message::span_error(
+                "invalid_string_escape_code",
+                file,
+                src,
+                span,
+                "This escape code is not valid",
+            ),
+            Error::IllegalTokenAtRootLevel { file, src, span } => Message::span_error(
+                "illegal_token_at_root_level",
+                file,
+                src,
+                span,
+                "This token is not allowed on the root-level",
+            ),
+            Error::UnclosedParenthesis { file, src, span } => Message::span_error(
+                "unclosed_parenthesis",
+                file,
+                src,
+                span,
+                "This parenthesis must be closed",
+            ),
             Error::InvalidUtf8 { file, src, span } => {
-                report = span_error_report(file, span, "This is invalid Utf8");
-                source = Source::from(src);
-                file
+                Message::span_error("invalid_utf8", file, src, span, "This is invalid Utf8")
             }
             Error::MissmatchBrackets {
                 file,
                 src,
                 span_a,
                 span_b,
-            } => {
-                report = span_double_error_report(
-                    file,
-                    span_a,
-                    span_b,
-                    "start",
-                    "end - These brackets do not match",
-                );
-                source = Source::from(src);
-                file
-            }
-        };
-        report.eprint((file, source)).unwrap();
+            } => Message::double_span_error(
+                "missmatch_brackets",
+                file,
+                src,
+                (span_a, span_b),
+                ("start", "end"),
+                "These brackets do not match",
+            ),
+            Error::InvalidNumber { file, src, span } => Message::span_error(
+                "invalid_number",
+                file,
+                src,
+                span,
+                "This is not a valid number",
+            ),
+            Error::NumberOverflow { file, src, span } => Message::span_error(
+                "number_overflow",
+                file,
+                src,
+                span,
+                "This number is out of range",
+            )
+            .with_note("Integers must fit into 64 bits, signed ones without the 'u' suffix"),
+            Error::NegativeUnsigned { file, src, span } => Message::span_error(
+                "negative_unsigned",
+                file,
+                src,
+                span,
+                "Unsigned numbers cannot be negative",
+            ),
+            Error::InvalidCharLiteral { file, src, span } => Message::span_error(
+                "invalid_char_literal",
+                file,
+                src,
+                span,
+                "A character literal must contain exactly one character",
+            ),
+        }
     }
 
-    pub fn abort(&self) -> ! {
-        self.report();
-        exit(1);
+    pub fn report(&self) {
+        report(&self.message());
     }
 }
@@ -1,9 +1,23 @@
 use crate::util::source::Span;
 
+use self::{ast::build_ast_recovering, error::Error, tokenizer::tokenize_recovering};
+
 pub mod ast;
+pub mod cst;
 pub mod error;
 pub mod tokenizer;
 
+/// Parses a module, reporting all syntax errors in the order of the source.
+///
+/// The module contains all top-level forms without errors, so that tools can keep working on
+/// the rest of a broken file.
+pub fn parse(name: String, file: String, src: String) -> (BareModule, Vec<Error>) {
+    let (tokens, errors) = tokenize_recovering(name, file, src);
+    let (module, mut errors) = build_ast_recovering(tokens, errors);
+    errors.sort_by_key(|err| err.span().start);
+    (module, errors)
+}
+
 #[derive(Debug)]
 pub struct TokenList {
     pub file: String,
@@ -20,6 +34,60 @@ pub struct BareModule {
     pub root: Vec<Node>,
 }
 
+/// A module parsed into a lossless concrete syntax tree.
+///
+/// Unlike [`BareModule`] it keeps whitespace, comments and the exact spelling of every
+/// token, so that its [`Display`](std::fmt::Display) output is the original source.
+#[derive(Debug)]
+pub struct ConcreteModule {
+    pub name: String,
+    pub file: String,
+    pub src: String,
+    pub root: Vec<CstNode>,
+}
+
+#[derive(Debug)]
+pub enum CstNode {
+    Whitespace {
+        span: Span,
+    },
+    /// A `;` or `#` comment up to, but not including, the end of the line
+    Comment {
+        span: Span,
+    },
+    /// An identifier, number or character literal as it is spelled in the source
+    Atom {
+        span: Span,
+    },
+    /// A string literal including its prefix, quotes and escape codes
+    String {
+        span: Span,
+    },
+    /// A bracketed list, where `span` includes both brackets
+    List {
+        span: Span,
+        type_: BracketType,
+        children: Vec<CstNode>,
+    },
+}
+
+impl CstNode {
+    pub fn span(&self) -> Span {
+        match self {
+            CstNode::Whitespace { span }
+            | CstNode::Comment { span }
+            | CstNode::Atom { span }
+            | CstNode::String { span }
+            | CstNode::List { span, .. } => span.clone(),
+        }
+    }
+
+    /// Whether the node is whitespace or a comment.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, CstNode::Whitespace { .. } | CstNode::Comment { .. })
+    }
+}
+
 #[derive(Debug)]
 pub enum Token {
     LeftBracket { span: Span, type_: BracketType },
@@ -29,6 +97,7 @@ pub enum Token {
     UInt { span: Span, value: u64 },
     Float { span: Span, value: f64 },
     String { span: Span, value: String },
+    Bytes { span: Span, value: Vec<u8> },
 }
 
 impl Token {
@@ -40,7 +109,8 @@ impl Token {
             | Token::Int { span, .. }
             | Token::UInt { span, .. }
             | Token::Float { span, .. }
-            | Token::String { span, .. } => span.clone(),
+            | Token::String { span, .. }
+            | Token::Bytes { span, .. } => span.clone(),
         }
     }
 }
@@ -67,6 +137,11 @@ pub enum Node {
         span: Span,
         value: String,
     },
+    /// A byte string like `b"\x01\x02"`
+    Bytes {
+        span: Span,
+        value: Vec<u8>,
+    },
     Node {
         span: Span,
         type_: BracketType,
@@ -82,6 +157,7 @@ impl Node {
             | Node::UInt { span, .. }
             | Node::Float { span, .. }
             | Node::String { span, .. }
+            | Node::Bytes { span, .. }
             | Node::Node { span, .. } => span.clone(),
             _ => unreachable!()
         }
@@ -101,6 +177,24 @@ pub enum BracketType {
     Curly,
 }
 
+impl BracketType {
+    pub fn open(&self) -> char {
+        match self {
+            Self::Round => '(',
+            Self::Square => '[',
+            Self::Curly => '{',
+        }
+    }
+
+    pub fn close(&self) -> char {
+        match self {
+            Self::Round => ')',
+            Self::Square => ']',
+            Self::Curly => '}',
+        }
+    }
+}
+
 impl From<char> for BracketType {
     fn from(value: char) -> Self {
         match value {
@@ -5,9 +5,62 @@ use super::{
     BracketType, Token, TokenList,
 };
 
+/// An error found while tokenizing, which becomes an [`Error`] once the source is no longer
+/// borrowed.
+enum TokenError {
+    NoWhitespaceBetweenTokens,
+    IdentStartingWithDigit,
+    InvalidNumber,
+    NumberOverflow,
+    NegativeUnsigned,
+    UnexpectedEndOfSource,
+    InvalidStringEscapeCode,
+    InvalidUtf8,
+    InvalidCharLiteral,
+}
+
+/// A decoded escape code.
+enum Escape {
+    Char(char),
+    /// A `\xNN` escape, which is a raw byte in byte strings
+    Byte(u8),
+}
+
+impl TokenError {
+    fn into_error(self, file: String, src: String, span: Span) -> Error {
+        match self {
+            TokenError::NoWhitespaceBetweenTokens => {
+                Error::NoWhitespaceBetweenTokens { file, src, span }
+            }
+            TokenError::IdentStartingWithDigit => Error::IdentStartingWithDigit { file, src, span },
+            TokenError::InvalidNumber => Error::InvalidNumber { file, src, span },
+            TokenError::NumberOverflow => Error::NumberOverflow { file, src, span },
+            TokenError::NegativeUnsigned => Error::NegativeUnsigned { file, src, span },
+            TokenError::UnexpectedEndOfSource => Error::UnexpectedEndOfSource { file, src, span },
+            TokenError::InvalidStringEscapeCode => {
+                Error::InvalidStringEscapeCode { file, src, span }
+            }
+            TokenError::InvalidUtf8 => Error::InvalidUtf8 { file, src, span },
+            TokenError::InvalidCharLiteral => Error::InvalidCharLiteral { file, src, span },
+        }
+    }
+}
+
 pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
+    let (tokens, errors) = tokenize_recovering(name, file, src);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(tokens),
+    }
+}
+
+/// Tokenizes the whole source, collecting all errors instead of stopping at the first one.
+///
+/// Invalid literals become [`Token::Ident`]s, so that the brackets around them stay balanced.
+pub fn tokenize_recovering(name: String, file: String, src: String) -> (TokenList, Vec<Error>) {
     let mut source = Source::new(&src);
     let mut tokens = Vec::new();
+    let mut errors = Vec::with_capacity(0);
     let mut g_index = 0;
     let mut g_len = 0;
     let mut g_token = false;
@@ -16,14 +69,7 @@ pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
         match c {
             '#' | ';' => {
                 if g_len > 0 {
-                    let Some(token) = parse_token(g_index..g_index + g_len, &mut source) else {
-                        return Err(Error::IdentStartingWithDigit {
-                            file,
-                            src,
-                            span: g_index..g_index + g_len,
-                        });
-                    };
-                    tokens.push(token);
+                    push_token(&mut tokens, &mut errors, g_index..g_index + g_len, &source);
                     g_len = 0;
                 }
                 source.eat();
@@ -38,13 +84,13 @@ pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
                 let index = source.index;
                 source.eat();
                 if g_len > 0 || g_token {
-                    let source_index = source.index;
-                    return Err(Error::NoWhitespaceBetweenTokens {
-                        file,
-                        src,
-                        span: index..source_index,
-                    });
+                    errors.push((TokenError::NoWhitespaceBetweenTokens, index..source.index));
                 }
+                if g_len > 0 {
+                    push_token(&mut tokens, &mut errors, g_index..g_index + g_len, &source);
+                    g_len = 0;
+                }
+                g_token = false;
                 tokens.push(Token::LeftBracket {
                     span: index..source.index,
                     type_: BracketType::from(c),
@@ -53,14 +99,7 @@ pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
             ')' | ']' | '}' => {
                 let index = source.index;
                 if g_len > 0 {
-                    let Some(token) = parse_token(g_index..g_index + g_len, &mut source) else {
-                        return Err(Error::IdentStartingWithDigit {
-                            file,
-                            src,
-                            span: g_index..g_index + g_len,
-                        });
-                    };
-                    tokens.push(token);
+                    push_token(&mut tokens, &mut errors, g_index..g_index + g_len, &source);
                     g_len = 0;
                 }
                 g_token = true;
@@ -71,149 +110,87 @@ pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
                 });
             }
             '"' => {
-                if g_len > 0 || g_token {
+                // `r`, `b` and `br` directly before the quote make raw and byte strings
+                let prefix = source.str(g_index..g_index + g_len);
+                let prefixed = matches!(prefix, "r" | "b" | "br");
+                let raw = prefixed && prefix.contains('r');
+                let bytes = prefixed && prefix.contains('b');
+                if g_token || g_len > 0 && !prefixed {
                     let index = source.index;
-                    source.eat();
-                    let source_index = source.index;
-                    return Err(Error::NoWhitespaceBetweenTokens {
-                        file,
-                        src,
-                        span: index..source_index,
-                    });
+                    errors.push((TokenError::NoWhitespaceBetweenTokens, index..index + 1));
+                    if g_len > 0 {
+                        push_token(&mut tokens, &mut errors, g_index..g_index + g_len, &source);
+                    }
                 }
-                let mut buf = String::with_capacity(0);
-                let s_index = source.index;
+                let s_index = if prefixed { g_index } else { source.index };
+                g_len = 0;
+                let mut buf = Vec::with_capacity(0);
                 source.eat();
-                let s_value_index = source.index;
-                let mut s_value_len = 0;
                 while source.has_next() {
                     let c = source.peek();
                     match c {
                         '"' => {
                             break;
                         }
-                        '\\' => {
-                            s_value_len += c.len_utf8();
+                        '\\' if !raw => {
                             let index = source.index;
                             source.eat();
-                            if !source.has_next() {
-                                let source_index = source.index;
-                                return Err(Error::UnexpectedEndOfSource {
-                                    file,
-                                    src,
-                                    span: index..source_index,
-                                });
-                            }
-                            let esc_c = source.peek();
-                            source.eat();
-                            match esc_c {
-                                '"' | '\\' => {
-                                    buf.push(esc_c);
-                                    s_value_len += esc_c.len_utf8();
-                                }
-                                'n' => {
-                                    buf.push('\n');
-                                    s_value_len += 1;
-                                }
-                                't' => {
-                                    buf.push('\t');
-                                    s_value_len += 1;
-                                }
-                                'r' => {
-                                    buf.push('\r');
-                                    s_value_len += 1;
-                                }
-                                'x' => {
-                                    s_value_len += 1;
-                                    if !source.has_next() {
-                                        let source_index = source.index;
-                                        return Err(Error::UnexpectedEndOfSource {
-                                            file,
-                                            src,
-                                            span: index..source_index,
-                                        });
-                                    }
-                                    let ac = source.peek();
-                                    source.eat();
-                                    s_value_len += ac.len_utf8();
-                                    if !source.has_next() {
-                                        let source_index = source.index;
-                                        return Err(Error::UnexpectedEndOfSource {
-                                            file,
-                                            src,
-                                            span: index..source_index,
-                                        });
-                                    }
-                                    let bc = source.peek();
-                                    source.eat();
-                                    s_value_len += bc.len_utf8();
-                                    if !ac.is_ascii_hexdigit() || !bc.is_ascii_hexdigit() {
-                                        let source_index = source.index;
-                                        return Err(Error::InvalidStringEscapeCode {
-                                            file,
-                                            src,
-                                            span: index..source_index,
-                                        });
-                                    }
-                                    let s = [ac as u8, bc as u8];
-                                    let Ok(s) = std::str::from_utf8(&s) else {
-                                        panic!("Utf8");
-                                    };
-                                    let Some(utf_c) =
-                                        char::from_u32(u32::from_str_radix(s, 16).expect("Hex str")) else
-                                    {
-                                        let source_index = source.index;
-                                        return Err(Error::InvalidUtf8 {
-                                            file,
-                                            src,
-                                            span: index..source_index,
-                                        });
-                                    };
-                                    buf.push(utf_c);
-                                }
-                                _ => {
-                                    let source_index = source.index;
-                                    return Err(Error::InvalidStringEscapeCode {
-                                        file,
-                                        src,
-                                        span: index..source_index,
-                                    });
-                                }
+                            match parse_escape(&mut source) {
+                                Ok(Escape::Byte(byte)) if bytes => buf.push(byte),
+                                Ok(Escape::Byte(byte)) => push_char(&mut buf, char::from(byte)),
+                                Ok(Escape::Char(c)) => push_char(&mut buf, c),
+                                // The unclosed string is reported below
+                                Err(TokenError::UnexpectedEndOfSource) => {}
+                                Err(err) => errors.push((err, index..source.index)),
                             }
                         }
                         _ => {
-                            buf.push(c);
+                            push_char(&mut buf, c);
                             source.eat();
-                            s_value_len += c.len_utf8();
                         }
                     }
                 }
                 if !source.has_next() {
-                    return Err(Error::UnexpectedEndOfSource {
-                        file,
-                        src,
-                        span: s_index..s_value_index + s_value_len,
-                    });
+                    errors.push((TokenError::UnexpectedEndOfSource, s_index..source.index));
+                    break;
                 }
                 source.eat();
-                let s_len = c.len_utf8() * 2 + s_value_len;
-                tokens.push(Token::String {
-                    span: s_index..s_index + s_len,
-                    value: buf,
-                });
+                let span = s_index..source.index;
+                if bytes {
+                    tokens.push(Token::Bytes { span, value: buf });
+                } else {
+                    let value = String::from_utf8(buf).expect("Only chars are pushed");
+                    tokens.push(Token::String { span, value });
+                }
+                g_token = true;
+            }
+            // Quotes inside of identifiers do not start a character literal
+            '\'' if g_len == 0 => {
+                let index = source.index;
+                source.eat();
+                if g_token {
+                    errors.push((TokenError::NoWhitespaceBetweenTokens, index..source.index));
+                }
+                match parse_char(&mut source) {
+                    Ok(c) => {
+                        tokens.push(Token::UInt {
+                            span: index..source.index,
+                            value: c as u64,
+                        });
+                    }
+                    Err(err) => {
+                        errors.push((err, index..source.index));
+                        tokens.push(Token::Ident {
+                            span: index..source.index,
+                        });
+                    }
+                }
                 g_token = true;
             }
             _ => {
                 if c.is_whitespace() {
                     if g_len > 0 {
-                        let Some(token) = parse_token(g_index..g_index + g_len, &mut source) else {
-                            return Err(Error::IdentStartingWithDigit {
-                                file,
-                                src,
-                                span: g_index..g_index + g_len,
-                            });
-                        };
-                        tokens.push(token);
+                        push_token(&mut tokens, &mut errors, g_index..g_index + g_len, &source);
                     }
                     g_len = 0;
                     g_token = false;
@@ -222,13 +199,11 @@ pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
                 }
                 if g_token {
                     let index = source.index;
-                    source.eat();
-                    let source_index = source.index;
-                    return Err(Error::NoWhitespaceBetweenTokens {
-                        file,
-                        src,
-                        span: index..source_index,
-                    });
+                    errors.push((
+                        TokenError::NoWhitespaceBetweenTokens,
+                        index..index + c.len_utf8(),
+                    ));
+                    g_token = false;
                 }
                 if g_len == 0 {
                     g_index = source.index;
@@ -239,60 +214,521 @@ pub fn tokenize(name: String, file: String, src: String) -> Result<TokenList> {
         }
     }
     if g_len > 0 {
-        let Some(token) = parse_token(g_index..g_index + g_len, &mut source) else {
-            return Err(Error::IdentStartingWithDigit {
-                file,
-                src,
-                span: g_index..g_index + g_len,
-            });
-        };
-        tokens.push(token);
+        push_token(&mut tokens, &mut errors, g_index..g_index + g_len, &source);
     }
-    Ok(TokenList {
+    let errors = errors
+        .into_iter()
+        .map(|(err, span)| err.into_error(file.clone(), src.clone(), span))
+        .collect();
+    let tokens = TokenList {
         name,
         file,
         src,
         tokens,
-    })
+    };
+    (tokens, errors)
 }
 
-fn parse_token(span: Span, source: &mut Source) -> Option<Token> {
-    let s = source.str(span.clone());
-    if let Some(s) = s.strip_prefix("0x") {
-        if !s.starts_with('-') && !s.starts_with('+') {
-            if let Some(s) = s.strip_suffix('u') {
-                if let Ok(value) = u64::from_str_radix(s, 16) {
-                    return Some(Token::UInt { span, value });
+/// Parses an identifier or a number, replacing invalid numbers with identifiers.
+fn push_token(
+    tokens: &mut Vec<Token>,
+    errors: &mut Vec<(TokenError, Span)>,
+    span: Span,
+    source: &Source,
+) {
+    match parse_token(span.clone(), source) {
+        Ok(token) => tokens.push(token),
+        Err(err) => {
+            errors.push((err, span.clone()));
+            tokens.push(Token::Ident { span });
+        }
+    }
+}
+
+/// Parses an escape code after its `\`.
+///
+/// Valid escape codes are `\"`, `\'`, `\\`, `\n`, `\t`, `\r`, `\0`, `\xNN` with two hex
+/// digits and `\u{N}` with up to six hex digits.
+fn parse_escape(source: &mut Source) -> std::result::Result<Escape, TokenError> {
+    if !source.has_next() {
+        return Err(TokenError::UnexpectedEndOfSource);
+    }
+    let c = source.peek();
+    source.eat();
+    match c {
+        '"' | '\'' | '\\' => Ok(Escape::Char(c)),
+        'n' => Ok(Escape::Char('\n')),
+        't' => Ok(Escape::Char('\t')),
+        'r' => Ok(Escape::Char('\r')),
+        '0' => Ok(Escape::Char('\0')),
+        'x' => {
+            let mut value = 0;
+            for _ in 0..2 {
+                if !source.has_next() {
+                    return Err(TokenError::UnexpectedEndOfSource);
                 }
+                let Some(digit) = source.peek().to_digit(16) else {
+                    return Err(TokenError::InvalidStringEscapeCode);
+                };
+                source.eat();
+                value = value * 16 + digit as u8;
             }
-            if let Ok(value) = i64::from_str_radix(s, 16) {
-                return Some(Token::Int { span, value });
+            Ok(Escape::Byte(value))
+        }
+        'u' => {
+            if !source.has_next() {
+                return Err(TokenError::UnexpectedEndOfSource);
             }
+            if source.peek() != '{' {
+                return Err(TokenError::InvalidStringEscapeCode);
+            }
+            source.eat();
+            let mut value = 0;
+            let mut digits = 0;
+            loop {
+                if !source.has_next() {
+                    return Err(TokenError::UnexpectedEndOfSource);
+                }
+                let c = source.peek();
+                if c == '}' {
+                    source.eat();
+                    break;
+                }
+                // The closing quote of an unclosed escape code still closes the literal
+                let Some(digit) = c.to_digit(16) else {
+                    return Err(TokenError::InvalidStringEscapeCode);
+                };
+                source.eat();
+                value = value * 16 + digit;
+                digits += 1;
+                if digits > 6 {
+                    return Err(TokenError::InvalidStringEscapeCode);
+                }
+            }
+            if digits == 0 {
+                return Err(TokenError::InvalidStringEscapeCode);
+            }
+            let Some(c) = char::from_u32(value) else {
+                return Err(TokenError::InvalidUtf8);
+            };
+            Ok(Escape::Char(c))
         }
+        _ => Err(TokenError::InvalidStringEscapeCode),
     }
-    if let Some(s) = s.strip_prefix("-0x") {
-        if !s.starts_with('-') && !s.starts_with('+') {
-            if let Ok(value) = i64::from_str_radix(s, 16) {
-                return Some(Token::Int {
-                    span,
-                    value: -value,
-                });
+}
+
+fn push_char(buf: &mut Vec<u8>, c: char) {
+    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// Parses a character literal like `'a'` or `'\n'` after its opening `'`.
+fn parse_char(source: &mut Source) -> std::result::Result<char, TokenError> {
+    if !source.has_next() {
+        return Err(TokenError::UnexpectedEndOfSource);
+    }
+    let c = source.peek();
+    source.eat();
+    let c = match c {
+        '\\' => match parse_escape(source) {
+            Ok(Escape::Char(c)) => c,
+            Ok(Escape::Byte(byte)) => char::from(byte),
+            Err(err) => {
+                skip_char(source);
+                return Err(err);
             }
+        },
+        '\'' => return Err(TokenError::InvalidCharLiteral),
+        _ => c,
+    };
+    if !source.has_next() {
+        return Err(TokenError::UnexpectedEndOfSource);
+    }
+    if source.peek() != '\'' {
+        skip_char(source);
+        return Err(TokenError::InvalidCharLiteral);
+    }
+    source.eat();
+    Ok(c)
+}
+
+/// Skips the rest of an invalid character literal, so that it is included in the error.
+fn skip_char(source: &mut Source) {
+    while source.has_next() && source.peek() != '\'' && source.peek() != '\n' {
+        source.eat();
+    }
+    if source.has_next() && source.peek() == '\'' {
+        source.eat();
+    }
+}
+
+/// Parses an identifier or a number.
+///
+/// Anything starting with a digit, optionally after a sign, is a number:
+///
+/// ```text
+/// number  = ("-" | "+")? (integer | float)
+/// integer = ("0x" hex | "0o" oct | "0b" bin | dec) "u"?
+/// float   = dec ("." dec)? (("e" | "E") ("-" | "+")? dec)? "f"?
+/// ```
+///
+/// Digits can be separated by `_`. Integers are signed 64-bit integers, or unsigned ones with
+/// the `u` suffix. A float needs a fraction, an exponent or the `f` suffix.
+fn parse_token(span: Span, source: &Source) -> std::result::Result<Token, TokenError> {
+    let s = source.str(span.clone());
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if !s.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Token::Ident { span });
+    }
+    let (radix, digits) = if let Some(digits) = s.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = s.strip_prefix("0o") {
+        (8, digits)
+    } else if let Some(digits) = s.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, s)
+    };
+    if radix == 10 && is_float(digits) {
+        let digits = digits.strip_suffix('f').unwrap_or(digits).replace('_', "");
+        let Ok(value) = digits.parse::<f64>() else {
+            return Err(TokenError::InvalidNumber);
+        };
+        if value.is_infinite() {
+            return Err(TokenError::NumberOverflow);
         }
+        let value = if negative { -value } else { value };
+        return Ok(Token::Float { span, value });
     }
-    if let Some(s) = s.strip_suffix('u') {
-        if let Ok(value) = s.parse() {
-            return Some(Token::UInt { span, value });
+    let (unsigned, digits) = match digits.strip_suffix('u') {
+        Some(digits) => (true, digits),
+        None => (false, digits),
+    };
+    if !digits.chars().all(|c| c == '_' || c.is_digit(radix))
+        || !digits.chars().any(|c| c.is_digit(radix))
+    {
+        if radix == 10 && !digits.contains('.') {
+            return Err(TokenError::IdentStartingWithDigit);
         }
+        return Err(TokenError::InvalidNumber);
     }
-    if let Ok(value) = s.parse() {
-        return Some(Token::Int { span, value });
+    // Only overflows remain, as all digits are valid
+    let Ok(magnitude) = u64::from_str_radix(&digits.replace('_', ""), radix) else {
+        return Err(TokenError::NumberOverflow);
+    };
+    if unsigned {
+        if negative {
+            return Err(TokenError::NegativeUnsigned);
+        }
+        return Ok(Token::UInt {
+            span,
+            value: magnitude,
+        });
+    }
+    let value = if negative {
+        0i64.checked_sub_unsigned(magnitude)
+    } else {
+        i64::try_from(magnitude).ok()
+    };
+    let Some(value) = value else {
+        return Err(TokenError::NumberOverflow);
+    };
+    Ok(Token::Int { span, value })
+}
+
+/// Checks whether decimal digits have a fraction, an exponent or the `f` suffix.
+fn is_float(s: &str) -> bool {
+    let (s, mut float) = match s.strip_suffix('f') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    let Some(mut rest) = strip_digits(s) else {
+        return false;
+    };
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let Some(fraction_rest) = strip_digits(fraction) else {
+            return false;
+        };
+        rest = fraction_rest;
+        float = true;
     }
-    if let Ok(value) = s.parse() {
-        return Some(Token::Float { span, value });
+    if let Some(exponent) = rest.strip_prefix(|c: char| c == 'e' || c == 'E') {
+        let exponent = exponent
+            .strip_prefix(|c: char| c == '-' || c == '+')
+            .unwrap_or(exponent);
+        let Some(exponent_rest) = strip_digits(exponent) else {
+            return false;
+        };
+        rest = exponent_rest;
+        float = true;
     }
-    if s.chars().next().unwrap().is_ascii_digit() {
+    float && rest.is_empty()
+}
+
+/// Strips leading decimal digits and `_`, or returns `None` if there is no digit.
+fn strip_digits(s: &str) -> Option<&str> {
+    let rest = s.trim_start_matches(|c: char| c == '_' || c.is_ascii_digit());
+    if !s[..s.len() - rest.len()].contains(|c: char| c.is_ascii_digit()) {
         return None;
     }
-    Some(Token::Ident { span })
+    Some(rest)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{error::Error, Token};
+
+    use super::tokenize_recovering;
+
+    fn tokenize(src: &str) -> (Vec<Token>, Vec<Error>) {
+        let (tokens, errors) =
+            tokenize_recovering("main".to_string(), "main.lvt".to_string(), src.to_string());
+        (tokens.tokens, errors)
+    }
+
+    /// Tokenizes a single token without errors.
+    fn token(src: &str) -> Token {
+        let (mut tokens, errors) = tokenize(src);
+        assert!(errors.is_empty(), "{src:?}: {errors:?}");
+        assert_eq!(tokens.len(), 1, "{src:?}: {tokens:?}");
+        tokens.pop().unwrap()
+    }
+
+    /// Tokenizes a source with exactly one error.
+    fn error(src: &str) -> Error {
+        let (_, mut errors) = tokenize(src);
+        assert_eq!(errors.len(), 1, "{src:?}: {errors:?}");
+        errors.pop().unwrap()
+    }
+
+    fn int(src: &str) -> i64 {
+        match token(src) {
+            Token::Int { value, .. } => value,
+            token => panic!("{src:?}: {token:?}"),
+        }
+    }
+
+    fn uint(src: &str) -> u64 {
+        match token(src) {
+            Token::UInt { value, .. } => value,
+            token => panic!("{src:?}: {token:?}"),
+        }
+    }
+
+    fn float(src: &str) -> f64 {
+        match token(src) {
+            Token::Float { value, .. } => value,
+            token => panic!("{src:?}: {token:?}"),
+        }
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(int("42"), 42);
+        assert_eq!(int("-42"), -42);
+        assert_eq!(int("+42"), 42);
+        assert_eq!(int("0x1F"), 31);
+        assert_eq!(int("-0x10"), -16);
+        assert_eq!(int("0o17"), 15);
+        assert_eq!(int("0b1010"), 10);
+        assert_eq!(int("1_000_000"), 1_000_000);
+        assert_eq!(int("0b1111_0000"), 0xf0);
+        assert_eq!(uint("16u"), 16);
+        assert_eq!(uint("0x10u"), 16);
+        assert_eq!(uint("0o20u"), 16);
+        assert_eq!(uint("0b1_0000u"), 16);
+        assert_eq!(uint("0xffff_ffff_ffff_ffffu"), u64::MAX);
+    }
+
+    #[test]
+    fn test_integer_limits() {
+        assert_eq!(int("9223372036854775807"), i64::MAX);
+        assert_eq!(int("-9223372036854775808"), i64::MIN);
+        assert_eq!(int("-0x8000000000000000"), i64::MIN);
+        assert_eq!(uint("18446744073709551615u"), u64::MAX);
+        for src in [
+            "9223372036854775808",
+            "-9223372036854775809",
+            "0x8000000000000000",
+            "18446744073709551616u",
+            "0x1_0000_0000_0000_0000u",
+        ] {
+            assert!(
+                matches!(error(src), Error::NumberOverflow { .. }),
+                "{src:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(float("1.5"), 1.5);
+        assert_eq!(float("-1.5"), -1.5);
+        assert_eq!(float("1f"), 1.0);
+        assert_eq!(float("1.5f"), 1.5);
+        assert_eq!(float("1e3"), 1000.0);
+        assert_eq!(float("2.5E-1"), 0.25);
+        assert_eq!(float("1_000.000_1"), 1000.0001);
+        assert!(matches!(error("1e999"), Error::NumberOverflow { .. }));
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        assert!(matches!(error("-0x10u"), Error::NegativeUnsigned { .. }));
+        assert!(matches!(error("-1u"), Error::NegativeUnsigned { .. }));
+        for src in ["1.5u", "1.", "1.e3", "0x1.5", "0b102", "0o8", "0x", "0xu"] {
+            assert!(matches!(error(src), Error::InvalidNumber { .. }), "{src:?}");
+        }
+        assert!(matches!(
+            error("1abc"),
+            Error::IdentStartingWithDigit { .. }
+        ));
+        // Invalid numbers are kept as identifiers
+        let (tokens, _) = tokenize("(1.5u)");
+        assert!(matches!(tokens[1], Token::Ident { .. }));
+    }
+
+    #[test]
+    fn test_signs_and_idents() {
+        for src in ["-", "+", "+label", "-label", "a-1", "mod::name"] {
+            assert!(matches!(token(src), Token::Ident { .. }), "{src:?}");
+        }
+    }
+
+    #[test]
+    fn test_chars() {
+        assert_eq!(uint("'a'"), 'a' as u64);
+        assert_eq!(uint("'('"), '(' as u64);
+        assert_eq!(uint("')'"), ')' as u64);
+        assert_eq!(uint("';'"), ';' as u64);
+        assert_eq!(uint("'#'"), '#' as u64);
+        assert_eq!(uint("' '"), ' ' as u64);
+        assert_eq!(uint("'\"'"), '"' as u64);
+        assert_eq!(uint("'ä'"), 'ä' as u64);
+        assert_eq!(uint("'🦀'"), '🦀' as u64);
+        assert_eq!(uint(r"'\''"), '\'' as u64);
+        assert_eq!(uint(r"'\n'"), '\n' as u64);
+        assert_eq!(uint(r"'\x7f'"), 0x7f);
+        assert_eq!(uint(r"'\u{1F980}'"), 0x1f980);
+        // Quotes inside of identifiers do not start a character literal
+        assert!(matches!(token("a'b"), Token::Ident { .. }));
+        let (tokens, errors) = tokenize("(mov r0 '(')");
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(tokens.len(), 5);
+        assert!(matches!(tokens[3], Token::UInt { value: 0x28, .. }));
+    }
+
+    #[test]
+    fn test_invalid_chars() {
+        for src in ["''", "'ab'", "'a b'"] {
+            assert!(
+                matches!(error(src), Error::InvalidCharLiteral { .. }),
+                "{src:?}"
+            );
+        }
+        // The rest of the literal is skipped after an invalid escape code
+        assert!(matches!(
+            error(r"(a '\q' b)"),
+            Error::InvalidStringEscapeCode { span, .. } if span == (3..7)
+        ));
+        for src in ["'", "'a", "'\\"] {
+            assert!(
+                matches!(error(src), Error::UnexpectedEndOfSource { .. }),
+                "{src:?}"
+            );
+        }
+    }
+
+    fn string(src: &str) -> String {
+        match token(src) {
+            Token::String { value, .. } => value,
+            token => panic!("{src:?}: {token:?}"),
+        }
+    }
+
+    fn bytes(src: &str) -> Vec<u8> {
+        match token(src) {
+            Token::Bytes { value, .. } => value,
+            token => panic!("{src:?}: {token:?}"),
+        }
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(string(r#""\"\'\\\n\t\r\0""#), "\"'\\\n\t\r\0");
+        assert_eq!(string(r#""\x41\x7f""#), "A\x7f");
+        assert_eq!(string(r#""\u{41}\u{e4}\u{1F980}""#), "Aä🦀");
+        assert_eq!(string(r#""\u{000041}""#), "A");
+        assert_eq!(string(r#""\u{10FFFF}""#), "\u{10ffff}");
+        assert_eq!(string("\"a\nb\""), "a\nb");
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        for src in [
+            r#""\q""#,
+            r#""\x4g""#,
+            r#""\x4""#,
+            r#""\u41""#,
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{12""#,
+            r#""\u{4g}""#,
+        ] {
+            assert!(
+                matches!(error(src), Error::InvalidStringEscapeCode { .. }),
+                "{src:?}"
+            );
+        }
+        // Surrogates and values above 0x10FFFF are not chars
+        for src in [r#""\u{D800}""#, r#""\u{DFFF}""#, r#""\u{110000}""#] {
+            assert!(matches!(error(src), Error::InvalidUtf8 { .. }), "{src:?}");
+        }
+        assert!(matches!(
+            error(r#"(a "b\qc" d)"#),
+            Error::InvalidStringEscapeCode { span, .. } if span == (5..7)
+        ));
+        assert!(matches!(
+            error(r#"(a "\u{12" d)"#),
+            Error::InvalidStringEscapeCode { span, .. } if span == (4..9)
+        ));
+        assert!(matches!(
+            error(r#""\"#),
+            Error::UnexpectedEndOfSource { .. }
+        ));
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        assert_eq!(string(r#"r"\n\q\u{}""#), r"\n\q\u{}");
+        assert_eq!(string(r#"r"a\""#), r"a\");
+        assert_eq!(bytes(r#"br"\x00""#), br"\x00");
+        let (tokens, errors) = tokenize(r#"(r"\" x)"#);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(tokens.len(), 4);
+    }
+
+    #[test]
+    fn test_byte_strings() {
+        assert_eq!(bytes(r#"b"abc""#), b"abc");
+        assert_eq!(bytes(r#"b"\x00\x7f\x80\xff""#), [0x00, 0x7f, 0x80, 0xff]);
+        assert_eq!(bytes(r#"b"\n\u{e4}ä""#), "\nää".as_bytes());
+        // `\xNN` is a byte in byte strings, but a char in strings
+        assert_eq!(string(r#""\xff""#), "\u{ff}");
+        assert_eq!(string(r#""\xff""#).as_bytes(), [0xc3, 0xbf]);
+        assert!(matches!(error(r#"b"\u{D800}""#), Error::InvalidUtf8 { .. }));
+    }
+
+    #[test]
+    fn test_string_prefixes() {
+        assert!(matches!(token(r#""b""#), Token::String { .. }));
+        for src in [r#"x"a""#, r#"rb"a""#, r#"bb"a""#] {
+            assert!(
+                matches!(error(src), Error::NoWhitespaceBetweenTokens { .. }),
+                "{src:?}"
+            );
+        }
+    }
 }
@@ -1,60 +1,45 @@
-use ariadne::{Color, Label, Report, ReportKind};
+use std::io::{stderr, Result, Write};
 
-use super::source::Span;
+use ariadne::{Color, Label, Report, ReportKind, Source};
 
-pub fn span_error_report<'a>(
-    filename: &'a str,
-    span: &Span,
-    msg: &str,
-) -> Report<'a, (&'a str, Span)> {
-    Report::build(ReportKind::Error, filename, span.start)
-        .with_label(
-            Label::new((filename, span.clone()))
-                .with_color(Color::Red)
-                .with_message(msg),
-        )
-        .finish()
-}
+use super::{
+    message::{Message, Severity},
+    source::Span,
+};
 
-pub fn span_double_error_report<'a>(
-    filename: &'a str,
-    span_a: &Span,
-    span_b: &Span,
-    msg_a: &str,
-    msg_b: &str,
-) -> Report<'a, (&'a str, Span)> {
-    Report::build(ReportKind::Error, filename, span_a.start)
-        .with_label(
-            Label::new((filename, span_a.clone()))
-                .with_color(Color::Red)
-                .with_message(msg_a),
-        )
-        .with_label(
-            Label::new((filename, span_b.clone()))
-                .with_color(Color::Red)
-                .with_message(msg_b),
-        )
-        .finish()
+/// Renders the message to stderr.
+pub fn report(message: &Message) {
+    write(message, &mut stderr()).unwrap();
 }
 
-pub fn span_error_report_with_note<'a>(
-    filename: &'a str,
-    span: &Span,
-    msg: &str,
-    note: &str,
-) -> Report<'a, (&'a str, Span)> {
-    Report::build(ReportKind::Error, filename, span.start)
-        .with_label(
-            Label::new((filename, span.clone()))
-                .with_color(Color::Red)
+/// Renders the message to `out`.
+pub fn write(message: &Message, out: &mut dyn Write) -> Result<()> {
+    let (kind, color) = match message.severity {
+        Severity::Error => (ReportKind::Error, Color::Red),
+        Severity::Warning => (ReportKind::Warning, Color::Yellow),
+    };
+    let file = message.file;
+    let offset = message.span().map(|span| span.start).unwrap_or(0);
+    let mut report = Report::<(&str, Span)>::build(kind, file, offset);
+    // Messages that are repeated by a label are not printed twice
+    if !message
+        .labels
+        .iter()
+        .any(|(_, msg)| *msg == message.message)
+    {
+        report = report.with_message(&message.message);
+    }
+    for (span, msg) in &message.labels {
+        report = report.with_label(
+            Label::new((file, span.clone()))
+                .with_color(color)
                 .with_message(msg),
-        )
-        .with_note(note)
-        .finish()
-}
-
-pub fn error_report<'a>(filename: &'a str, msg: &str) -> Report<'a, (&'a str, Span)> {
-    Report::build(ReportKind::Error, filename, 0)
-        .with_message(msg)
+        );
+    }
+    for note in &message.notes {
+        report = report.with_note(note);
+    }
+    report
         .finish()
+        .write((file, Source::from(message.src)), out)
 }
@@ -0,0 +1,216 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+use super::{ariadne, source::Span};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// How diagnostics are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Colored reports
+    #[default]
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+impl MessageFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(MessageFormat::Human),
+            "json" => Some(MessageFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of a diagnostic, independent of how it is rendered.
+#[derive(Debug)]
+pub struct Message<'a> {
+    pub severity: Severity,
+    /// Stable name of the error or lint
+    pub code: &'static str,
+    pub file: &'a str,
+    pub src: &'a str,
+    pub message: String,
+    pub labels: Vec<(Span, String)>,
+    pub notes: Vec<String>,
+}
+
+impl<'a> Message<'a> {
+    /// An error that does not point into the source code.
+    pub fn error(code: &'static str, file: &'a str, msg: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            file,
+            src: "",
+            message: msg.into(),
+            labels: Vec::with_capacity(0),
+            notes: Vec::with_capacity(0),
+        }
+    }
+
+    pub fn span_error(
+        code: &'static str,
+        file: &'a str,
+        src: &'a str,
+        span: &Span,
+        msg: impl Into<String>,
+    ) -> Self {
+        let msg = msg.into();
+        Self {
+            severity: Severity::Error,
+            code,
+            file,
+            src,
+            labels: vec![(span.clone(), msg.clone())],
+            message: msg,
+            notes: Vec::with_capacity(0),
+        }
+    }
+
+    /// An error with two labels that explains the relation of the two spans in `msg`.
+    pub fn double_span_error(
+        code: &'static str,
+        file: &'a str,
+        src: &'a str,
+        spans: (&Span, &Span),
+        labels: (&str, &str),
+        msg: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            file,
+            src,
+            message: msg.into(),
+            labels: vec![
+                (spans.0.clone(), labels.0.to_string()),
+                (spans.1.clone(), labels.1.to_string()),
+            ],
+            notes: Vec::with_capacity(0),
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// The span of the first label.
+    pub fn span(&self) -> Option<&Span> {
+        self.labels.first().map(|(span, _)| span)
+    }
+
+    /// Returns the one-based line and column of a byte offset.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let before = &self.src[..offset.min(self.src.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    /// Writes the message to `out` in the given format.
+    pub fn emit(&self, format: MessageFormat, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            MessageFormat::Human => ariadne::write(self, out),
+            MessageFormat::Json => writeln!(out, "{}", self.to_json()),
+        }
+    }
+
+    /// Serializes the message into a single line of JSON.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push('{');
+        write!(
+            out,
+            "\"severity\":{},\"code\":{},\"file\":{},",
+            json_string(self.severity.name()),
+            json_string(self.code),
+            json_string(self.file)
+        )
+        .unwrap();
+        match self.span() {
+            Some(span) => {
+                out.push_str("\"span\":");
+                self.write_json_span(&mut out, span);
+            }
+            None => out.push_str("\"span\":null"),
+        }
+        write!(
+            out,
+            ",\"message\":{},\"labels\":[",
+            json_string(&self.message)
+        )
+        .unwrap();
+        for (i, (span, msg)) in self.labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"span\":");
+            self.write_json_span(&mut out, span);
+            write!(out, ",\"message\":{}}}", json_string(msg)).unwrap();
+        }
+        out.push_str("],\"notes\":[");
+        for (i, note) in self.notes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&json_string(note));
+        }
+        out.push_str("]}");
+        out
+    }
+
+    fn write_json_span(&self, out: &mut String, span: &Span) {
+        let (line, column) = self.line_column(span.start);
+        let (end_line, end_column) = self.line_column(span.end);
+        write!(
+            out,
+            "{{\"start\":{},\"end\":{},\"line\":{line},\"column\":{column},\
+            \"end_line\":{end_line},\"end_column\":{end_column}}}",
+            span.start, span.end
+        )
+        .unwrap();
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
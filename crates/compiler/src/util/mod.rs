@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 pub mod ariadne;
+pub mod message;
 pub mod source;
 
 #[inline(always)]
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     io::{Error, ErrorKind, Result, Seek, SeekFrom, Write},
 };
 
@@ -24,14 +24,79 @@ use crate::{
         Coord,
     },
     util::{alignment, MaxBitsU32},
+    Span,
 };
 
-#[derive(Debug)]
+/// Bytes reachable by the 22 bit word offsets of `ldr`, `lea` and conditional branches
+const NEAR_RANGE: usize = (1 << 21) * 4;
+/// Upper bound of the bytes a single op is assembled to, see [`emit_far_static`]
+const MAX_OP_SIZE: usize = 36;
+
+/// Modules, statics and functions are kept sorted by their index, so they are assembled in
+/// ascending module index and within a module in ascending static and function index.
+/// Identical input therefore always produces a byte-identical binary and offset table.
+#[derive(Debug, Default)]
 pub struct Binary {
-    pub modules: HashMap<usize, BinaryModule>,
+    pub modules: BTreeMap<usize, BinaryModule>,
 }
 
 impl Binary {
+    /// Removes all statics and functions that cannot be reached from `main`.
+    ///
+    /// Functions are followed through calls and statics through loads of their value or
+    /// address, across module boundaries.
+    pub fn filter(&mut self, main: Coord) {
+        let mut used_statics = HashSet::with_capacity(0);
+        let mut used_funcs = HashSet::with_capacity(0);
+        let mut queue = vec![main];
+        used_funcs.insert((main.module, main.element));
+        while let Some(coord) = queue.pop() {
+            let Some(func) = self
+                .modules
+                .get(&coord.module)
+                .and_then(|module| module.funcs.get(&coord.element))
+            else {
+                continue;
+            };
+            for op in &func.ops {
+                match op {
+                    LowOp::Call { coord } if used_funcs.insert((coord.module, coord.element)) => {
+                        queue.push(*coord);
+                    }
+                    LowOp::LoadStatic64 { coord, .. } | LowOp::LoadStaticAddress { coord, .. } => {
+                        used_statics.insert((coord.module, coord.element));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (module_index, module) in &mut self.modules {
+            module
+                .statics
+                .retain(|index, _| used_statics.contains(&(*module_index, *index)));
+            module
+                .funcs
+                .retain(|index, _| used_funcs.contains(&(*module_index, *index)));
+        }
+    }
+
+    /// Returns an upper bound of the size of the assembled binary.
+    pub fn size_bound(&self) -> usize {
+        let mut size = 0;
+        for module in self.modules.values() {
+            for static_ in module.statics.values() {
+                size += static_.size();
+            }
+            for func in module.funcs.values() {
+                for local in &func.locals {
+                    size += local.size();
+                }
+                size += func.ops.len() * MAX_OP_SIZE;
+            }
+        }
+        size
+    }
+
     pub fn assemble(
         &self,
         out: &mut (impl Write + Seek),
@@ -42,11 +107,13 @@ impl Binary {
         const ENTRYPOINT_OFFSET: u64 = 8;
         const HEADER_LENGTH: u64 = 16;
         let mut offset_table = OffsetTable {
-            table: HashMap::new(),
+            table: BTreeMap::new(),
+            sources: BTreeMap::new(),
         };
         out.write_all(b"\0urb")?;
         out.write_u32::<LittleEndian>(EXECUTABLE)?;
         out.write_u64::<LittleEndian>(0)?;
+        let far_statics = self.size_bound() >= NEAR_RANGE;
         let mut ptr = 0usize;
         let mut modules = HashMap::with_capacity(self.modules.len());
         let mut post_procs = Vec::with_capacity(0);
@@ -94,79 +161,54 @@ impl Binary {
                         );
                     }
                 }
+                let far_branches = func.ops.len() * MAX_OP_SIZE >= NEAR_RANGE;
                 for op in &func.ops {
-                    match op {
-                        LowOp::PutCoord { coord } => {
-                            coords.insert(*coord, ptr);
-                        }
-                        LowOp::BranchCoord { coord } => {
-                            local_post_procs
-                                .push(LocalPostProc::BranchCoord { ptr, coord: *coord });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
-                        }
-                        LowOp::BranchCoordIfNonZero { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordIfNonZero {
+                    if let Some((opcode, reg, coord)) = conditional_branch(op) {
+                        let opcode = opcode | reg.value() << 22;
+                        if far_branches {
+                            local_post_procs.push(LocalPostProc::FarBranchCoordIf {
                                 ptr,
-                                reg: *reg,
+                                opcode: inverted_branch(opcode),
                                 coord: *coord,
                             });
                             emit(&mut ptr, out, 0xFFFF_FFFF)?;
-                        }
-                        LowOp::BranchCoordIfZero { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordIfZero {
+                        } else {
+                            local_post_procs.push(LocalPostProc::BranchCoordIf {
                                 ptr,
-                                reg: *reg,
+                                opcode,
                                 coord: *coord,
                             });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
                         }
-                        LowOp::BranchCoordEqual { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordEqual {
-                                ptr,
-                                reg: *reg,
-                                coord: *coord,
-                            });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
-                        }
-                        LowOp::BranchCoordNonEqual { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordNonEqual {
-                                ptr,
-                                reg: *reg,
-                                coord: *coord,
-                            });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
-                        }
-                        LowOp::BranchCoordLess { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordLess {
-                                ptr,
-                                reg: *reg,
-                                coord: *coord,
-                            });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
+                        emit(&mut ptr, out, 0xFFFF_FFFF)?;
+                        continue;
+                    }
+                    match op {
+                        LowOp::PutCoord { coord } => {
+                            coords.insert(*coord, ptr);
                         }
-                        LowOp::BranchCoordGreater { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordGreater {
-                                ptr,
-                                reg: *reg,
-                                coord: *coord,
-                            });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
+                        LowOp::PutSpan { span } => {
+                            if offset_out.is_some() {
+                                if let Some(file) = &module.file {
+                                    offset_table.add_source(file.clone(), span.clone(), ptr);
+                                }
+                            }
                         }
-                        LowOp::BranchCoordLessEqual { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordLessEqual {
-                                ptr,
-                                reg: *reg,
-                                coord: *coord,
-                            });
+                        LowOp::BranchCoord { coord } => {
+                            local_post_procs
+                                .push(LocalPostProc::BranchCoord { ptr, coord: *coord });
                             emit(&mut ptr, out, 0xFFFF_FFFF)?;
                         }
-                        LowOp::BranchCoordGreaterEqual { reg, coord } => {
-                            local_post_procs.push(LocalPostProc::BranchCoordGreaterEqual {
-                                ptr,
-                                reg: *reg,
-                                coord: *coord,
-                            });
-                            emit(&mut ptr, out, 0xFFFF_FFFF)?;
+                        LowOp::BranchCoordIfNonZero { .. }
+                        | LowOp::BranchCoordIfZero { .. }
+                        | LowOp::BranchCoordEqual { .. }
+                        | LowOp::BranchCoordNonEqual { .. }
+                        | LowOp::BranchCoordLess { .. }
+                        | LowOp::BranchCoordGreater { .. }
+                        | LowOp::BranchCoordLessEqual { .. }
+                        | LowOp::BranchCoordGreaterEqual { .. } => unreachable!(),
+                        LowOp::LoadStatic64 { dst, coord } if far_statics => {
+                            let far = emit_far_static(&mut ptr, out, *dst, true, None)?;
+                            post_procs.push(GlobalPostProc::FarStatic { far, coord: *coord });
                         }
                         LowOp::LoadStatic64 { dst, coord } => {
                             post_procs.push(GlobalPostProc::LoadStatic64 {
@@ -178,12 +220,16 @@ impl Binary {
                         }
                         LowOp::LoadLocalStatic64 { dst, coord } => {
                             let offset = (locals[coord] as isize - ptr as isize) / 4;
-                            if (-(1 << 21)..(1 << 21) - 1).contains(&offset) {
-                                emit(&mut ptr, out, L0_LDR | dst.value() | offset.cut(22) << 5)?;
+                            if offset.fits(22) {
+                                emit(&mut ptr, out, L0_LDR | dst.value() | offset.cut(22)? << 5)?;
                             } else {
-                                todo!("Big range local static")
+                                emit_far_static(&mut ptr, out, *dst, true, Some(locals[coord]))?;
                             }
                         }
+                        LowOp::LoadStaticAddress { dst, coord } if far_statics => {
+                            let far = emit_far_static(&mut ptr, out, *dst, false, None)?;
+                            post_procs.push(GlobalPostProc::FarStatic { far, coord: *coord });
+                        }
                         LowOp::LoadStaticAddress { dst, coord } => {
                             post_procs.push(GlobalPostProc::LoadStaticAddress {
                                 ptr,
@@ -194,10 +240,10 @@ impl Binary {
                         }
                         LowOp::LoadLocalStaticAddress { dst, coord } => {
                             let offset = (locals[coord] as isize - ptr as isize) / 4;
-                            if (-(1 << 21)..(1 << 21) - 1).contains(&offset) {
-                                emit(&mut ptr, out, L0_LEA | dst.value() | offset.cut(22) << 5)?;
+                            if offset.fits(22) {
+                                emit(&mut ptr, out, L0_LEA | dst.value() | offset.cut(22)? << 5)?;
                             } else {
-                                todo!("Big range local static")
+                                emit_far_static(&mut ptr, out, *dst, false, Some(locals[coord]))?;
                             }
                         }
                         LowOp::Call { coord } => {
@@ -211,7 +257,7 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_ADD | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_ADD | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
                         )?,
                         LowOp::SubImmediate {
                             dst,
@@ -220,7 +266,7 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_SUB | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_SUB | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
                         )?,
                         LowOp::MulImmediate {
                             dst,
@@ -229,7 +275,7 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_MUL | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_MUL | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
                         )?,
                         LowOp::DivImmediate {
                             dst,
@@ -238,7 +284,7 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_DIV | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_DIV | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
                         )?,
                         LowOp::RemImmediate {
                             dst,
@@ -247,7 +293,7 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_REM | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_REM | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
                         )?,
                         LowOp::DivSignedImmediate {
                             dst,
@@ -256,7 +302,7 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_DIVS | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_DIVS | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
                         )?,
                         LowOp::RemSignedImmediate {
                             dst,
@@ -265,79 +311,81 @@ impl Binary {
                         } => emit(
                             &mut ptr,
                             out,
-                            L0_REMS | dst.value() | src.value() << 5 | immediate.cut(17) << 10,
+                            L0_REMS | dst.value() | src.value() << 5 | immediate.cut(17)? << 10,
+                        )?,
+                        LowOp::MoveImmediate { dst, immediate } => emit(
+                            &mut ptr,
+                            out,
+                            L0_MOV | dst.value() | immediate.cut(22)? << 5,
                         )?,
-                        LowOp::MoveImmediate { dst, immediate } => {
-                            emit(&mut ptr, out, L0_MOV | dst.value() | immediate.cut(22) << 5)?
-                        }
                         LowOp::MoveSignedImmediate { dst, immediate } => emit(
                             &mut ptr,
                             out,
-                            L0_MOVS | dst.value() | immediate.cut(22) << 5,
+                            L0_MOVS | dst.value() | immediate.cut(22)? << 5,
                         )?,
                         LowOp::ShiftLeftImmediate { dst, lhs, rhs } => emit(
                             &mut ptr,
                             out,
-                            L1_SHL | dst.value() | lhs.value() << 5 | rhs.cut(11) << 10,
+                            L1_SHL | dst.value() | lhs.value() << 5 | rhs.cut(11)? << 10,
                         )?,
                         LowOp::ShiftRightImmediate { dst, lhs, rhs } => emit(
                             &mut ptr,
                             out,
-                            L1_SHR | dst.value() | lhs.value() << 5 | rhs.cut(11) << 10,
+                            L1_SHR | dst.value() | lhs.value() << 5 | rhs.cut(11)? << 10,
                         )?,
                         LowOp::ShiftRightSignedImmediate { dst, lhs, rhs } => emit(
                             &mut ptr,
                             out,
-                            L1_SHRS | dst.value() | lhs.value() << 5 | rhs.cut(11) << 10,
+                            L1_SHRS | dst.value() | lhs.value() << 5 | rhs.cut(11)? << 10,
                         )?,
                         LowOp::Load8 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_LDRB | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_LDRB | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Load16 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_LDRH | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_LDRH | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Load32 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_LDRW | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_LDRW | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Load64 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_LDR | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_LDR | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Store8 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_STRB | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_STRB | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Store16 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_STRH | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_STRH | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Store32 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_STRW | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_STRW | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::Store64 { dst, src, offset } => emit(
                             &mut ptr,
                             out,
-                            L1_STR | dst.value() | src.value() << 5 | offset.cut(11) << 10,
+                            L1_STR | dst.value() | src.value() << 5 | offset.cut(11)? << 10,
                         )?,
                         LowOp::InterruptImmediate { id } => {
                             emit(&mut ptr, out, L1_INT | *id as u32)?
                         }
                         LowOp::NativeCallImmediate { id } => {
-                            emit(&mut ptr, out, L1_NCALL | id.cut(21))?
+                            emit(&mut ptr, out, L1_NCALL | id.cut(21)?)?
                         }
                         LowOp::VirtualCallImmediate { id } => {
-                            emit(&mut ptr, out, L1_VCALL | id.cut(21))?
+                            emit(&mut ptr, out, L1_VCALL | id.cut(21)?)?
                         }
                         LowOp::Add { dst, lhs, rhs } => emit(
                             &mut ptr,
@@ -478,57 +526,20 @@ impl Binary {
                     match local_post_proc {
                         LocalPostProc::BranchCoord { ptr, coord } => {
                             out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH | offset.cut(27))?;
-                        }
-                        LocalPostProc::BranchCoordIfNonZero { ptr, reg, coord } => {
-                            out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_NZ | offset.cut(22) | reg.value() << 22)?;
-                        }
-                        LocalPostProc::BranchCoordIfZero { ptr, reg, coord } => {
-                            out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_ZR | offset.cut(22) | reg.value() << 22)?;
-                        }
-                        LocalPostProc::BranchCoordEqual { ptr, reg, coord } => {
-                            out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_EQ | offset.cut(22) | reg.value() << 22)?;
-                        }
-                        LocalPostProc::BranchCoordNonEqual { ptr, reg, coord } => {
-                            out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_NE | offset.cut(22) | reg.value() << 22)?;
+                            let offset = branch_offset(ptr, coords[&coord], 27)?;
+                            emit_in_place(out, L0_BRANCH | offset)?;
                         }
-                        LocalPostProc::BranchCoordLess { ptr, reg, coord } => {
+                        LocalPostProc::BranchCoordIf { ptr, opcode, coord } => {
                             out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_LT | offset.cut(22) | reg.value() << 22)?;
+                            let offset = branch_offset(ptr, coords[&coord], 22)?;
+                            emit_in_place(out, opcode | offset)?;
                         }
-                        LocalPostProc::BranchCoordGreater { ptr, reg, coord } => {
+                        LocalPostProc::FarBranchCoordIf { ptr, opcode, coord } => {
+                            // Skip the unconditional branch if the condition is not met
                             out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_GT | offset.cut(22) | reg.value() << 22)?;
-                        }
-                        LocalPostProc::BranchCoordLessEqual { ptr, reg, coord } => {
-                            out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_LE | offset.cut(22) | reg.value() << 22)?;
-                        }
-                        LocalPostProc::BranchCoordGreaterEqual { ptr, reg, coord } => {
-                            out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
-                            let dst = coords[&coord];
-                            let offset = (dst as isize - ptr as isize) / 4;
-                            emit_in_place(out, L0_BRANCH_GE | offset.cut(22) | reg.value() << 22)?;
+                            emit_in_place(out, opcode | 2)?;
+                            let offset = branch_offset(ptr + 4, coords[&coord], 27)?;
+                            emit_in_place(out, L0_BRANCH | offset)?;
                         }
                     }
                 }
@@ -541,20 +552,25 @@ impl Binary {
                 GlobalPostProc::Call { ptr, coord } => {
                     out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
                     let dst = modules[&coord.module].funcs[&coord.element];
-                    let offset = (dst as isize - ptr as isize) / 4;
-                    emit_in_place(out, L0_BRANCH_L | offset.cut(27))?;
+                    let offset = branch_offset(ptr, dst, 27)?;
+                    emit_in_place(out, L0_BRANCH_L | offset)?;
                 }
                 GlobalPostProc::LoadStatic64 { ptr, dst, coord } => {
                     out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
                     let coord = modules[&coord.module].statics[&coord.element];
-                    let offset = (coord as isize - ptr as isize) / 4;
-                    emit_in_place(out, L0_LDR | dst.value() | offset.cut(22) << 5)?;
+                    let offset = branch_offset(ptr, coord, 22)?;
+                    emit_in_place(out, L0_LDR | dst.value() | offset << 5)?;
                 }
                 GlobalPostProc::LoadStaticAddress { ptr, dst, coord } => {
                     out.seek(SeekFrom::Start(HEADER_LENGTH + ptr as u64))?;
                     let coord = modules[&coord.module].statics[&coord.element];
-                    let offset = (coord as isize - ptr as isize) / 4;
-                    emit_in_place(out, L0_LEA | dst.value() | offset.cut(22) << 5)?;
+                    let offset = branch_offset(ptr, coord, 22)?;
+                    emit_in_place(out, L0_LEA | dst.value() | offset << 5)?;
+                }
+                GlobalPostProc::FarStatic { far, coord } => {
+                    out.seek(SeekFrom::Start(HEADER_LENGTH + far.literal as u64))?;
+                    let coord = modules[&coord.module].statics[&coord.element];
+                    out.write_i64::<LittleEndian>(coord as i64 - far.base as i64)?;
                 }
             }
         }
@@ -567,29 +583,13 @@ impl Binary {
     }
 }
 
-impl Default for Binary {
-    fn default() -> Self {
-        Self {
-            modules: HashMap::with_capacity(0),
-        }
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct BinaryModule {
     pub name: Option<String>,
-    pub statics: HashMap<usize, BinaryStatic>,
-    pub funcs: HashMap<usize, BinaryFunc>,
-}
-
-impl Default for BinaryModule {
-    fn default() -> Self {
-        Self {
-            name: None,
-            statics: HashMap::with_capacity(0),
-            funcs: HashMap::with_capacity(0),
-        }
-    }
+    /// Source file the spans of [`LowOp::PutSpan`] refer to
+    pub file: Option<String>,
+    pub statics: BTreeMap<usize, BinaryStatic>,
+    pub funcs: BTreeMap<usize, BinaryFunc>,
 }
 
 #[derive(Debug)]
@@ -610,6 +610,11 @@ pub enum BinaryStatic {
         name: Option<String>,
         value: String,
     },
+    /// Raw bytes, which unlike strings are not null-terminated
+    Bytes {
+        name: Option<String>,
+        value: Vec<u8>,
+    },
     FilledBuffer {
         name: Option<String>,
         size: usize,
@@ -636,6 +641,7 @@ impl BinaryStatic {
             | BinaryStatic::UInt { name, .. }
             | BinaryStatic::Float { name, .. }
             | BinaryStatic::String { name, .. }
+            | BinaryStatic::Bytes { name, .. }
             | BinaryStatic::FilledBuffer { name, .. }
             | BinaryStatic::IntArray { name, .. }
             | BinaryStatic::UIntArray { name, .. }
@@ -643,6 +649,21 @@ impl BinaryStatic {
         }
     }
 
+    /// Returns the amount of bytes written by [`BinaryStatic::assemble`].
+    pub fn size(&self) -> usize {
+        match self {
+            BinaryStatic::Int { .. } | BinaryStatic::UInt { .. } | BinaryStatic::Float { .. } => 8,
+            BinaryStatic::String { value, .. } => {
+                8 + value.len() + 1 + alignment(value.len() + 1, 4)
+            }
+            BinaryStatic::Bytes { value, .. } => 8 + value.len() + alignment(value.len(), 4),
+            BinaryStatic::FilledBuffer { size, .. } => 8 + size + alignment(*size, 4),
+            BinaryStatic::IntArray { values, .. } => 8 + values.len() * 8,
+            BinaryStatic::UIntArray { values, .. } => 8 + values.len() * 8,
+            BinaryStatic::FloatArray { values, .. } => 8 + values.len() * 8,
+        }
+    }
+
     pub fn assemble(&self, ptr: &mut usize, out: &mut (impl Write + Seek)) -> Result<usize> {
         let mut addr = *ptr;
         match self {
@@ -671,6 +692,17 @@ impl BinaryStatic {
                     *ptr += 1;
                 }
             }
+            BinaryStatic::Bytes { value, .. } => {
+                out.write_u64::<LittleEndian>(value.len() as u64)?;
+                *ptr += 8;
+                addr = *ptr;
+                out.write_all(value)?;
+                *ptr += value.len();
+                for _ in 0..alignment(value.len(), 4) {
+                    out.write_u8(0)?;
+                    *ptr += 1;
+                }
+            }
             BinaryStatic::FilledBuffer { size, fill, .. } => {
                 out.write_u64::<LittleEndian>(*size as u64)?;
                 *ptr += 8;
@@ -710,7 +742,7 @@ impl BinaryStatic {
                 for value in values {
                     out.write_f64::<LittleEndian>(*value)?;
                 }
-            },
+            }
         }
         Ok(addr)
     }
@@ -738,8 +770,13 @@ pub struct ModuleTable {
     pub funcs: HashMap<usize, usize>,
 }
 
+/// Names of statics, locals and functions, written in ascending order of their offset.
+///
+/// Source locations follow as `d <file>:<start>..<end> <offset>` lines, every instruction
+/// from the offset up to the next source location was produced by the byte span of the file.
 pub struct OffsetTable {
-    pub table: HashMap<usize, (char, String)>,
+    pub table: BTreeMap<usize, (char, String)>,
+    pub sources: BTreeMap<usize, (String, Span)>,
 }
 
 impl OffsetTable {
@@ -747,6 +784,18 @@ impl OffsetTable {
         self.table.insert(offset, (c, name));
     }
 
+    pub fn add_source(&mut self, file: String, span: Span, offset: usize) {
+        self.sources.insert(offset, (file, span));
+    }
+
+    /// Returns the file and span that produced the instruction at `offset`.
+    pub fn source(&self, offset: usize) -> Option<&(String, Span)> {
+        self.sources
+            .range(..=offset)
+            .next_back()
+            .map(|(_, source)| source)
+    }
+
     pub fn write(&self, out: &mut impl Write) -> Result<()> {
         for (offset, (c, name)) in &self.table {
             out.write_u8(*c as u8)?;
@@ -756,12 +805,36 @@ impl OffsetTable {
             out.write_all(format!("{offset:x}").as_bytes())?;
             out.write_u8(b'\n')?;
         }
+        for (offset, (file, span)) in &self.sources {
+            writeln!(out, "d {file}:{}..{} {offset:x}", span.start, span.end)?;
+        }
         Ok(())
     }
 
     pub fn read_offset_key(read: &str) -> Result<Self> {
-        let mut table = HashMap::with_capacity(0);
+        let mut table = BTreeMap::new();
+        let mut sources = BTreeMap::new();
         for line in read.lines() {
+            if let Some(line) = line.strip_prefix("d ") {
+                let Some((source, offset)) = line.rsplit_once(' ') else {
+                    return Err(Error::new(ErrorKind::Other, "Invalid file format"));
+                };
+                let Some((file, span)) = source.rsplit_once(':') else {
+                    return Err(Error::new(ErrorKind::Other, "Invalid file format"));
+                };
+                let Some((start, end)) = span.split_once("..") else {
+                    return Err(Error::new(ErrorKind::Other, "Invalid file format"));
+                };
+                let (Ok(start), Ok(end), Ok(offset)) = (
+                    start.parse(),
+                    end.parse(),
+                    usize::from_str_radix(offset, 16),
+                ) else {
+                    return Err(Error::new(ErrorKind::Other, "Invalid file format"));
+                };
+                sources.insert(offset, (file.to_string(), start..end));
+                continue;
+            }
             let mut split = line.split(' ');
             let Some(c) = split.next() else {
                 return Err(Error::new(ErrorKind::Other, "Invalid file format"));
@@ -788,26 +861,150 @@ impl OffsetTable {
             };
             table.insert(offset, (c, name.to_string()));
         }
-        Ok(Self { table })
+        Ok(Self { table, sources })
     }
 }
 
 pub enum LocalPostProc {
-    BranchCoord { ptr: usize, coord: usize },
-    BranchCoordIfNonZero { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordIfZero { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordEqual { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordNonEqual { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordLess { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordGreater { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordLessEqual { ptr: usize, reg: Reg, coord: usize },
-    BranchCoordGreaterEqual { ptr: usize, reg: Reg, coord: usize },
+    BranchCoord {
+        ptr: usize,
+        coord: usize,
+    },
+    /// `opcode` contains the condition and register
+    BranchCoordIf {
+        ptr: usize,
+        opcode: u32,
+        coord: usize,
+    },
+    /// `opcode` contains the inverted condition and register
+    FarBranchCoordIf {
+        ptr: usize,
+        opcode: u32,
+        coord: usize,
+    },
 }
 
 pub enum GlobalPostProc {
     Call { ptr: usize, coord: Coord },
     LoadStatic64 { ptr: usize, dst: Reg, coord: Coord },
     LoadStaticAddress { ptr: usize, dst: Reg, coord: Coord },
+    FarStatic { far: FarStatic, coord: Coord },
+}
+
+/// Location of a sequence emitted by [`emit_far_static`].
+pub struct FarStatic {
+    /// Address loaded by `ldpc`
+    pub base: usize,
+    /// Address of the literal holding the distance from `base` to the static
+    pub literal: usize,
+}
+
+/// Returns the opcode and operands of a conditional branch.
+fn conditional_branch(op: &LowOp) -> Option<(u32, &Reg, &usize)> {
+    match op {
+        LowOp::BranchCoordIfNonZero { reg, coord } => Some((L0_BRANCH_NZ, reg, coord)),
+        LowOp::BranchCoordIfZero { reg, coord } => Some((L0_BRANCH_ZR, reg, coord)),
+        LowOp::BranchCoordEqual { reg, coord } => Some((L0_BRANCH_EQ, reg, coord)),
+        LowOp::BranchCoordNonEqual { reg, coord } => Some((L0_BRANCH_NE, reg, coord)),
+        LowOp::BranchCoordLess { reg, coord } => Some((L0_BRANCH_LT, reg, coord)),
+        LowOp::BranchCoordGreater { reg, coord } => Some((L0_BRANCH_GT, reg, coord)),
+        LowOp::BranchCoordLessEqual { reg, coord } => Some((L0_BRANCH_LE, reg, coord)),
+        LowOp::BranchCoordGreaterEqual { reg, coord } => Some((L0_BRANCH_GE, reg, coord)),
+        _ => None,
+    }
+}
+
+/// Inverts the condition of a conditional branch and keeps its register.
+fn inverted_branch(opcode: u32) -> u32 {
+    let reg = opcode & (0b11111 << 22);
+    let inverted = match opcode & !(0b11111 << 22) {
+        L0_BRANCH_NZ => L0_BRANCH_ZR,
+        L0_BRANCH_ZR => L0_BRANCH_NZ,
+        L0_BRANCH_EQ => L0_BRANCH_NE,
+        L0_BRANCH_NE => L0_BRANCH_EQ,
+        L0_BRANCH_LT => L0_BRANCH_GE,
+        L0_BRANCH_GE => L0_BRANCH_LT,
+        L0_BRANCH_GT => L0_BRANCH_LE,
+        L0_BRANCH_LE => L0_BRANCH_GT,
+        _ => unreachable!(),
+    };
+    inverted | reg
+}
+
+/// Returns the word offset from `from` to `to` cut to `bits` bits.
+fn branch_offset(from: usize, to: usize, bits: usize) -> Result<u32> {
+    let offset = (to as isize - from as isize) / 4;
+    if !offset.fits(bits) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("The address {to:#010x} is out of range for the instruction at {from:#010x}"),
+        ));
+    }
+    offset.cut(bits)
+}
+
+/// Loads the address or value of a static that is out of range of `lea` and `ldr`.
+///
+/// The distance to the static is stored in a literal after the sequence. Another register
+/// holds the distance and is saved below the stack pointer in the meantime. If `target` is
+/// unknown the literal has to be filled in later.
+///
+/// ```text
+/// str r31 rX -8
+/// ldpc dst
+/// ldr rX [literal]
+/// add dst dst rX
+/// ldr rX r31 -8
+/// ldr dst dst 0 ; only when loading the value
+/// branch [end]
+/// literal: distance from ldpc to the static
+/// end:
+/// ```
+fn emit_far_static(
+    ptr: &mut usize,
+    out: &mut (impl Write + Seek),
+    dst: Reg,
+    load: bool,
+    target: Option<usize>,
+) -> Result<FarStatic> {
+    const STACK_POINTER: u32 = 31;
+    if dst.value() == STACK_POINTER {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "A far static cannot be loaded into the stack pointer",
+        ));
+    }
+    let temp = if dst.value() == 0 { 1 } else { 0 };
+    let words = if load { 7 } else { 6 };
+    let literal = *ptr + words * 4;
+    emit(
+        ptr,
+        out,
+        L1_STR | STACK_POINTER | temp << 5 | (-8).cut(11)? << 10,
+    )?;
+    let base = *ptr;
+    emit(ptr, out, L4_LDPC | dst.value())?;
+    let offset = branch_offset(*ptr, literal, 22)?;
+    emit(ptr, out, L0_LDR | temp | offset << 5)?;
+    emit(
+        ptr,
+        out,
+        L2_ADD | dst.value() | dst.value() << 5 | temp << 10,
+    )?;
+    emit(
+        ptr,
+        out,
+        L1_LDR | temp | STACK_POINTER << 5 | (-8).cut(11)? << 10,
+    )?;
+    if load {
+        emit(ptr, out, L1_LDR | dst.value() | dst.value() << 5)?;
+    }
+    let offset = branch_offset(*ptr, literal + 8, 27)?;
+    emit(ptr, out, L0_BRANCH | offset)?;
+    let distance = target.map_or(0, |target| target as i64 - base as i64);
+    out.write_i64::<LittleEndian>(distance)?;
+    *ptr += 8;
+    Ok(FarStatic { base, literal })
 }
 
 pub fn emit(ptr: &mut usize, out: &mut (impl Write + Seek), insn: u32) -> Result<()> {
@@ -818,3 +1015,185 @@ pub fn emit(ptr: &mut usize, out: &mut (impl Write + Seek), insn: u32) -> Result
 pub fn emit_in_place(out: &mut (impl Write + Seek), insn: u32) -> Result<()> {
     out.write_u32::<LittleEndian>(insn)
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use byteorder::{LittleEndian, ReadBytesExt};
+    use urban_common::opcodes::{L0_BRANCH, L0_LDR, L1_LDR, L1_STR, L2_ADD, L4_LDPC};
+
+    use crate::layers::{
+        lower::{LowOp, Reg},
+        Coord,
+    };
+
+    use super::{
+        branch_offset, emit_far_static, Binary, BinaryFunc, BinaryModule, BinaryStatic, NEAR_RANGE,
+    };
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        let mut cursor = Cursor::new(bytes);
+        let mut words = Vec::with_capacity(bytes.len() / 4);
+        while let Ok(word) = cursor.read_u32::<LittleEndian>() {
+            words.push(word);
+        }
+        words
+    }
+
+    #[test]
+    fn test_emit_far_static() {
+        let mut out = Cursor::new(Vec::new());
+        let mut ptr = 0;
+        let far = emit_far_static(&mut ptr, &mut out, Reg::new(2), true, Some(100)).unwrap();
+        assert_eq!(far.base, 4);
+        assert_eq!(far.literal, 28);
+        assert_eq!(ptr, 36);
+        let bytes = out.into_inner();
+        assert_eq!(
+            words(&bytes[..28]),
+            [
+                L1_STR | 31 | (-8i32 as u32 & 0x7FF) << 10,
+                L4_LDPC | 2,
+                L0_LDR | 5 << 5,
+                L2_ADD | 2 | 2 << 5,
+                L1_LDR | 31 << 5 | (-8i32 as u32 & 0x7FF) << 10,
+                L1_LDR | 2 | 2 << 5,
+                L0_BRANCH | 3,
+            ]
+        );
+        let distance = (&bytes[28..]).read_i64::<LittleEndian>().unwrap();
+        assert_eq!(distance, 100 - 4);
+    }
+
+    #[test]
+    fn test_assemble_far_static() {
+        // The buffer pushes the function out of the range of `ldr` to the first static
+        let module = BinaryModule {
+            statics: [
+                (
+                    0,
+                    BinaryStatic::Int {
+                        name: None,
+                        value: 42,
+                    },
+                ),
+                (
+                    1,
+                    BinaryStatic::FilledBuffer {
+                        name: None,
+                        size: NEAR_RANGE,
+                        fill: 0,
+                    },
+                ),
+            ]
+            .into(),
+            funcs: [(
+                0,
+                BinaryFunc {
+                    ops: vec![
+                        LowOp::LoadStatic64 {
+                            dst: Reg::new(0),
+                            coord: Coord {
+                                module: 0,
+                                element: 0,
+                            },
+                        },
+                        LowOp::Halt,
+                    ],
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        let mut binary = Binary::default();
+        binary.modules.insert(0, module);
+        let mut out = Cursor::new(Vec::new());
+        let main = Coord {
+            module: 0,
+            element: 0,
+        };
+        binary
+            .assemble(&mut out, None::<&mut Vec<u8>>, main)
+            .unwrap();
+        let bytes = &out.get_ref()[16..];
+        let entrypoint = (&out.get_ref()[8..16]).read_u64::<LittleEndian>().unwrap() as usize;
+        assert!(entrypoint >= NEAR_RANGE);
+        let code = words(&bytes[entrypoint..entrypoint + 28]);
+        assert_eq!(code[1], L4_LDPC);
+        // `ldpc` loads the address of itself, adding the literal yields the static at 0
+        let literal = (&bytes[entrypoint + 28..])
+            .read_i64::<LittleEndian>()
+            .unwrap();
+        assert_eq!(entrypoint as i64 + 4 + literal, 0);
+    }
+
+    #[test]
+    fn test_branch_out_of_range() {
+        assert_eq!(branch_offset(0, NEAR_RANGE - 4, 22).unwrap(), (1 << 21) - 1);
+        assert!(branch_offset(0, NEAR_RANGE, 22).is_err());
+        assert!(branch_offset(NEAR_RANGE + 4, 0, 22).is_err());
+    }
+
+    fn coord(module: usize, element: usize) -> Coord {
+        Coord { module, element }
+    }
+
+    fn module(statics: usize, funcs: Vec<Vec<LowOp>>) -> BinaryModule {
+        BinaryModule {
+            statics: (0..statics)
+                .map(|index| {
+                    let value = index as i64;
+                    (index, BinaryStatic::Int { name: None, value })
+                })
+                .collect(),
+            funcs: funcs
+                .into_iter()
+                .map(|ops| BinaryFunc {
+                    ops,
+                    ..Default::default()
+                })
+                .enumerate()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let dst = Reg::new(0);
+        let main = vec![
+            LowOp::Call { coord: coord(1, 0) },
+            LowOp::LoadStatic64 {
+                dst,
+                coord: coord(1, 0),
+            },
+        ];
+        let unreachable = vec![LowOp::LoadStaticAddress {
+            dst,
+            coord: coord(1, 2),
+        }];
+        let callee = vec![
+            LowOp::LoadStaticAddress {
+                dst,
+                coord: coord(1, 1),
+            },
+            LowOp::Call { coord: coord(0, 0) },
+        ];
+        let mut binary = Binary::default();
+        binary.modules.insert(0, module(1, vec![main, unreachable]));
+        binary
+            .modules
+            .insert(1, module(3, vec![callee, Vec::new()]));
+        binary.filter(coord(0, 0));
+        let kept = |module: usize| {
+            let module = &binary.modules[&module];
+            let statics: Vec<_> = module.statics.keys().cloned().collect();
+            let funcs: Vec<_> = module.funcs.keys().cloned().collect();
+            (statics, funcs)
+        };
+        assert_eq!(kept(0), (vec![], vec![0]));
+        assert_eq!(kept(1), (vec![0, 1], vec![0]));
+    }
+}
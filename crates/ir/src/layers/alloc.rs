@@ -0,0 +1,182 @@
+use std::{collections::HashMap, ops::Range};
+
+use super::{destructure::Op, lower::Reg, upper::Expr};
+
+/// Registers 16 to 30 are persistent and hold variables, register 31 is the stack pointer
+pub const VAR_REGS: Range<u8> = 16..31;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    Reg(Reg),
+    /// Index of an 8 byte slot in the stack frame
+    Stack(usize),
+}
+
+#[derive(Debug)]
+pub struct Allocation {
+    pub locations: Vec<Location>,
+    /// Persistent registers that are written by the function
+    pub saved: Vec<Reg>,
+    pub slots: usize,
+}
+
+/// Assigns every variable a persistent register or a stack slot using linear scan.
+///
+/// Live ranges are computed over the op indices and extended over loops, variables with
+/// overlapping ranges never share a location. When all registers are taken the variable
+/// whose range ends last is spilled to the stack.
+pub fn allocate(var_count: usize, params: usize, ops: &[Op]) -> Allocation {
+    let mut ranges: Vec<Option<(usize, usize)>> = vec![None; var_count];
+    for param in ranges.iter_mut().take(params) {
+        *param = Some((0, 0));
+    }
+    let mut touch = |index: usize, pos: usize| {
+        let range = &mut ranges[index];
+        *range = Some(match *range {
+            Some((start, end)) => (start.min(pos), end.max(pos)),
+            None => (pos, pos),
+        });
+    };
+    let mut coords = HashMap::with_capacity(0);
+    let mut loops = Vec::with_capacity(0);
+    for (pos, op) in ops.iter().enumerate() {
+        match op {
+            Op::PutCoord { coord } => {
+                coords.insert(*coord, pos);
+            }
+            Op::PutSpan { .. } => {}
+            Op::BranchCoord { coord } => {
+                if let Some(start) = coords.get(coord) {
+                    loops.push((*start, pos));
+                }
+            }
+            Op::BranchCoordIf {
+                coord, left, right, ..
+            } => {
+                if let Some(start) = coords.get(coord) {
+                    loops.push((*start, pos));
+                }
+                visit_vars(left, &mut |index| touch(index, pos));
+                visit_vars(right, &mut |index| touch(index, pos));
+            }
+            Op::Let { index, expr } | Op::Assign { index, expr } => {
+                visit_vars(expr, &mut |index| touch(index, pos));
+                touch(*index, pos);
+            }
+            Op::Return { expr } => {
+                if let Some(expr) = expr {
+                    visit_vars(expr, &mut |index| touch(index, pos));
+                }
+            }
+            Op::Call { params, .. } => {
+                for param in params {
+                    visit_vars(param, &mut |index| touch(index, pos));
+                }
+            }
+        }
+    }
+    // A variable that is alive somewhere in a loop has to survive the whole loop
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (loop_start, loop_end) in &loops {
+            for (start, end) in ranges.iter_mut().flatten() {
+                if *start > *loop_end || *end < *loop_start {
+                    continue;
+                }
+                if *start > *loop_start || *end < *loop_end {
+                    *start = (*start).min(*loop_start);
+                    *end = (*end).max(*loop_end);
+                    changed = true;
+                }
+            }
+        }
+    }
+    let mut order = (0..var_count)
+        .filter(|i| ranges[*i].is_some())
+        .collect::<Vec<_>>();
+    order.sort_by_key(|i| ranges[*i].unwrap());
+    let mut locations = vec![Location::Stack(0); var_count];
+    let mut free = VAR_REGS.rev().map(Reg::new).collect::<Vec<_>>();
+    let mut active: Vec<usize> = Vec::with_capacity(VAR_REGS.len());
+    let mut saved = Vec::with_capacity(0);
+    let mut slots = 0;
+    let mut spill = |locations: &mut Vec<Location>, index: usize| {
+        locations[index] = Location::Stack(slots);
+        slots += 1;
+    };
+    for index in order {
+        let (start, end) = ranges[index].unwrap();
+        active.retain(|other| {
+            if ranges[*other].unwrap().1 >= start {
+                return true;
+            }
+            let Location::Reg(reg) = locations[*other] else {
+                unreachable!()
+            };
+            free.push(reg);
+            false
+        });
+        if let Some(reg) = free.pop() {
+            if !saved.contains(&reg) {
+                saved.push(reg);
+            }
+            locations[index] = Location::Reg(reg);
+            active.push(index);
+            continue;
+        }
+        let (victim_pos, victim) = active
+            .iter()
+            .cloned()
+            .enumerate()
+            .max_by_key(|(_, other)| ranges[*other].unwrap().1)
+            .unwrap();
+        if ranges[victim].unwrap().1 > end {
+            locations[index] = locations[victim];
+            spill(&mut locations, victim);
+            active[victim_pos] = index;
+        } else {
+            spill(&mut locations, index);
+        }
+    }
+    saved.sort_by_key(|reg| reg.value());
+    Allocation {
+        locations,
+        saved,
+        slots,
+    }
+}
+
+fn visit_vars(expr: &Expr, f: &mut impl FnMut(usize)) {
+    match expr {
+        Expr::Variable { index, .. } => f(*index),
+        Expr::Static { .. }
+        | Expr::StaticAddress { .. }
+        | Expr::Int { .. }
+        | Expr::UInt { .. }
+        | Expr::Float { .. }
+        | Expr::String { .. } => {}
+        Expr::CastInt { expr, .. }
+        | Expr::CastUInt { expr, .. }
+        | Expr::CastFloat { expr, .. }
+        | Expr::BitNot { expr, .. } => visit_vars(expr, f),
+        Expr::Add { left, right, .. }
+        | Expr::Sub { left, right, .. }
+        | Expr::Mul { left, right, .. }
+        | Expr::Div { left, right, .. }
+        | Expr::Rem { left, right, .. }
+        | Expr::BitAnd { left, right, .. }
+        | Expr::BitOr { left, right, .. }
+        | Expr::BitXor { left, right, .. }
+        | Expr::ShiftLeft { left, right, .. }
+        | Expr::ShiftRight { left, right, .. } => {
+            visit_vars(left, f);
+            visit_vars(right, f);
+        }
+        Expr::Call { params, .. } => {
+            for param in params {
+                visit_vars(param, f);
+            }
+        }
+    }
+}
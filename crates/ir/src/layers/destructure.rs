@@ -1,9 +1,24 @@
+use crate::{binary::BinaryStatic, Span};
+
 use super::{
+    alloc::{allocate, Location},
     error::Result,
+    lower::{LowOp, LowerLayer, Reg},
     upper::{Expr, Var},
-    CompareType, Coord,
+    BinaryOpType, CompareType, Coord, Type,
 };
 
+/// Stack pointer as described in `ABI.md`
+const STACK_POINTER: Reg = Reg::new(31);
+/// Registers 0 to 14 are used for temporary values and call parameters
+const TEMP_REGS: u8 = 15;
+/// Parameters that don't fit into the registers 0 to 14 are passed on the stack
+const PARAM_REGS: u8 = TEMP_REGS;
+/// Register 15 is never allocated and only holds values for a single instruction
+const SCRATCH: Reg = Reg::new(15);
+/// Largest offset of the `ldr` and `str` instructions
+const MAX_OFFSET: u32 = (1 << 10) - 1;
+
 #[derive(Debug)]
 pub struct DestructureLayer {
     coord_index: usize,
@@ -22,6 +37,10 @@ impl DestructureLayer {
         self.ops.push(Op::PutCoord { coord });
     }
 
+    pub fn put_span(&mut self, span: Span) {
+        self.ops.push(Op::PutSpan { span });
+    }
+
     pub fn branch(&mut self, coord: usize) {
         self.ops.push(Op::BranchCoord { coord });
     }
@@ -46,6 +65,72 @@ impl DestructureLayer {
         }
         Ok(layer)
     }
+
+    /// Lowers the layer into bare instructions.
+    ///
+    /// The first `params` variables are the function parameters which are passed as
+    /// described in [`Lowering::call`]. Variables are placed by [`allocate`].
+    pub fn lower(self, name: Option<String>, params: usize) -> LowerLayer {
+        let DestructureLayer {
+            coord_index,
+            vars,
+            ops,
+        } = self;
+        assert!(params <= vars.len());
+        let allocation = allocate(vars.len(), params, &ops);
+        let mut layer = LowerLayer::new(name, coord_index);
+        let exit = layer.alloc_coord();
+        let mut lowering = Lowering {
+            layer,
+            vars: &vars,
+            locations: allocation.locations,
+            saved: allocation.saved.len(),
+            exit,
+            temps: 0,
+            depth: 0,
+        };
+        let frame = (allocation.saved.len() + allocation.slots) as u32 * 8;
+        if frame > 0 {
+            lowering.push(LowOp::SubImmediate {
+                dst: STACK_POINTER,
+                lhs: STACK_POINTER,
+                rhs: frame,
+            });
+        }
+        for (i, reg) in allocation.saved.iter().enumerate() {
+            lowering.store_stack(*reg, i as u32 * 8);
+        }
+        for param in 0..params.min(PARAM_REGS as usize) {
+            lowering.write_var(param, Reg::new(param as u8));
+        }
+        // The register parameters are stored, so register 0 is free again
+        for param in PARAM_REGS as usize..params {
+            let offset = frame + (param - PARAM_REGS as usize) as u32 * 8;
+            lowering.load_stack(Reg::new(0), offset);
+            lowering.write_var(param, Reg::new(0));
+        }
+        for op in ops {
+            lowering.op(op);
+        }
+        if let Some(LowOp::BranchCoord { coord }) = lowering.layer.ops.last() {
+            if *coord == exit {
+                lowering.layer.ops.pop();
+            }
+        }
+        lowering.push(LowOp::PutCoord { coord: exit });
+        for (i, reg) in allocation.saved.iter().enumerate() {
+            lowering.load_stack(*reg, i as u32 * 8);
+        }
+        if frame > 0 {
+            lowering.push(LowOp::AddImmediate {
+                dst: STACK_POINTER,
+                lhs: STACK_POINTER,
+                rhs: frame,
+            });
+        }
+        lowering.push(LowOp::Return);
+        lowering.layer
+    }
 }
 
 impl Default for DestructureLayer {
@@ -63,6 +148,9 @@ pub enum Op {
     PutCoord {
         coord: usize,
     },
+    PutSpan {
+        span: Span,
+    },
     BranchCoord {
         coord: usize,
     },
@@ -132,3 +220,457 @@ impl Op {
         }
     }
 }
+
+struct Lowering<'a> {
+    layer: LowerLayer,
+    vars: &'a [Var],
+    locations: Vec<Location>,
+    /// Amount of persistent registers saved below the stack slots
+    saved: usize,
+    exit: usize,
+    temps: u8,
+    /// Bytes pushed onto the stack since the prologue
+    depth: u32,
+}
+
+impl Lowering<'_> {
+    fn push(&mut self, op: LowOp) {
+        self.layer.ops.push(op);
+    }
+
+    fn move_(&mut self, dst: Reg, src: Reg) {
+        if dst != src {
+            self.push(LowOp::Move { dst, src });
+        }
+    }
+
+    fn alloc_temp(&mut self) -> Reg {
+        assert!(self.temps < TEMP_REGS);
+        self.temps += 1;
+        Reg::new(self.temps - 1)
+    }
+
+    fn free_temps(&mut self, count: u8) {
+        self.temps -= count;
+    }
+
+    fn load_stack(&mut self, dst: Reg, offset: u32) {
+        if offset <= MAX_OFFSET {
+            self.push(LowOp::Load64 {
+                dst,
+                src: STACK_POINTER,
+                offset: offset as i32,
+            });
+            return;
+        }
+        self.push(LowOp::AddImmediate {
+            dst: SCRATCH,
+            lhs: STACK_POINTER,
+            rhs: offset,
+        });
+        self.push(LowOp::Load64 {
+            dst,
+            src: SCRATCH,
+            offset: 0,
+        });
+    }
+
+    fn store_stack(&mut self, src: Reg, offset: u32) {
+        if offset <= MAX_OFFSET {
+            self.push(LowOp::Store64 {
+                dst: STACK_POINTER,
+                src,
+                offset: offset as i32,
+            });
+            return;
+        }
+        self.push(LowOp::AddImmediate {
+            dst: SCRATCH,
+            lhs: STACK_POINTER,
+            rhs: offset,
+        });
+        self.push(LowOp::Store64 {
+            dst: SCRATCH,
+            src,
+            offset: 0,
+        });
+    }
+
+    fn slot_offset(&self, slot: usize) -> u32 {
+        (self.saved + slot) as u32 * 8 + self.depth
+    }
+
+    fn read_var(&mut self, index: usize, dst: Reg) {
+        match self.locations[index] {
+            Location::Reg(reg) => self.move_(dst, reg),
+            Location::Stack(slot) => self.load_stack(dst, self.slot_offset(slot)),
+        }
+    }
+
+    fn write_var(&mut self, index: usize, src: Reg) {
+        match self.locations[index] {
+            Location::Reg(reg) => self.move_(reg, src),
+            Location::Stack(slot) => self.store_stack(src, self.slot_offset(slot)),
+        }
+    }
+
+    /// Pushes the registers onto the stack.
+    fn save(&mut self, regs: &[Reg]) {
+        if regs.is_empty() {
+            return;
+        }
+        self.push(LowOp::SubImmediate {
+            dst: STACK_POINTER,
+            lhs: STACK_POINTER,
+            rhs: regs.len() as u32 * 8,
+        });
+        self.depth += regs.len() as u32 * 8;
+        for (i, reg) in regs.iter().enumerate() {
+            self.store_stack(*reg, i as u32 * 8);
+        }
+    }
+
+    /// Pops the registers pushed by [`Lowering::save`] from the stack.
+    fn restore(&mut self, regs: &[Reg]) {
+        if regs.is_empty() {
+            return;
+        }
+        for (i, reg) in regs.iter().enumerate() {
+            self.load_stack(*reg, i as u32 * 8);
+        }
+        self.push(LowOp::AddImmediate {
+            dst: STACK_POINTER,
+            lhs: STACK_POINTER,
+            rhs: regs.len() as u32 * 8,
+        });
+        self.depth -= regs.len() as u32 * 8;
+    }
+
+    fn op(&mut self, op: Op) {
+        match op {
+            Op::PutCoord { coord } => self.push(LowOp::PutCoord { coord }),
+            Op::PutSpan { span } => self.push(LowOp::PutSpan { span }),
+            Op::BranchCoord { coord } => self.push(LowOp::BranchCoord { coord }),
+            Op::BranchCoordIf {
+                coord,
+                op,
+                left,
+                right,
+            } => {
+                let type_ = left.type_(self.vars);
+                let lhs = self.alloc_temp();
+                self.expr(left, lhs);
+                let rhs = self.alloc_temp();
+                self.expr(right, rhs);
+                self.push(match type_ {
+                    Type::Int => LowOp::CompareSigned { dst: lhs, lhs, rhs },
+                    Type::Float => LowOp::CompareFloat { dst: lhs, lhs, rhs },
+                    Type::Unit | Type::UInt | Type::String => LowOp::Compare { dst: lhs, lhs, rhs },
+                });
+                self.free_temps(2);
+                let reg = lhs;
+                self.push(match op {
+                    CompareType::Equal => LowOp::BranchCoordEqual { reg, coord },
+                    CompareType::NotEqual => LowOp::BranchCoordNonEqual { reg, coord },
+                    CompareType::Less => LowOp::BranchCoordLess { reg, coord },
+                    CompareType::Greater => LowOp::BranchCoordGreater { reg, coord },
+                    CompareType::LessEqual => LowOp::BranchCoordLessEqual { reg, coord },
+                    CompareType::GreaterEqual => LowOp::BranchCoordGreaterEqual { reg, coord },
+                });
+            }
+            Op::Let { index, expr } | Op::Assign { index, expr } => {
+                if let (Location::Reg(reg), true) = (self.locations[index], expr.is_leaf()) {
+                    self.expr(expr, reg);
+                    return;
+                }
+                let temp = self.alloc_temp();
+                self.expr(expr, temp);
+                self.write_var(index, temp);
+                self.free_temps(1);
+            }
+            Op::Return { expr } => {
+                if let Some(expr) = expr {
+                    let reg = self.alloc_temp();
+                    self.expr(expr, reg);
+                    self.free_temps(1);
+                }
+                self.push(LowOp::BranchCoord { coord: self.exit });
+            }
+            Op::Call { coord, params } => self.call(coord, params, None),
+        }
+    }
+
+    fn expr(&mut self, expr: Expr, dst: Reg) {
+        let type_ = expr.type_(self.vars);
+        match expr {
+            Expr::Static { coord, .. } => self.push(LowOp::LoadStatic64 { dst, coord }),
+            Expr::StaticAddress { coord, .. } => self.push(LowOp::LoadStaticAddress { dst, coord }),
+            Expr::Variable { index, .. } => self.read_var(index, dst),
+            Expr::Int { value, .. } => {
+                if (-(1 << 21)..(1 << 21)).contains(&value) {
+                    self.push(LowOp::MoveSignedImmediate {
+                        dst,
+                        immediate: value as i32,
+                    });
+                } else {
+                    self.load_local(dst, BinaryStatic::Int { name: None, value });
+                }
+            }
+            Expr::UInt { value, .. } => {
+                if value < (1 << 22) {
+                    self.push(LowOp::MoveImmediate {
+                        dst,
+                        immediate: value as u32,
+                    });
+                } else {
+                    self.load_local(dst, BinaryStatic::UInt { name: None, value });
+                }
+            }
+            Expr::Float { value, .. } => {
+                if value == 0.0 {
+                    self.push(LowOp::Zero { dst });
+                } else {
+                    self.load_local(dst, BinaryStatic::Float { name: None, value });
+                }
+            }
+            Expr::String { value, .. } => {
+                self.layer
+                    .locals
+                    .push(BinaryStatic::String { name: None, value });
+                self.push(LowOp::LoadLocalStaticAddress {
+                    dst,
+                    coord: self.layer.locals.len() - 1,
+                });
+            }
+            Expr::CastInt { expr, .. } | Expr::CastUInt { expr, .. } => {
+                let from = expr.type_(self.vars);
+                self.expr(*expr, dst);
+                if from == Type::Float {
+                    self.push(LowOp::FloatToInt { dst, src: dst });
+                }
+            }
+            Expr::CastFloat { expr, .. } => {
+                let from = expr.type_(self.vars);
+                self.expr(*expr, dst);
+                if from != Type::Float {
+                    self.push(LowOp::IntToFloat { dst, src: dst });
+                }
+            }
+            Expr::Add { left, right, .. } => {
+                self.binary(BinaryOpType::Add, type_, *left, *right, dst)
+            }
+            Expr::Sub { left, right, .. } => {
+                self.binary(BinaryOpType::Sub, type_, *left, *right, dst)
+            }
+            Expr::Mul { left, right, .. } => {
+                self.binary(BinaryOpType::Mul, type_, *left, *right, dst)
+            }
+            Expr::Div { left, right, .. } => {
+                self.binary(BinaryOpType::Div, type_, *left, *right, dst)
+            }
+            Expr::Rem { left, right, .. } => {
+                self.binary(BinaryOpType::Rem, type_, *left, *right, dst)
+            }
+            Expr::BitAnd { left, right, .. } => {
+                self.binary(BinaryOpType::BitAnd, type_, *left, *right, dst)
+            }
+            Expr::BitOr { left, right, .. } => {
+                self.binary(BinaryOpType::BitOr, type_, *left, *right, dst)
+            }
+            Expr::BitXor { left, right, .. } => {
+                self.binary(BinaryOpType::BitXor, type_, *left, *right, dst)
+            }
+            Expr::ShiftLeft { left, right, .. } => {
+                self.binary(BinaryOpType::ShiftLeft, type_, *left, *right, dst)
+            }
+            Expr::ShiftRight { left, right, .. } => {
+                self.binary(BinaryOpType::ShiftRight, type_, *left, *right, dst)
+            }
+            Expr::BitNot { expr, .. } => {
+                self.expr(*expr, dst);
+                self.push(LowOp::Not { dst, src: dst });
+            }
+            Expr::Call { coord, params, .. } => self.call(coord, params, Some(dst)),
+        }
+    }
+
+    fn load_local(&mut self, dst: Reg, value: BinaryStatic) {
+        self.layer.locals.push(value);
+        self.push(LowOp::LoadLocalStatic64 {
+            dst,
+            coord: self.layer.locals.len() - 1,
+        });
+    }
+
+    fn binary(&mut self, op: BinaryOpType, type_: Type, left: Expr, right: Expr, dst: Reg) {
+        self.expr(left, dst);
+        if type_ != Type::Float {
+            if let Some(op) = immediate_op(&op, type_, &right, dst) {
+                self.push(op);
+                return;
+            }
+        }
+        let spill = self.temps == TEMP_REGS;
+        let rhs = if spill {
+            // Keep the left operand on the stack while evaluating the right one
+            self.save(&[dst]);
+            self.expr(right, dst);
+            self.move_(SCRATCH, dst);
+            self.restore(&[dst]);
+            SCRATCH
+        } else {
+            let rhs = self.alloc_temp();
+            self.expr(right, rhs);
+            rhs
+        };
+        let lhs = dst;
+        self.push(match (op, type_) {
+            (BinaryOpType::Add, Type::Float) => LowOp::AddFloat { dst, lhs, rhs },
+            (BinaryOpType::Sub, Type::Float) => LowOp::SubFloat { dst, lhs, rhs },
+            (BinaryOpType::Mul, Type::Float) => LowOp::MulFloat { dst, lhs, rhs },
+            (BinaryOpType::Div, Type::Float) => LowOp::DivFloat { dst, lhs, rhs },
+            (BinaryOpType::Rem, Type::Float) => LowOp::RemFloat { dst, lhs, rhs },
+            (BinaryOpType::Div, Type::Int) => LowOp::DivSigned { dst, lhs, rhs },
+            (BinaryOpType::Rem, Type::Int) => LowOp::RemSigned { dst, lhs, rhs },
+            (BinaryOpType::ShiftRight, Type::Int) => LowOp::ShiftRightSigned { dst, lhs, rhs },
+            (BinaryOpType::Add, _) => LowOp::Add { dst, lhs, rhs },
+            (BinaryOpType::Sub, _) => LowOp::Sub { dst, lhs, rhs },
+            (BinaryOpType::Mul, _) => LowOp::Mul { dst, lhs, rhs },
+            (BinaryOpType::Div, _) => LowOp::Div { dst, lhs, rhs },
+            (BinaryOpType::Rem, _) => LowOp::Rem { dst, lhs, rhs },
+            (BinaryOpType::BitAnd, _) => LowOp::And { dst, lhs, rhs },
+            (BinaryOpType::BitOr, _) => LowOp::Or { dst, lhs, rhs },
+            (BinaryOpType::BitXor, _) => LowOp::Xor { dst, lhs, rhs },
+            (BinaryOpType::ShiftLeft, _) => LowOp::ShiftLeft { dst, lhs, rhs },
+            (BinaryOpType::ShiftRight, _) => LowOp::ShiftRight { dst, lhs, rhs },
+        });
+        if !spill {
+            self.free_temps(1);
+        }
+    }
+
+    /// Calls a function as described in `ABI.md`.
+    ///
+    /// The first parameters are passed in the registers 0 to 14, the remaining ones are copied
+    /// onto the stack in last to first order so the first of them ends up on top. The callee
+    /// leaves them there and returns its value in register 0.
+    ///
+    /// All temporary registers below `dst` are saved on the stack during the call.
+    fn call(&mut self, coord: Coord, params: Vec<Expr>, dst: Option<Reg>) {
+        let temps = self.temps;
+        let live = if let Some(dst) = dst {
+            assert_eq!(dst.value() + 1, temps as u32);
+            temps - 1
+        } else {
+            temps
+        };
+        let saved = (0..live).map(Reg::new).collect::<Vec<_>>();
+        self.save(&saved);
+        self.temps = 0;
+        let mut params = params.into_iter();
+        let reg_params = params
+            .by_ref()
+            .take(PARAM_REGS as usize)
+            .collect::<Vec<_>>();
+        let stack_params = params.collect::<Vec<_>>();
+        let stack_size = stack_params.len() as u32 * 8;
+        if stack_size > 0 {
+            self.push(LowOp::SubImmediate {
+                dst: STACK_POINTER,
+                lhs: STACK_POINTER,
+                rhs: stack_size,
+            });
+            self.depth += stack_size;
+        }
+        for (i, param) in stack_params.into_iter().enumerate() {
+            let reg = self.alloc_temp();
+            self.expr(param, reg);
+            self.store_stack(reg, i as u32 * 8);
+            self.free_temps(1);
+        }
+        for param in reg_params {
+            let reg = self.alloc_temp();
+            self.expr(param, reg);
+        }
+        self.temps = temps;
+        self.push(LowOp::Call { coord });
+        if stack_size > 0 {
+            self.push(LowOp::AddImmediate {
+                dst: STACK_POINTER,
+                lhs: STACK_POINTER,
+                rhs: stack_size,
+            });
+            self.depth -= stack_size;
+        }
+        if let Some(dst) = dst {
+            self.move_(dst, Reg::new(0));
+        }
+        self.restore(&saved);
+    }
+}
+
+/// Returns the immediate variant of an operation if `right` is a small enough constant.
+fn immediate_op(op: &BinaryOpType, type_: Type, right: &Expr, dst: Reg) -> Option<LowOp> {
+    let value = match right {
+        Expr::Int { value, .. } if *value >= 0 => *value as u64,
+        Expr::UInt { value, .. } => *value,
+        _ => return None,
+    };
+    let lhs = dst;
+    let op = match op {
+        BinaryOpType::Add if value < 1 << 17 => LowOp::AddImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        BinaryOpType::Sub if value < 1 << 17 => LowOp::SubImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        BinaryOpType::Mul if value < 1 << 17 => LowOp::MulImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        BinaryOpType::Div if type_ == Type::Int && value < 1 << 16 => LowOp::DivSignedImmediate {
+            dst,
+            lhs,
+            rhs: value as i32,
+        },
+        BinaryOpType::Rem if type_ == Type::Int && value < 1 << 16 => LowOp::RemSignedImmediate {
+            dst,
+            lhs,
+            rhs: value as i32,
+        },
+        BinaryOpType::Div if value < 1 << 17 => LowOp::DivImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        BinaryOpType::Rem if value < 1 << 17 => LowOp::RemImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        BinaryOpType::ShiftLeft if value < 64 => LowOp::ShiftLeftImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        BinaryOpType::ShiftRight if type_ == Type::Int && value < 64 => {
+            LowOp::ShiftRightSignedImmediate {
+                dst,
+                lhs,
+                rhs: value as u32,
+            }
+        }
+        BinaryOpType::ShiftRight if value < 64 => LowOp::ShiftRightImmediate {
+            dst,
+            lhs,
+            rhs: value as u32,
+        },
+        _ => return None,
+    };
+    Some(op)
+}
@@ -1,4 +1,7 @@
-use crate::binary::{BinaryFunc, BinaryStatic};
+use crate::{
+    binary::{BinaryFunc, BinaryStatic},
+    Span,
+};
 
 use super::Coord;
 
@@ -10,6 +13,14 @@ pub struct LowerLayer {
 }
 
 impl LowerLayer {
+    pub fn new(name: Option<String>, coord_index: usize) -> Self {
+        Self {
+            coord_index,
+            name,
+            ..Default::default()
+        }
+    }
+
     pub fn alloc_coord(&mut self) -> usize {
         let coord = self.coord_index;
         self.coord_index += 1;
@@ -36,7 +47,7 @@ impl Default for LowerLayer {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Reg {
     value: u8,
 }
@@ -53,9 +64,11 @@ impl Reg {
     }
 }
 
+/// `PutSpan` marks the following ops as produced by the source code at its span.
 #[derive(Debug)]
 pub enum LowOp {
     PutCoord { coord: usize },
+    PutSpan { span: Span },
     BranchCoord { coord: usize },
     BranchCoordIfNonZero { reg: Reg, coord: usize },
     BranchCoordIfZero { reg: Reg, coord: usize },
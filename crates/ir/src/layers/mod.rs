@@ -2,12 +2,13 @@ use crate::Span;
 
 use self::upper::Expr;
 
+pub mod alloc;
 pub mod destructure;
 pub mod error;
 pub mod lower;
 pub mod upper;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Coord {
     pub module: usize,
     pub element: usize,
@@ -20,7 +21,7 @@ pub enum NextCoord {
     Unknown,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Type {
     Unit,
     Int,
@@ -92,9 +93,13 @@ mod test {
         io::{stderr, Write},
     };
 
-    use crate::layers::upper::{Cond, Expr, Stmnt};
+    use crate::layers::upper::{Cond, Expr, Stmnt, Var};
 
-    use super::upper::{Block, UpperLayer};
+    use super::{
+        alloc::{allocate, Location},
+        upper::{Block, UpperLayer},
+        Type,
+    };
 
     fn block(elements: Vec<Stmnt>) -> Block {
         Block {
@@ -207,4 +212,45 @@ mod test {
         let layer = layer.destructure().const_eval().expect("consteval");
         debug(&layer);
     }
+
+    #[test]
+    fn test_alloc() {
+        let vars = (0..20)
+            .map(|_| Var { type_: Type::Int })
+            .collect::<Vec<_>>();
+        let mut elements = (0..20)
+            .map(|index| Stmnt::Let {
+                span: 0..0,
+                index,
+                expr: num(index as i64),
+            })
+            .collect::<Vec<_>>();
+        for index in 0..20 {
+            elements.push(Stmnt::Assign {
+                span: 0..0,
+                index,
+                expr: add(Expr::Variable { span: 0..0, index }, num(1)),
+            });
+        }
+        let layer = UpperLayer::Block {
+            vars,
+            block: block(elements),
+        };
+        let layer = layer.destructure().const_eval().expect("consteval");
+        let allocation = allocate(layer.vars.len(), 0, &layer.ops);
+        assert_eq!(allocation.saved.len(), 15);
+        assert_eq!(allocation.slots, 5);
+        let mut regs = allocation
+            .locations
+            .iter()
+            .filter_map(|location| match location {
+                Location::Reg(reg) => Some(reg.value()),
+                Location::Stack(_) => None,
+            })
+            .collect::<Vec<_>>();
+        regs.sort();
+        regs.dedup();
+        assert_eq!(regs.len(), 15);
+        debug(&layer.lower(None, 0).ops);
+    }
 }
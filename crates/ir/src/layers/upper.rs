@@ -9,7 +9,7 @@ use super::{
 
 #[derive(Debug)]
 pub enum UpperLayer {
-    Expr { expr: Expr },
+    Expr { vars: Vec<Var>, expr: Expr },
     Block { vars: Vec<Var>, block: Block },
 }
 
@@ -17,7 +17,9 @@ impl UpperLayer {
     pub fn destructure(self) -> DestructureLayer {
         let mut layer = DestructureLayer::default();
         match self {
-            UpperLayer::Expr { expr } => {
+            UpperLayer::Expr { vars, expr } => {
+                layer.vars = vars;
+                layer.put_span(expr.span());
                 layer.ops.push(Op::Return { expr: Some(expr) });
             }
             UpperLayer::Block { vars, block } => {
@@ -62,7 +64,10 @@ pub enum Stmnt {
     },
     For {
         span: Span,
-        // TODO
+        init: Box<Stmnt>,
+        cond: Cond,
+        step: Box<Stmnt>,
+        block: Block,
     },
     Let {
         span: Span,
@@ -91,6 +96,7 @@ impl Stmnt {
             Stmnt::If { cond, block, .. } => {
                 let success = layer.alloc_coord();
                 let failure = layer.alloc_coord();
+                layer.put_span(cond.span());
                 cond.expand(layer, success, failure, NextCoord::Success);
                 layer.put_coord(success);
                 block.expand(layer);
@@ -104,14 +110,50 @@ impl Stmnt {
                 layer.put_coord(success);
                 block.expand(layer);
                 layer.put_coord(check);
+                layer.put_span(cond.span());
                 cond.expand(layer, success, failure, NextCoord::Failure);
                 layer.put_coord(failure);
             }
-            Stmnt::For { span: _ } => todo!(),
-            Stmnt::Let { index, expr, .. } => layer.ops.push(Op::Let { index, expr }),
-            Stmnt::Return { expr, .. } => layer.ops.push(Op::Return { expr }),
-            Stmnt::Assign { index, expr, .. } => layer.ops.push(Op::Assign { index, expr }),
-            Stmnt::Call { coord, params, .. } => layer.ops.push(Op::Call { coord, params }),
+            Stmnt::For {
+                init,
+                cond,
+                step,
+                block,
+                ..
+            } => {
+                let check = layer.alloc_coord();
+                let success = layer.alloc_coord();
+                let failure = layer.alloc_coord();
+                init.expand(layer);
+                layer.branch(check);
+                layer.put_coord(success);
+                block.expand(layer);
+                step.expand(layer);
+                layer.put_coord(check);
+                layer.put_span(cond.span());
+                cond.expand(layer, success, failure, NextCoord::Failure);
+                layer.put_coord(failure);
+            }
+            Stmnt::Let { span, index, expr } => {
+                layer.put_span(span);
+                layer.ops.push(Op::Let { index, expr });
+            }
+            Stmnt::Return { span, expr } => {
+                layer.put_span(span);
+                layer.ops.push(Op::Return { expr });
+            }
+            Stmnt::Assign { span, index, expr } => {
+                layer.put_span(span);
+                layer.ops.push(Op::Assign { index, expr });
+            }
+            Stmnt::Call {
+                span,
+                coord,
+                params,
+            } => {
+                layer.put_span(span);
+                layer.ops.push(Op::Call { coord, params });
+            }
         }
     }
 }
@@ -121,6 +163,12 @@ pub enum Expr {
     Static {
         span: Span,
         coord: Coord,
+        type_: Type,
+    },
+    StaticAddress {
+        span: Span,
+        coord: Coord,
+        type_: Type,
     },
     Variable {
         span: Span,
@@ -212,6 +260,7 @@ pub enum Expr {
         span: Span,
         coord: Coord,
         params: Vec<Expr>,
+        type_: Type,
     },
 }
 
@@ -219,6 +268,7 @@ impl Expr {
     pub fn span(&self) -> Span {
         match self {
             Expr::Static { span, .. }
+            | Expr::StaticAddress { span, .. }
             | Expr::Variable { span, .. }
             | Expr::Int { span, .. }
             | Expr::UInt { span, .. }
@@ -242,6 +292,47 @@ impl Expr {
         }
     }
 
+    pub fn type_(&self, vars: &[Var]) -> Type {
+        match self {
+            Expr::Static { type_, .. }
+            | Expr::StaticAddress { type_, .. }
+            | Expr::Call { type_, .. } => *type_,
+            Expr::Variable { index, .. } => vars[*index].type_,
+            Expr::Int { .. } | Expr::CastInt { .. } => Type::Int,
+            Expr::UInt { .. } | Expr::CastUInt { .. } => Type::UInt,
+            Expr::Float { .. } | Expr::CastFloat { .. } => Type::Float,
+            Expr::String { .. } => Type::String,
+            Expr::Add { left, .. }
+            | Expr::Sub { left, .. }
+            | Expr::Mul { left, .. }
+            | Expr::Div { left, .. }
+            | Expr::Rem { left, .. }
+            | Expr::BitAnd { left, .. }
+            | Expr::BitOr { left, .. }
+            | Expr::BitXor { left, .. }
+            | Expr::ShiftLeft { left, .. }
+            | Expr::ShiftRight { left, .. } => left.type_(vars),
+            Expr::BitNot { expr, .. } => expr.type_(vars),
+        }
+    }
+
+    /// Returns the operator and operands of a binary operation.
+    pub fn binary_op(&self) -> Option<(BinaryOpType, &Expr, &Expr)> {
+        match self {
+            Expr::Add { left, right, .. } => Some((BinaryOpType::Add, left, right)),
+            Expr::Sub { left, right, .. } => Some((BinaryOpType::Sub, left, right)),
+            Expr::Mul { left, right, .. } => Some((BinaryOpType::Mul, left, right)),
+            Expr::Div { left, right, .. } => Some((BinaryOpType::Div, left, right)),
+            Expr::Rem { left, right, .. } => Some((BinaryOpType::Rem, left, right)),
+            Expr::BitAnd { left, right, .. } => Some((BinaryOpType::BitAnd, left, right)),
+            Expr::BitOr { left, right, .. } => Some((BinaryOpType::BitOr, left, right)),
+            Expr::BitXor { left, right, .. } => Some((BinaryOpType::BitXor, left, right)),
+            Expr::ShiftLeft { left, right, .. } => Some((BinaryOpType::ShiftLeft, left, right)),
+            Expr::ShiftRight { left, right, .. } => Some((BinaryOpType::ShiftRight, left, right)),
+            _ => None,
+        }
+    }
+
     pub fn is_const(&self) -> bool {
         matches!(
             self,
@@ -249,6 +340,14 @@ impl Expr {
         )
     }
 
+    pub fn is_leaf(&self) -> bool {
+        self.is_const()
+            || matches!(
+                self,
+                Expr::Static { .. } | Expr::StaticAddress { .. } | Expr::Variable { .. }
+            )
+    }
+
     pub fn const_type_match(&self, other: &Self) -> bool {
         if !self.is_const() || !other.is_const() {
             return false;
@@ -380,6 +479,7 @@ impl Expr {
                 span,
                 coord,
                 params,
+                type_,
             } => {
                 let mut new_params = Vec::with_capacity(params.len());
                 for param in params {
@@ -389,6 +489,7 @@ impl Expr {
                     span,
                     coord,
                     params: new_params,
+                    type_,
                 })
             }
             other => Ok(other),
@@ -531,6 +632,33 @@ impl Cond {
         }
     }
 
+    pub fn span(&self) -> Span {
+        match self {
+            Cond::Equal { span, .. }
+            | Cond::NotEqual { span, .. }
+            | Cond::Less { span, .. }
+            | Cond::Greater { span, .. }
+            | Cond::LessEqual { span, .. }
+            | Cond::GreaterEqual { span, .. }
+            | Cond::Not { span, .. }
+            | Cond::And { span, .. }
+            | Cond::Or { span, .. } => span.clone(),
+        }
+    }
+
+    /// Returns the operands of a comparison.
+    pub fn operands(&self) -> Option<(&Expr, &Expr)> {
+        match self {
+            Cond::Equal { left, right, .. }
+            | Cond::NotEqual { left, right, .. }
+            | Cond::Less { left, right, .. }
+            | Cond::Greater { left, right, .. }
+            | Cond::LessEqual { left, right, .. }
+            | Cond::GreaterEqual { left, right, .. } => Some((left, right)),
+            _ => None,
+        }
+    }
+
     pub fn is_comparison(&self) -> bool {
         !matches!(self, Cond::Not { .. } | Cond::And { .. } | Cond::Or { .. })
     }
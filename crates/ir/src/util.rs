@@ -1,27 +1,58 @@
-/// TODO: return result
-/// error if not in range
+use std::io::{Error, ErrorKind, Result};
+
+/// Cuts a value down to its lowest `bits` bits.
+///
+/// Unsigned values have to be smaller than `2^bits` and signed values have to be in the
+/// range of a `bits` wide two's complement number, otherwise an error is returned.
 pub trait MaxBitsU32 {
-    fn cut(&self, bits: usize) -> u32;
+    fn cut(&self, bits: usize) -> Result<u32>;
+
+    fn fits(&self, bits: usize) -> bool;
 }
 
 impl MaxBitsU32 for u32 {
-    fn cut(&self, bits: usize) -> Self {
-        self & ((1 << bits) - 1)
+    fn cut(&self, bits: usize) -> Result<u32> {
+        if !self.fits(bits) {
+            return Err(out_of_range(*self as i64, bits));
+        }
+        Ok(self & ((1 << bits) - 1))
+    }
+
+    fn fits(&self, bits: usize) -> bool {
+        (*self as u64) < 1 << bits
     }
 }
 
 impl MaxBitsU32 for i32 {
-    fn cut(&self, bits: usize) -> u32 {
-        (self & ((1 << bits) - 1)) as u32
+    fn cut(&self, bits: usize) -> Result<u32> {
+        (*self as isize).cut(bits)
+    }
+
+    fn fits(&self, bits: usize) -> bool {
+        (*self as isize).fits(bits)
     }
 }
 
 impl MaxBitsU32 for isize {
-    fn cut(&self, bits: usize) -> u32 {
-        (self & ((1 << bits) - 1)) as u32
+    fn cut(&self, bits: usize) -> Result<u32> {
+        if !self.fits(bits) {
+            return Err(out_of_range(*self as i64, bits));
+        }
+        Ok((self & ((1 << bits) - 1)) as u32)
+    }
+
+    fn fits(&self, bits: usize) -> bool {
+        (-(1 << (bits - 1))..1 << (bits - 1)).contains(self)
     }
 }
 
+fn out_of_range(value: i64, bits: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("The value {value} does not fit into {bits} bits"),
+    )
+}
+
 pub const fn alignment(size: usize, align: usize) -> usize {
     if size % align == 0 {
         0